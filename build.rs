@@ -0,0 +1,25 @@
+// SPDX-License-Identifier: Apache-2.0
+use std::{env, process::Command};
+
+// Exposes the commit being built as GIT_COMMIT. Docker builds pass it through the
+// VCS_REF build argument since the image build context has no git history.
+fn main() {
+    println!("cargo:rerun-if-env-changed=VCS_REF");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+
+    let commit = env::var("VCS_REF")
+        .ok()
+        .filter(|s| !s.is_empty() && s != "unknown")
+        .or_else(|| {
+            Command::new("git")
+                .args(["rev-parse", "--short=12", "HEAD"])
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .and_then(|output| String::from_utf8(output.stdout).ok())
+                .map(|s| s.trim().to_string())
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+
+    println!("cargo:rustc-env=GIT_COMMIT={}", commit);
+}
@@ -0,0 +1,359 @@
+// SPDX-License-Identifier: Apache-2.0
+use crate::error::SubgraphError;
+use crate::policy::DeploymentTarget;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    future::Future,
+    io::Write,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+const REDACTED: &str = "<redacted>";
+
+/// Whether a tool's calls are written to the audit log.
+pub fn is_audited_tool(tool_name: &str) -> bool {
+    tool_name.starts_with("execute_query_")
+        || tool_name.starts_with("get_schema_")
+        || tool_name == "export_query"
+}
+
+/// Returns the hex SHA-256 of an API key, so entries can be attributed to a key
+/// without storing the key itself.
+pub fn hash_api_key(api_key: &str) -> String {
+    Sha256::digest(api_key.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Which literals are replaced before a query and its variables are logged.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Redaction {
+    None,
+    /// String literals, which may carry addresses, names or other user input.
+    #[default]
+    Strings,
+    /// String and numeric literals.
+    All,
+}
+
+impl Redaction {
+    pub fn from_env() -> Self {
+        match env::var("AUDIT_REDACT_LITERALS")
+            .map(|v| v.to_lowercase())
+            .as_deref()
+        {
+            Ok("none") | Ok("false") => Redaction::None,
+            Ok("all") => Redaction::All,
+            _ => Redaction::Strings,
+        }
+    }
+
+    /// Replaces string (and, with `All`, numeric) literals in a GraphQL query.
+    /// Comments are dropped since they can hold anything.
+    pub fn redact_query(&self, query: &str) -> String {
+        if *self == Redaction::None {
+            return query.to_string();
+        }
+
+        let chars: Vec<char> = query.chars().collect();
+        let mut out = String::with_capacity(query.len());
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            if c == '#' {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            } else if c == '"' {
+                let block = chars[i..].starts_with(&['"', '"', '"']);
+                i += if block { 3 } else { 1 };
+                while i < chars.len() {
+                    if chars[i] == '\\' {
+                        i += 2;
+                    } else if block && chars[i..].starts_with(&['"', '"', '"']) {
+                        i += 3;
+                        break;
+                    } else if !block && (chars[i] == '"' || chars[i] == '\n') {
+                        i += 1;
+                        break;
+                    } else {
+                        i += 1;
+                    }
+                }
+                out.push('"');
+                out.push_str(REDACTED);
+                out.push('"');
+            } else if *self == Redaction::All
+                && (c.is_ascii_digit()
+                    || (c == '-' && chars.get(i + 1).is_some_and(|n| n.is_ascii_digit())))
+                && !out
+                    .chars()
+                    .last()
+                    .is_some_and(|p| p.is_alphanumeric() || p == '_')
+            {
+                i += 1;
+                while i < chars.len()
+                    && (chars[i].is_ascii_alphanumeric() || matches!(chars[i], '.' | '+' | '-'))
+                {
+                    i += 1;
+                }
+                out.push('0');
+            } else {
+                out.push(c);
+                i += 1;
+            }
+        }
+        out
+    }
+
+    /// Applies the same redaction to the values of a variables object.
+    pub fn redact_value(&self, value: &serde_json::Value) -> serde_json::Value {
+        use serde_json::Value;
+        match (self, value) {
+            (Redaction::None, _) => value.clone(),
+            (_, Value::String(_)) => Value::String(REDACTED.to_string()),
+            (Redaction::All, Value::Number(_)) => Value::from(0),
+            (_, Value::Array(items)) => {
+                Value::Array(items.iter().map(|v| self.redact_value(v)).collect())
+            }
+            (_, Value::Object(map)) => Value::Object(
+                map.iter()
+                    .map(|(k, v)| (k.clone(), self.redact_value(v)))
+                    .collect(),
+            ),
+            _ => value.clone(),
+        }
+    }
+}
+
+/// One audited tool call.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct AuditEntry {
+    /// RFC 3339 time at which the call completed.
+    pub timestamp: String,
+    pub request_id: String,
+    pub session_id: String,
+    pub api_key_hash: Option<String>,
+    pub tool: String,
+    /// The deployment identifier passed by the caller.
+    pub deployment: Option<String>,
+    pub ipfs_hash: Option<String>,
+    pub subgraph_id: Option<String>,
+    pub network: Option<String>,
+    pub query: Option<String>,
+    pub variables: Option<serde_json::Value>,
+    pub response_bytes: usize,
+    pub fee_grt: Option<f64>,
+    pub status: &'static str,
+    pub error: Option<String>,
+    pub latency_ms: u64,
+}
+
+/// Destination for audit entries. Implementations must not drop entries silently;
+/// errors are logged by the caller.
+pub trait AuditSink: Send + Sync {
+    fn record(&self, entry: &AuditEntry) -> Result<(), SubgraphError>;
+}
+
+/// Appends entries as newline-delimited JSON, rotating the file once it reaches
+/// `max_bytes`. Rotated files are renamed `<path>.1` (newest) up to `<path>.<max_files>`.
+pub struct NdjsonFileSink {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: Mutex<Option<(File, u64)>>,
+}
+
+impl NdjsonFileSink {
+    pub fn new(path: impl Into<PathBuf>, max_bytes: u64, max_files: usize) -> Self {
+        Self {
+            path: path.into(),
+            max_bytes,
+            max_files,
+            file: Mutex::new(None),
+        }
+    }
+
+    fn open(&self) -> std::io::Result<(File, u64)> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let size = file.metadata()?.len();
+        Ok((file, size))
+    }
+
+    fn rotate(&self) -> std::io::Result<()> {
+        let rotated = |n: usize| PathBuf::from(format!("{}.{}", self.path.display(), n));
+        if self.max_files == 0 {
+            return fs::remove_file(&self.path);
+        }
+        let _ = fs::remove_file(rotated(self.max_files));
+        for n in (1..self.max_files).rev() {
+            if rotated(n).exists() {
+                fs::rename(rotated(n), rotated(n + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated(1))
+    }
+}
+
+impl AuditSink for NdjsonFileSink {
+    fn record(&self, entry: &AuditEntry) -> Result<(), SubgraphError> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+
+        let io_error = |e: std::io::Error| {
+            SubgraphError::InternalProcessingError(format!(
+                "Failed to write audit log {}: {}",
+                self.path.display(),
+                e
+            ))
+        };
+
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if file.is_none() {
+            *file = Some(self.open().map_err(io_error)?);
+        }
+        let size = file.as_ref().map_or(0, |(_, size)| *size);
+        if size > 0 && size + line.len() as u64 > self.max_bytes {
+            *file = None;
+            self.rotate().map_err(io_error)?;
+            *file = Some(self.open().map_err(io_error)?);
+        }
+
+        let (handle, size) = file.as_mut().expect("audit log file is open");
+        handle.write_all(&line).map_err(io_error)?;
+        handle.flush().map_err(io_error)?;
+        *size += line.len() as u64;
+        Ok(())
+    }
+}
+
+/// Builds audit entries from tool calls, redacts them and hands them to a sink.
+pub struct AuditLogger {
+    sink: Arc<dyn AuditSink>,
+    redaction: Redaction,
+}
+
+/// What the server knows about an audited call once it has completed.
+pub struct AuditedCall<'a> {
+    pub request_id: &'a str,
+    pub session_id: &'a str,
+    pub api_key: Option<&'a str>,
+    pub tool: &'a str,
+    pub arguments: Option<&'a serde_json::Map<String, serde_json::Value>>,
+    pub details: AuditDetails,
+    pub response_bytes: usize,
+    pub error: Option<String>,
+    pub latency: Duration,
+}
+
+impl AuditLogger {
+    pub fn new(sink: Arc<dyn AuditSink>, redaction: Redaction) -> Self {
+        Self { sink, redaction }
+    }
+
+    pub fn entry(&self, call: AuditedCall<'_>) -> AuditEntry {
+        let argument = |name: &str| call.arguments.and_then(|args| args.get(name));
+        let deployment = ["deployment_id", "ipfs_hash", "subgraph_id", "deployment"]
+            .iter()
+            .find_map(|name| argument(name).and_then(|v| v.as_str()))
+            .map(str::to_string);
+        let target = call.details.deployment.unwrap_or_default();
+
+        AuditEntry {
+            timestamp: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            request_id: call.request_id.to_string(),
+            session_id: call.session_id.to_string(),
+            api_key_hash: call.api_key.map(hash_api_key),
+            tool: call.tool.to_string(),
+            deployment,
+            ipfs_hash: target.ipfs_hash,
+            subgraph_id: target.subgraph_id,
+            network: target.network,
+            query: argument("query")
+                .and_then(|v| v.as_str())
+                .map(|query| self.redaction.redact_query(query)),
+            variables: argument("variables")
+                .filter(|v| !v.is_null())
+                .map(|v| self.redaction.redact_value(v)),
+            response_bytes: call.response_bytes,
+            fee_grt: call.details.fee_grt,
+            status: if call.error.is_none() {
+                "success"
+            } else {
+                "error"
+            },
+            error: call.error,
+            latency_ms: call.latency.as_millis() as u64,
+        }
+    }
+
+    /// Writes the entry for `call`. A failing sink is logged rather than failing the call.
+    pub fn record(&self, call: AuditedCall<'_>) {
+        let entry = self.entry(call);
+        if let Err(e) = self.sink.record(&entry) {
+            tracing::error!(target: "mcp_audit", error = %e, tool = %entry.tool, "Failed to write audit entry");
+        }
+    }
+}
+
+/// Audit log configured through `AUDIT_LOG_FILE`, shared by every session.
+pub static AUDIT_LOGGER: Lazy<Option<Arc<AuditLogger>>> = Lazy::new(|| {
+    let path = env::var("AUDIT_LOG_FILE").ok().filter(|s| !s.is_empty())?;
+    let max_bytes = env::var("AUDIT_LOG_MAX_BYTES")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(100 * 1024 * 1024);
+    let max_files = env::var("AUDIT_LOG_MAX_FILES")
+        .ok()
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(5);
+    tracing::info!(target: "mcp_audit", path = %path, max_bytes, max_files, "Writing audit log");
+    Some(Arc::new(AuditLogger::new(
+        Arc::new(NdjsonFileSink::new(path, max_bytes, max_files)),
+        Redaction::from_env(),
+    )))
+});
+
+/// Details learned while a tool runs that the audit entry needs.
+#[derive(Clone, Debug, Default)]
+pub struct AuditDetails {
+    pub deployment: Option<DeploymentTarget>,
+    pub fee_grt: Option<f64>,
+}
+
+tokio::task_local! {
+    static AUDIT_DETAILS: Arc<Mutex<AuditDetails>>;
+}
+
+/// Runs `f`, collecting the details noted by the tool for its audit entry.
+pub async fn collect_audit_details<F: Future>(f: F) -> (F::Output, AuditDetails) {
+    let details = Arc::new(Mutex::new(AuditDetails::default()));
+    let output = AUDIT_DETAILS.scope(details.clone(), f).await;
+    let details = details.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    (output, details)
+}
+
+fn note(update: impl FnOnce(&mut AuditDetails)) {
+    let _ = AUDIT_DETAILS
+        .try_with(|details| update(&mut details.lock().unwrap_or_else(|e| e.into_inner())));
+}
+
+/// Records the deployment a call resolved to, when its audit details are collected.
+pub fn note_deployment(target: &DeploymentTarget) {
+    note(|details| details.deployment = Some(target.clone()));
+}
+
+/// Records the fee the gateway reported for a call, when its audit details are collected.
+pub fn note_fee(fee_grt: f64) {
+    note(|details| *details.fee_grt.get_or_insert(0.0) += fee_grt);
+}
@@ -0,0 +1,318 @@
+// SPDX-License-Identifier: Apache-2.0
+use crate::constants::{GATEWAY_QOS_ORACLE, GRAPH_NETWORK_SUBGRAPH_ARBITRUM, REQUEST_ID_HEADER};
+use crate::error::SubgraphError;
+use crate::telemetry::{current_request_id, inject_trace_context};
+use crate::types::GraphQLRequest;
+use async_trait::async_trait;
+use http::{HeaderMap, StatusCode};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+/// A gateway's answer to a GraphQL request. The body is kept raw so callers can
+/// decide how to treat non-JSON error pages.
+#[derive(Clone, Debug)]
+pub struct GatewayResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl GatewayResponse {
+    /// A response with a JSON body and no headers.
+    pub fn json(status: StatusCode, body: &serde_json::Value) -> Self {
+        Self {
+            status,
+            headers: HeaderMap::new(),
+            body: serde_json::to_vec(body).unwrap_or_default(),
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|value| value.to_str().ok())
+    }
+}
+
+/// Sends the server's GraphQL requests to a gateway. The server only talks to the
+/// gateway through this trait, so embedders can route requests elsewhere and tests
+/// can answer them from memory.
+///
+/// Implementations return transport failures as errors and every HTTP answer,
+/// including error statuses, as a `GatewayResponse`.
+#[async_trait]
+pub trait GatewayBackend: Send + Sync {
+    /// Queries the Graph Network subgraph, which indexes subgraphs and their deployments.
+    async fn query_network_subgraph(
+        &self,
+        api_key: &str,
+        gateway_url: &str,
+        request: &GraphQLRequest,
+    ) -> Result<GatewayResponse, SubgraphError>;
+
+    /// Queries a subgraph deployment. `endpoint_type` is the gateway path the `id`
+    /// belongs to: `deployments/id` or `subgraphs/id`.
+    async fn query_deployment(
+        &self,
+        api_key: &str,
+        gateway_url: &str,
+        endpoint_type: &str,
+        id: &str,
+        request: &GraphQLRequest,
+    ) -> Result<GatewayResponse, SubgraphError>;
+
+    /// Queries the QoS oracle subgraph, which holds query volume statistics.
+    async fn query_qos_oracle(
+        &self,
+        api_key: &str,
+        gateway_url: &str,
+        request: &GraphQLRequest,
+    ) -> Result<GatewayResponse, SubgraphError>;
+}
+
+/// The default backend, POSTing to the gateway over HTTP.
+pub struct ReqwestBackend {
+    client: Client,
+    network_subgraph: String,
+}
+
+impl ReqwestBackend {
+    /// Queries the Arbitrum network subgraph unless `with_network_subgraph` says otherwise.
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            network_subgraph: GRAPH_NETWORK_SUBGRAPH_ARBITRUM.to_string(),
+        }
+    }
+
+    /// Overrides the deployment queried for network subgraph requests.
+    pub fn with_network_subgraph(mut self, network_subgraph: impl Into<String>) -> Self {
+        self.network_subgraph = network_subgraph.into();
+        self
+    }
+
+    pub fn network_subgraph(&self) -> &str {
+        &self.network_subgraph
+    }
+
+    /// POSTs `request`, propagating the current trace context and request ID, and
+    /// recording the response status on the current span.
+    async fn post(
+        &self,
+        url: &str,
+        request: &GraphQLRequest,
+    ) -> Result<GatewayResponse, SubgraphError> {
+        let mut builder = inject_trace_context(self.client.post(url));
+        if let Some(request_id) = current_request_id() {
+            builder = builder.header(REQUEST_ID_HEADER, request_id);
+        }
+        let response = builder.json(request).send().await?;
+        tracing::Span::current().record("http.response.status_code", response.status().as_u16());
+
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.bytes().await?.to_vec();
+        Ok(GatewayResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+#[async_trait]
+impl GatewayBackend for ReqwestBackend {
+    async fn query_network_subgraph(
+        &self,
+        api_key: &str,
+        gateway_url: &str,
+        request: &GraphQLRequest,
+    ) -> Result<GatewayResponse, SubgraphError> {
+        let url = format!(
+            "{}/{}/deployments/id/{}",
+            gateway_url, api_key, self.network_subgraph
+        );
+        self.post(&url, request).await
+    }
+
+    async fn query_deployment(
+        &self,
+        api_key: &str,
+        gateway_url: &str,
+        endpoint_type: &str,
+        id: &str,
+        request: &GraphQLRequest,
+    ) -> Result<GatewayResponse, SubgraphError> {
+        let url = format!("{}/{}/{}/{}", gateway_url, api_key, endpoint_type, id);
+        self.post(&url, request).await
+    }
+
+    async fn query_qos_oracle(
+        &self,
+        api_key: &str,
+        gateway_url: &str,
+        request: &GraphQLRequest,
+    ) -> Result<GatewayResponse, SubgraphError> {
+        let url = format!(
+            "{}/{}/deployments/id/{}",
+            gateway_url, api_key, GATEWAY_QOS_ORACLE
+        );
+        self.post(&url, request).await
+    }
+}
+
+/// The subgraph a gateway request was addressed to.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum GatewayTarget {
+    NetworkSubgraph,
+    QosOracle,
+    Deployment { endpoint_type: String, id: String },
+}
+
+struct Fixture {
+    target: GatewayTarget,
+    operation: Option<String>,
+    response: GatewayResponse,
+}
+
+/// Answers gateway requests from canned responses held in memory, and keeps the
+/// requests it was sent so tests can assert on them.
+///
+/// A response is picked by target and, optionally, by a string the query must
+/// contain (typically the operation name). Fixtures are tried in insertion order.
+#[derive(Default)]
+pub struct FixtureBackend {
+    fixtures: Vec<Fixture>,
+    requests: Mutex<Vec<(GatewayTarget, GraphQLRequest)>>,
+}
+
+impl FixtureBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_response(
+        mut self,
+        target: GatewayTarget,
+        operation: Option<&str>,
+        response: GatewayResponse,
+    ) -> Self {
+        self.fixtures.push(Fixture {
+            target,
+            operation: operation.map(str::to_string),
+            response,
+        });
+        self
+    }
+
+    /// Answers network subgraph queries containing `operation` with `{"data": data}`.
+    pub fn with_network_subgraph_data(self, operation: &str, data: serde_json::Value) -> Self {
+        self.with_response(
+            GatewayTarget::NetworkSubgraph,
+            Some(operation),
+            GatewayResponse::json(StatusCode::OK, &serde_json::json!({ "data": data })),
+        )
+    }
+
+    /// Answers every query to the deployment or subgraph `id` with `{"data": data}`.
+    pub fn with_deployment_data(
+        self,
+        endpoint_type: &str,
+        id: &str,
+        data: serde_json::Value,
+    ) -> Self {
+        self.with_response(
+            GatewayTarget::Deployment {
+                endpoint_type: endpoint_type.to_string(),
+                id: id.to_string(),
+            },
+            None,
+            GatewayResponse::json(StatusCode::OK, &serde_json::json!({ "data": data })),
+        )
+    }
+
+    /// Answers QoS oracle queries with `{"data": data}`.
+    pub fn with_qos_oracle_data(self, data: serde_json::Value) -> Self {
+        self.with_response(
+            GatewayTarget::QosOracle,
+            None,
+            GatewayResponse::json(StatusCode::OK, &serde_json::json!({ "data": data })),
+        )
+    }
+
+    /// The requests received so far, oldest first.
+    pub fn requests(&self) -> Vec<(GatewayTarget, GraphQLRequest)> {
+        self.requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn respond(
+        &self,
+        target: GatewayTarget,
+        request: &GraphQLRequest,
+    ) -> Result<GatewayResponse, SubgraphError> {
+        let response = self
+            .fixtures
+            .iter()
+            .find(|fixture| {
+                fixture.target == target
+                    && fixture
+                        .operation
+                        .as_ref()
+                        .is_none_or(|operation| request.query.contains(operation.as_str()))
+            })
+            .map(|fixture| fixture.response.clone());
+        self.requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((target.clone(), request.clone()));
+
+        response.ok_or_else(|| {
+            SubgraphError::InternalProcessingError(format!(
+                "No fixture for {:?} matching query {}",
+                target,
+                request.query.trim()
+            ))
+        })
+    }
+}
+
+#[async_trait]
+impl GatewayBackend for FixtureBackend {
+    async fn query_network_subgraph(
+        &self,
+        _api_key: &str,
+        _gateway_url: &str,
+        request: &GraphQLRequest,
+    ) -> Result<GatewayResponse, SubgraphError> {
+        self.respond(GatewayTarget::NetworkSubgraph, request)
+    }
+
+    async fn query_deployment(
+        &self,
+        _api_key: &str,
+        _gateway_url: &str,
+        endpoint_type: &str,
+        id: &str,
+        request: &GraphQLRequest,
+    ) -> Result<GatewayResponse, SubgraphError> {
+        self.respond(
+            GatewayTarget::Deployment {
+                endpoint_type: endpoint_type.to_string(),
+                id: id.to_string(),
+            },
+            request,
+        )
+    }
+
+    async fn query_qos_oracle(
+        &self,
+        _api_key: &str,
+        _gateway_url: &str,
+        request: &GraphQLRequest,
+    ) -> Result<GatewayResponse, SubgraphError> {
+        self.respond(GatewayTarget::QosOracle, request)
+    }
+}
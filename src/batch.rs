@@ -0,0 +1,95 @@
+// SPDX-License-Identifier: Apache-2.0
+use crate::error::SubgraphError;
+use crate::metrics::ErrorKind;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::env;
+
+/// Size and parallelism of `execute_queries_batch` calls.
+#[derive(Clone, Copy, Debug)]
+pub struct BatchLimits {
+    /// Maximum number of queries in one batch.
+    pub max_queries: usize,
+    /// Maximum number of queries of a batch sent to the gateway at the same time.
+    /// A request may ask for less.
+    pub max_concurrency: usize,
+}
+
+impl Default for BatchLimits {
+    fn default() -> Self {
+        Self {
+            max_queries: 20,
+            max_concurrency: 4,
+        }
+    }
+}
+
+impl BatchLimits {
+    /// Reads `QUERY_BATCH_MAX_QUERIES` and `QUERY_BATCH_MAX_CONCURRENCY`.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let limit = |name: &str, default: usize| {
+            env::var(name)
+                .ok()
+                .and_then(|s| s.parse::<usize>().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(default)
+        };
+        Self {
+            max_queries: limit("QUERY_BATCH_MAX_QUERIES", defaults.max_queries),
+            max_concurrency: limit("QUERY_BATCH_MAX_CONCURRENCY", defaults.max_concurrency),
+        }
+    }
+}
+
+/// Why one query of a batch failed. The rest of the batch is unaffected.
+#[derive(Clone, Debug, Serialize)]
+pub struct BatchItemError {
+    /// Same classes as the `error_kind` metric label.
+    pub kind: &'static str,
+    pub message: String,
+}
+
+impl From<SubgraphError> for BatchItemError {
+    fn from(e: SubgraphError) -> Self {
+        Self {
+            kind: e.error_kind(),
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<rmcp::Error> for BatchItemError {
+    fn from(e: rmcp::Error) -> Self {
+        Self {
+            kind: e.error_kind(),
+            message: e.message.to_string(),
+        }
+    }
+}
+
+impl ErrorKind for BatchItemError {
+    fn error_kind(&self) -> &'static str {
+        self.kind
+    }
+}
+
+/// The entry of one query in the batch result: the query response, `data` and
+/// `freshness`, or an `error`.
+pub fn batch_item_result(
+    index: usize,
+    deployment: &str,
+    outcome: Result<Value, BatchItemError>,
+) -> Value {
+    let mut entry = json!({ "index": index, "deployment": deployment });
+    match outcome {
+        Ok(Value::Object(response)) => {
+            for (key, value) in response {
+                entry[key] = value;
+            }
+        }
+        Ok(response) => entry["data"] = response,
+        Err(error) => entry["error"] = json!(error),
+    }
+    entry
+}
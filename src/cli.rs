@@ -0,0 +1,250 @@
+// SPDX-License-Identifier: Apache-2.0
+use crate::constants::POLICY_VIOLATION_ERROR_CODE;
+use crate::error::SubgraphError;
+use crate::policy::DeploymentRef;
+use crate::server::SubgraphServer;
+use crate::tabular::{cell_text, column_names, flatten_row};
+use clap::{Subcommand, ValueEnum};
+use rmcp::model::Extensions;
+use serde_json::{json, Map, Value};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// One-shot subcommands running the same logic as the MCP tools.
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Search subgraphs whose display name contains a keyword
+    Search { keyword: String },
+    /// Print the schema of a deployment ID (0x...), IPFS hash (Qm...) or subgraph ID
+    Schema { id: String },
+    /// Run a GraphQL query against a deployment ID, IPFS hash or subgraph ID
+    Query {
+        id: String,
+        /// File holding the GraphQL query
+        #[arg(long, short)]
+        file: PathBuf,
+        /// JSON file holding the query variables
+        #[arg(long)]
+        vars: Option<PathBuf>,
+    },
+    /// Top 3 deployments indexing a contract on a chain, by query fees
+    Top { contract: String, chain: String },
+    /// Total query counts over the last 30 days of deployments given by IPFS hash
+    Counts {
+        #[arg(required = true)]
+        ipfs_hashes: Vec<String>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// The JSON the matching MCP tool returns
+    #[default]
+    Json,
+    /// Aligned columns, one row per result
+    Table,
+}
+
+/// Process exit code for a failed command. Clap already exits with 2 on usage errors.
+pub fn exit_code(error: &SubgraphError) -> u8 {
+    match error {
+        SubgraphError::InternalProcessingError(_) | SubgraphError::ExportRefused(_) => 1,
+        SubgraphError::ApiKeyNotSet => 3,
+        SubgraphError::InvalidGatewayId(_) => 4,
+        SubgraphError::HttpError(_) | SubgraphError::HttpStatus(_) => 5,
+        SubgraphError::GraphQlError(_) => 6,
+        SubgraphError::JsonError(_) => 7,
+        SubgraphError::QueryRejected(_) | SubgraphError::InvalidTransform(_) => 8,
+        SubgraphError::BudgetExceeded(_) => 9,
+        SubgraphError::PolicyViolation(_) => 10,
+    }
+}
+
+/// Runs `command` against `server` and returns what to print. `gateway` selects a
+/// gateway by ID like the `x-gateway-id` header does.
+pub async fn run(
+    server: &SubgraphServer,
+    command: Command,
+    gateway: Option<&str>,
+    format: OutputFormat,
+) -> Result<String, SubgraphError> {
+    let mut extensions = Extensions::new();
+    if let Some(gateway) = gateway {
+        let (parts, _) = http::Request::builder()
+            .header("x-gateway-id", gateway)
+            .body(())
+            .map_err(|e| SubgraphError::InvalidGatewayId(format!("Invalid gateway ID: {}", e)))?
+            .into_parts();
+        extensions.insert(parts);
+    }
+    let api_key = server.get_api_key(&extensions)?;
+    let gateway_url = server.get_gateway_url(&extensions)?;
+
+    let (result, rows_at) = match command {
+        Command::Search { keyword } => (
+            server
+                .search_subgraphs_by_keyword_internal(&api_key, &gateway_url, &keyword)
+                .await?,
+            "subgraphs",
+        ),
+        Command::Schema { id } => {
+            let deployment = DeploymentRef::parse(&id);
+            let tool_name = match deployment {
+                DeploymentRef::DeploymentId(_) => "get_schema_by_deployment_id",
+                DeploymentRef::IpfsHash(_) => "get_schema_by_ipfs_hash",
+                DeploymentRef::SubgraphId(_) => "get_schema_by_subgraph_id",
+            };
+            enforce_policy(server, tool_name, &api_key, &gateway_url, deployment).await?;
+            let schema = server
+                .get_schema_internal(&api_key, &gateway_url, deployment)
+                .await?;
+            // A schema is text, so the table format prints it as is
+            return Ok(match format {
+                OutputFormat::Json => format!("{:#}", json!({ "schema": schema })),
+                OutputFormat::Table => schema,
+            });
+        }
+        Command::Query { id, file, vars } => {
+            let query = read_input(&file)?;
+            let variables = vars
+                .map(|vars| read_input(&vars).and_then(|v| Ok(serde_json::from_str(&v)?)))
+                .transpose()?;
+            let deployment = DeploymentRef::parse(&id);
+            let tool_name = match deployment {
+                DeploymentRef::DeploymentId(_) => "execute_query_by_deployment_id",
+                DeploymentRef::IpfsHash(_) => "execute_query_by_ipfs_hash",
+                DeploymentRef::SubgraphId(_) => "execute_query_by_subgraph_id",
+            };
+            enforce_policy(server, tool_name, &api_key, &gateway_url, deployment).await?;
+            (
+                server
+                    .execute_query_with_freshness(
+                        &api_key,
+                        &gateway_url,
+                        deployment.endpoint_type(),
+                        &id,
+                        &query,
+                        variables,
+                        None,
+                    )
+                    .await?,
+                "data",
+            )
+        }
+        Command::Top { contract, chain } => (
+            server
+                .get_top_subgraph_deployments_internal(&api_key, &gateway_url, &contract, &chain)
+                .await?,
+            "subgraphDeployments",
+        ),
+        Command::Counts { ipfs_hashes } => (
+            server
+                .get_deployment_30day_query_counts_internal(&api_key, &gateway_url, &ipfs_hashes)
+                .await?,
+            "deployments",
+        ),
+    };
+
+    Ok(match format {
+        OutputFormat::Json => format!("{:#}", result),
+        OutputFormat::Table => match &result[rows_at] {
+            // Query results hold one list per top-level field
+            Value::Object(fields) if rows_at == "data" => fields
+                .iter()
+                .map(|(field, rows)| format!("{}\n{}", field, render_table(&as_rows(rows))))
+                .chain(
+                    result["freshness"]["warnings"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|warning| Some(format!("warning: {}", warning.as_str()?))),
+                )
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+            rows => render_table(&as_rows(rows)),
+        },
+    })
+}
+
+/// Applies the deployment policy the matching tool applies, keeping the
+/// `SubgraphError` variants that pick the exit code.
+async fn enforce_policy(
+    server: &SubgraphServer,
+    tool_name: &str,
+    api_key: &str,
+    gateway_url: &str,
+    deployment: DeploymentRef<'_>,
+) -> Result<(), SubgraphError> {
+    server
+        .enforce_deployment_policy(tool_name, api_key, gateway_url, deployment)
+        .await
+        .map_err(|e| {
+            if e.code.0 == POLICY_VIOLATION_ERROR_CODE {
+                SubgraphError::PolicyViolation(e.message.into_owned())
+            } else {
+                SubgraphError::InternalProcessingError(e.message.into_owned())
+            }
+        })
+}
+
+fn read_input(path: &Path) -> Result<String, SubgraphError> {
+    fs::read_to_string(path).map_err(|e| {
+        SubgraphError::InternalProcessingError(format!("Failed to read {}: {}", path.display(), e))
+    })
+}
+
+fn as_rows(value: &Value) -> Vec<Value> {
+    match value {
+        Value::Array(rows) => rows.clone(),
+        Value::Null => Vec::new(),
+        row => vec![row.clone()],
+    }
+}
+
+/// Renders JSON rows as aligned text columns, sorted by column name.
+pub fn render_table(rows: &[Value]) -> String {
+    let rows: Vec<Map<String, Value>> = rows.iter().map(flatten_row).collect();
+    let headers = column_names(&rows);
+    if headers.is_empty() {
+        return "(no rows)".to_string();
+    }
+
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            headers
+                .iter()
+                .map(|column| cell_text(row.get(column)))
+                .collect()
+        })
+        .collect();
+    let widths: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(i, header)| {
+            cells
+                .iter()
+                .map(|row| row[i].chars().count())
+                .chain([header.chars().count()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let line = |values: &[String]| {
+        values
+            .iter()
+            .zip(&widths)
+            .map(|(value, width)| format!("{:<width$}", value, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    std::iter::once(line(&headers))
+        .chain(cells.iter().map(|row| line(row)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
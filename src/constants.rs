@@ -16,6 +16,20 @@ pub static GATEWAY_REGISTRY: Lazy<HashMap<&'static str, &'static str>> = Lazy::n
 // Default gateway ID
 pub const DEFAULT_GATEWAY_ID: &str = "edgeandnode";
 
+// Cost hint attached to each tool's input schema so clients can tell billable calls apart
+pub const TOOL_COST_HINT_KEY: &str = "x-cost-hint";
+pub const TOOL_COST_BILLABLE: &str = "billable-gateway-query";
+pub const TOOL_COST_FREE: &str = "free";
+
+// Header carrying the MCP request ID on outgoing gateway requests
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+// JSON-RPC error code returned when a tool call is throttled
+pub const RATE_LIMITED_ERROR_CODE: i32 = -32029;
+
+// JSON-RPC error code returned when a deployment is rejected by the deployment policy
+pub const POLICY_VIOLATION_ERROR_CODE: i32 = -32030;
+
 pub const SUBGRAPH_SERVER_INSTRUCTIONS: &str = "**Interacting with The Graph Subgraphs**
 **IMPORTANT: ALWAYS verify query volumes using `get_deployment_30day_query_counts` for any potential subgraph candidate *before* selecting or querying it. This step is NON-OPTIONAL. Failure to do so may result in using outdated or irrelevant data.**
 **Follow this sequence strictly:**
@@ -0,0 +1,155 @@
+// SPDX-License-Identifier: Apache-2.0
+use crate::error::SubgraphError;
+use crate::schema_summary::SchemaSummary;
+use crate::transform::sort_rows;
+use graphql_parser::query::{parse_query, Definition, OperationDefinition, Selection};
+use rmcp::schemars;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+/// Column added to merged rows, naming the chain each row comes from.
+pub const NETWORK_COLUMN: &str = "_network";
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderDirection {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// A top-level field of a query and the fields it selects on the entity.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RequiredField {
+    pub name: String,
+    pub selected: Vec<String>,
+}
+
+/// Short hash of a schema, equal for deployments sharing the same SDL whatever its
+/// formatting.
+pub fn schema_hash(sdl: &str) -> String {
+    let normalized = match graphql_parser::parse_schema::<String>(sdl) {
+        Ok(document) => document.to_string(),
+        Err(_) => sdl.split_whitespace().collect::<Vec<_>>().join(" "),
+    };
+    Sha256::digest(normalized.as_bytes())
+        .iter()
+        .take(8)
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// The entity query fields `query` reads and the fields it selects on each, which
+/// a deployment's schema must provide. `_meta` and introspection fields are left
+/// out as every deployment has them.
+pub fn required_fields(query: &str) -> Result<Vec<RequiredField>, SubgraphError> {
+    let document = parse_query::<String>(query)
+        .map_err(|e| SubgraphError::QueryRejected(format!("Query is not valid GraphQL: {}", e)))?;
+
+    let mut required = Vec::new();
+    for definition in &document.definitions {
+        let selection_set = match definition {
+            Definition::Operation(OperationDefinition::SelectionSet(set)) => set,
+            Definition::Operation(OperationDefinition::Query(q)) => &q.selection_set,
+            _ => continue,
+        };
+        for selection in &selection_set.items {
+            let Selection::Field(field) = selection else {
+                continue;
+            };
+            if field.name.starts_with("__") || field.name == "_meta" {
+                continue;
+            }
+            let selected = field
+                .selection_set
+                .items
+                .iter()
+                .filter_map(|selection| match selection {
+                    Selection::Field(field) if !field.name.starts_with("__") => {
+                        Some(field.name.clone())
+                    }
+                    _ => None,
+                })
+                .collect();
+            required.push(RequiredField {
+                name: field.name.clone(),
+                selected,
+            });
+        }
+    }
+    Ok(required)
+}
+
+/// Checks that the schema summarized by `summary` has every entity query field and
+/// entity field in `required`, returning the first missing one otherwise.
+pub fn check_compatibility(
+    summary: &SchemaSummary,
+    required: &[RequiredField],
+) -> Result<(), String> {
+    for field in required {
+        let entity = summary
+            .entities
+            .iter()
+            .find(|e| {
+                e.query_fields.collection == field.name
+                    || e.query_fields.single.as_deref() == Some(field.name.as_str())
+            })
+            .ok_or_else(|| format!("The schema has no `{}` query field", field.name))?;
+        for selected in &field.selected {
+            let known = entity.fields.iter().any(|f| &f.name == selected)
+                || entity.relationships.iter().any(|r| &r.field == selected);
+            if !known {
+                return Err(format!(
+                    "The `{}` entity has no `{}` field",
+                    entity.name, selected
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Concatenates the top-level collections of each chain's `data`, tagging every row
+/// with its chain, and sorts the rows by `order_by` when given. Rows missing the
+/// field come last.
+pub fn merge_collections(
+    results: &[(String, &Value)],
+    order_by: Option<&str>,
+    direction: OrderDirection,
+) -> Map<String, Value> {
+    let mut merged: Map<String, Value> = Map::new();
+    for (network, data) in results {
+        let Some(fields) = data.as_object() else {
+            continue;
+        };
+        for (field, rows) in fields {
+            let Some(rows) = rows.as_array() else {
+                continue;
+            };
+            let entry = merged
+                .entry(field.clone())
+                .or_insert_with(|| Value::Array(Vec::new()));
+            if let Value::Array(merged_rows) = entry {
+                merged_rows.extend(rows.iter().map(|row| {
+                    let mut row = row.clone();
+                    if let Value::Object(columns) = &mut row {
+                        columns.insert(NETWORK_COLUMN.to_string(), Value::from(network.as_str()));
+                    }
+                    row
+                }));
+            }
+        }
+    }
+
+    if let Some(order_by) = order_by {
+        for rows in merged.values_mut() {
+            if let Value::Array(rows) = rows {
+                sort_rows(rows, order_by, direction);
+            }
+        }
+    }
+    merged
+}
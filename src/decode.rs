@@ -0,0 +1,362 @@
+// SPDX-License-Identifier: Apache-2.0
+use crate::schema_summary::{EntitySummary, SchemaSummary};
+use crate::transform::lookup;
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, SecondsFormat, Utc};
+use graphql_parser::query::{
+    parse_query, Definition, FragmentDefinition, OperationDefinition, Selection, SelectionSet,
+    TypeCondition,
+};
+use rmcp::schemars;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+};
+
+/// Suffix of the field holding a value scaled by its token's decimals.
+pub const DECODED_SUFFIX: &str = "_decoded";
+/// Suffix of the field holding a timestamp as ISO-8601.
+pub const ISO_SUFFIX: &str = "_iso";
+/// Largest exponent written out in full; more digits than any 256-bit amount.
+const MAX_EXPANDED_EXPONENT: u64 = 80;
+
+/// Decoding of raw on-chain values in a query result into readable ones. Decoded
+/// values are added next to the originals, which are left as they are:
+/// `amount_decoded` next to `amount` and `timestamp_iso` next to `timestamp`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct DecodeSpec {
+    #[schemars(
+        description = "Optional decimals per field name, as a number (e.g. {\"value\": 6}) or as the path to a decimals field relative to the same object (e.g. {\"amount0\": \"token0.decimals\"}). Takes precedence over decimals found in the result"
+    )]
+    pub decimals: Option<BTreeMap<String, DecimalsHint>>,
+    #[schemars(
+        description = "Optional. Scale the fields the schema types as BigInt by a `decimals` field on the same object or on its only related object that has one, e.g. a transfer's `token` (default true)"
+    )]
+    pub infer_decimals: Option<bool>,
+    #[schemars(
+        description = "Optional. Add ISO-8601 dates for timestamp fields (`timestamp`, `*Timestamp`, `*At`, `*Time`) holding Unix seconds, milliseconds or microseconds, as numbers, strings or Bytes (default true)"
+    )]
+    pub timestamps: Option<bool>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(untagged)]
+pub enum DecimalsHint {
+    /// A fixed number of decimals
+    Fixed(u8),
+    /// The path to a decimals field, relative to the object holding the value
+    Field(String),
+}
+
+/// The schema types of a query's result fields by response key, as far as the
+/// selection can be followed through the deployment's schema.
+#[derive(Debug, Default)]
+pub struct ResultTypes {
+    fields: HashMap<String, ResultType>,
+}
+
+#[derive(Debug)]
+enum ResultType {
+    /// The named type of a scalar or enum field, without list or non-null markers
+    Scalar(String),
+    Object(ResultTypes),
+}
+
+impl ResultTypes {
+    /// Follows `query` through `schema`. Fields that can't be followed, and
+    /// every field of a query that doesn't parse, are left untyped.
+    pub fn new(query: &str, schema: &SchemaSummary) -> Self {
+        let mut types = Self::default();
+        let Ok(document) = parse_query::<String>(query) else {
+            return types;
+        };
+        let fragments: HashMap<&str, &FragmentDefinition<String>> = document
+            .definitions
+            .iter()
+            .filter_map(|definition| match definition {
+                Definition::Fragment(fragment) => Some((fragment.name.as_str(), fragment)),
+                _ => None,
+            })
+            .collect();
+        let mut collector = Collector {
+            schema,
+            fragments,
+            fragment_stack: Vec::new(),
+        };
+        for definition in &document.definitions {
+            let Definition::Operation(operation) = definition else {
+                continue;
+            };
+            let selection_set = match operation {
+                OperationDefinition::SelectionSet(set) => set,
+                OperationDefinition::Query(q) => &q.selection_set,
+                OperationDefinition::Mutation(m) => &m.selection_set,
+                OperationDefinition::Subscription(s) => &s.selection_set,
+            };
+            collector.collect(&mut types, selection_set, None);
+        }
+        types
+    }
+
+    fn scalar(&self, key: &str) -> Option<&str> {
+        match self.fields.get(key)? {
+            ResultType::Scalar(name) => Some(name),
+            ResultType::Object(_) => None,
+        }
+    }
+
+    fn object(&self, key: &str) -> Option<&ResultTypes> {
+        match self.fields.get(key)? {
+            ResultType::Object(types) => Some(types),
+            ResultType::Scalar(_) => None,
+        }
+    }
+}
+
+struct Collector<'a> {
+    schema: &'a SchemaSummary,
+    fragments: HashMap<&'a str, &'a FragmentDefinition<'a, String>>,
+    fragment_stack: Vec<&'a str>,
+}
+
+impl<'a> Collector<'a> {
+    /// Adds the fields of `selection_set` to `types`; `parent` is `None` on `Query`.
+    fn collect(
+        &mut self,
+        types: &mut ResultTypes,
+        selection_set: &'a SelectionSet<'a, String>,
+        parent: Option<&'a EntitySummary>,
+    ) {
+        for selection in &selection_set.items {
+            match selection {
+                Selection::Field(field) => {
+                    let key = field.alias.as_ref().unwrap_or(&field.name);
+                    let name = field.name.as_str();
+                    let target = match parent {
+                        None => self.schema.entities.iter().find(|e| {
+                            e.query_fields.collection == name
+                                || e.query_fields.single.as_deref() == Some(name)
+                        }),
+                        Some(entity) => {
+                            if let Some(scalar) = entity.fields.iter().find(|f| f.name == name) {
+                                let base = scalar
+                                    .field_type
+                                    .trim_matches(|c| matches!(c, '[' | ']' | '!'));
+                                types
+                                    .fields
+                                    .insert(key.clone(), ResultType::Scalar(base.to_string()));
+                                continue;
+                            }
+                            entity
+                                .relationships
+                                .iter()
+                                .find(|r| r.field == name)
+                                .and_then(|r| self.entity(&r.target))
+                        }
+                    };
+                    if let Some(target) = target {
+                        let entry = types
+                            .fields
+                            .entry(key.clone())
+                            .or_insert_with(|| ResultType::Object(ResultTypes::default()));
+                        if let ResultType::Object(nested) = entry {
+                            self.collect(nested, &field.selection_set, Some(target));
+                        }
+                    }
+                }
+                Selection::InlineFragment(fragment) => {
+                    let parent = self.narrowed(fragment.type_condition.as_ref(), parent);
+                    self.collect(types, &fragment.selection_set, parent);
+                }
+                Selection::FragmentSpread(spread) => {
+                    let name = spread.fragment_name.as_str();
+                    // Cyclic fragments are invalid GraphQL; the gateway reports them.
+                    if self.fragment_stack.contains(&name) {
+                        continue;
+                    }
+                    if let Some(fragment) = self.fragments.get(name).copied() {
+                        let parent = self.narrowed(Some(&fragment.type_condition), parent);
+                        self.fragment_stack.push(name);
+                        self.collect(types, &fragment.selection_set, parent);
+                        self.fragment_stack.pop();
+                    }
+                }
+            }
+        }
+    }
+
+    fn entity(&self, name: &str) -> Option<&'a EntitySummary> {
+        self.schema.entities.iter().find(|e| e.name == name)
+    }
+
+    /// The entity a fragment applies to, e.g. an implementation of an interface.
+    fn narrowed(
+        &self,
+        condition: Option<&TypeCondition<String>>,
+        parent: Option<&'a EntitySummary>,
+    ) -> Option<&'a EntitySummary> {
+        match condition {
+            Some(TypeCondition::On(name)) => self.entity(name).or(parent),
+            None => parent,
+        }
+    }
+}
+
+impl DecodeSpec {
+    /// Adds the decoded values to `response["data"]`. Amounts are only inferred
+    /// and exponents only written out for fields `types` knows as `BigInt` and
+    /// `BigDecimal`.
+    pub fn apply(&self, mut response: Value, types: &ResultTypes) -> Value {
+        if let Some(data) = response.get_mut("data") {
+            self.decode(data, Some(types));
+        }
+        response
+    }
+
+    fn decode(&self, value: &mut Value, types: Option<&ResultTypes>) {
+        match value {
+            Value::Array(items) => items.iter_mut().for_each(|item| self.decode(item, types)),
+            Value::Object(fields) => {
+                for (key, field) in fields.iter_mut() {
+                    self.decode(field, types.and_then(|types| types.object(key)));
+                }
+                self.decode_fields(fields, types);
+            }
+            _ => {}
+        }
+    }
+
+    fn decode_fields(&self, fields: &mut Map<String, Value>, types: Option<&ResultTypes>) {
+        let object = Value::Object(fields.clone());
+        let inferred = if self.infer_decimals.unwrap_or(true) {
+            inferred_decimals(fields)
+        } else {
+            None
+        };
+
+        let mut decoded = Vec::new();
+        for (name, value) in fields.iter() {
+            if self.timestamps.unwrap_or(true) && is_timestamp_name(name) {
+                if let Some(iso) = iso_timestamp(value) {
+                    decoded.push((format!("{}{}", name, ISO_SUFFIX), Value::String(iso)));
+                }
+                continue;
+            }
+
+            let field_type = types.and_then(|types| types.scalar(name));
+            let hinted = self.decimals.as_ref().and_then(|hints| hints.get(name));
+            let scaled = match hinted {
+                Some(DecimalsHint::Fixed(decimals)) => scale(value, *decimals),
+                Some(DecimalsHint::Field(path)) => lookup(&object, path)
+                    .and_then(decimals)
+                    .and_then(|d| scale(value, d)),
+                // `BigDecimal` values are already scaled, and `Int` values are
+                // never token amounts
+                None if field_type == Some("BigInt") && is_quantity_name(name) => {
+                    inferred.and_then(|d| scale(value, d))
+                }
+                None => None,
+            };
+            let scaled = scaled.or_else(|| match field_type {
+                Some("BigDecimal") => expand_exponent(value),
+                _ => None,
+            });
+            if let Some(scaled) = scaled {
+                decoded.push((format!("{}{}", name, DECODED_SUFFIX), Value::String(scaled)));
+            }
+        }
+        fields.extend(decoded);
+    }
+}
+
+/// The decimals of an object's amounts: its own `decimals` field, or that of its
+/// only related object that has one. Objects relating several tokens, such as a
+/// swap with `token0` and `token1`, are left to hints.
+fn inferred_decimals(fields: &Map<String, Value>) -> Option<u8> {
+    if let Some(own) = fields.get("decimals") {
+        return decimals(own);
+    }
+    let mut related = fields
+        .values()
+        .filter_map(|value| value.get("decimals"))
+        .filter_map(decimals);
+    match (related.next(), related.next()) {
+        (Some(decimals), None) => Some(decimals),
+        _ => None,
+    }
+}
+
+fn decimals(value: &Value) -> Option<u8> {
+    match value {
+        Value::Number(n) => n.as_u64().and_then(|n| u8::try_from(n).ok()),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn integer_text(value: &Value) -> Option<String> {
+    let text = match value {
+        Value::Number(n) if n.is_i64() || n.is_u64() => n.to_string(),
+        Value::String(s) => s.trim().to_string(),
+        _ => return None,
+    };
+    let digits = text.strip_prefix('-').unwrap_or(&text);
+    (!digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())).then_some(text)
+}
+
+/// An integer divided by 10^decimals, exactly, as a plain decimal string.
+fn scale(value: &Value, decimals: u8) -> Option<String> {
+    let text = integer_text(value)?;
+    let scaled = BigDecimal::from_str(&format!("{}e-{}", text, decimals)).ok()?;
+    Some(scaled.normalized().to_plain_string())
+}
+
+/// A `BigDecimal` string in exponent notation, such as `1.5e21`, written out,
+/// unless it would run to more than `MAX_EXPANDED_EXPONENT` zeros.
+fn expand_exponent(value: &Value) -> Option<String> {
+    let text = value.as_str()?.trim();
+    if !text.contains(['e', 'E']) {
+        return None;
+    }
+    let decimal = BigDecimal::from_str(text).ok()?.normalized();
+    let (_, scale) = decimal.as_bigint_and_exponent();
+    (scale.unsigned_abs() <= MAX_EXPANDED_EXPONENT).then(|| decimal.to_plain_string())
+}
+
+/// Whether a field's name suggests an amount rather than a block number, a count,
+/// an index or a time.
+fn is_quantity_name(name: &str) -> bool {
+    let lower = name.to_ascii_lowercase();
+    !(lower == "id"
+        || lower == "decimals"
+        || name.ends_with("Id")
+        || name.ends_with("ID")
+        || ["block", "count", "index", "nonce", "number", "time", "date"]
+            .iter()
+            .any(|word| lower.contains(word)))
+}
+
+fn is_timestamp_name(name: &str) -> bool {
+    name.to_ascii_lowercase().ends_with("timestamp")
+        || (name.ends_with("At") && name.len() > 2)
+        || name.ends_with("Time")
+}
+
+/// A timestamp as ISO-8601, telling Unix seconds, milliseconds and microseconds
+/// (graph-node's `Timestamp`) apart by magnitude. `Bytes` values are read as a
+/// big-endian number of seconds.
+fn iso_timestamp(value: &Value) -> Option<String> {
+    let raw = match value.as_str().and_then(|s| s.strip_prefix("0x")) {
+        Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+        None => integer_text(value)?.parse::<i64>().ok()?,
+    };
+    let time: DateTime<Utc> = match raw {
+        1_000_000_000..=9_999_999_999 => DateTime::from_timestamp(raw, 0)?,
+        1_000_000_000_000..=9_999_999_999_999 => DateTime::from_timestamp_millis(raw)?,
+        1_000_000_000_000_000..=9_999_999_999_999_999 => DateTime::from_timestamp_micros(raw)?,
+        _ => return None,
+    };
+    Some(time.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+}
@@ -7,6 +7,8 @@ pub enum SubgraphError {
     ApiKeyNotSet,
     #[error("HTTP error: {0}")]
     HttpError(#[from] reqwest::Error),
+    #[error("Gateway returned HTTP status {0}")]
+    HttpStatus(u16),
     #[error("GraphQL error: {0}")]
     GraphQlError(String),
     #[error("JSON error: {0}")]
@@ -15,4 +17,14 @@ pub enum SubgraphError {
     InternalProcessingError(String),
     #[error("{0}")]
     InvalidGatewayId(String),
+    #[error("Budget exceeded: {0}")]
+    BudgetExceeded(String),
+    #[error("Policy violation: {0}")]
+    PolicyViolation(String),
+    #[error("Query rejected: {0}")]
+    QueryRejected(String),
+    #[error("Invalid transform: {0}")]
+    InvalidTransform(String),
+    #[error("Export refused: {0}")]
+    ExportRefused(String),
 }
@@ -0,0 +1,387 @@
+// SPDX-License-Identifier: Apache-2.0
+use crate::error::SubgraphError;
+use crate::schema_summary::{EntityKind, EntitySummary, ScalarField, SchemaSummary};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+pub const DEFAULT_EXAMPLE_FIRST: u32 = 10;
+/// graph-node's largest page size
+pub const MAX_EXAMPLE_FIRST: u32 = 1000;
+
+// Scalar fields selected per entity, to keep the examples readable
+const MAX_SELECTED_FIELDS: usize = 8;
+const MAX_NESTED_FIELDS: usize = 4;
+const NESTED_FIRST: u32 = 5;
+
+const NUMERIC_TYPES: [&str; 5] = ["BigInt", "BigDecimal", "Int", "Int8", "Float"];
+// Types a timestamp or block number is stored as
+const RECENCY_TYPES: [&str; 4] = ["BigInt", "Int", "Int8", "Timestamp"];
+
+/// Ready-to-run queries for one entity of a deployment.
+#[derive(Debug, Serialize)]
+pub struct ExampleQueries {
+    pub entity: String,
+    pub queries: Vec<ExampleQuery>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExampleQuery {
+    pub name: String,
+    pub description: String,
+    pub query: String,
+    /// Example values, valid as the `variables` of `execute_query_*`.
+    pub variables: Map<String, Value>,
+    /// GraphQL type of each variable.
+    pub variable_types: BTreeMap<String, String>,
+}
+
+struct QueryBuilder {
+    name: String,
+    description: String,
+    variables: Map<String, Value>,
+    variable_types: BTreeMap<String, String>,
+}
+
+impl QueryBuilder {
+    fn new(name: String, description: String) -> Self {
+        Self {
+            name,
+            description,
+            variables: Map::new(),
+            variable_types: BTreeMap::new(),
+        }
+    }
+
+    fn variable(mut self, name: &str, graphql_type: &str, example: Value) -> Self {
+        self.variables.insert(name.to_string(), example);
+        self.variable_types
+            .insert(name.to_string(), graphql_type.to_string());
+        self
+    }
+
+    fn build(self, body: String) -> ExampleQuery {
+        let declarations = self
+            .variable_types
+            .iter()
+            .map(|(name, graphql_type)| format!("${}: {}", name, graphql_type))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let query = if declarations.is_empty() {
+            format!("query {} {{\n{}\n}}", self.name, body)
+        } else {
+            format!("query {}({}) {{\n{}\n}}", self.name, declarations, body)
+        };
+        ExampleQuery {
+            name: self.name,
+            description: self.description,
+            query,
+            variables: self.variables,
+            variable_types: self.variable_types,
+        }
+    }
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+fn base_type_name(field_type: &str) -> &str {
+    field_type.trim_matches(|c| matches!(c, '[' | ']' | '!'))
+}
+
+fn is_list(field_type: &str) -> bool {
+    field_type.starts_with('[')
+}
+
+/// Scalar fields to select, `id` first.
+fn selection(fields: &[ScalarField], max: usize, indent: usize) -> String {
+    let mut names: Vec<&str> = fields
+        .iter()
+        .filter(|f| f.name == "id")
+        .chain(fields.iter().filter(|f| f.name != "id"))
+        .map(|f| f.name.as_str())
+        .take(max)
+        .collect();
+    if names.is_empty() {
+        names.push("id");
+    }
+    names
+        .iter()
+        .map(|name| format!("{}{}", " ".repeat(indent), name))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The field that best orders entities by recency: a timestamp, then a block number.
+/// Only integer fields qualify, so that e.g. a `blockHash` is never taken for one.
+fn recency_field(entity: &EntitySummary) -> Option<&ScalarField> {
+    let scalar = |f: &&ScalarField| {
+        !is_list(&f.field_type) && RECENCY_TYPES.contains(&base_type_name(&f.field_type))
+    };
+    let named = |pattern: &str| {
+        entity
+            .fields
+            .iter()
+            .filter(scalar)
+            .find(|f| f.name.to_lowercase().contains(pattern))
+    };
+    entity
+        .fields
+        .iter()
+        .filter(scalar)
+        .find(|f| f.name == "timestamp")
+        .or_else(|| named("timestamp"))
+        .or_else(|| named("createdat"))
+        .or_else(|| named("blocknumber"))
+        .or_else(|| named("block"))
+}
+
+/// A `where` condition on a field an agent is likely to filter by, with the
+/// variable type and an example value.
+fn filter_condition(
+    summary: &SchemaSummary,
+    entity: &EntitySummary,
+) -> Option<(String, String, String, Value)> {
+    let candidates: Vec<&ScalarField> = entity
+        .fields
+        .iter()
+        .filter(|f| f.name != "id" && !is_list(&f.field_type))
+        .collect();
+
+    let enum_field = candidates.iter().find_map(|f| {
+        let type_name = base_type_name(&f.field_type);
+        let values = &summary.enums.iter().find(|e| e.name == type_name)?.values;
+        Some((
+            f.name.clone(),
+            f.name.clone(),
+            format!("{}!", type_name),
+            json!(values.first()?),
+        ))
+    });
+    let numeric_field = || {
+        candidates.iter().find_map(|f| {
+            let type_name = base_type_name(&f.field_type);
+            NUMERIC_TYPES.contains(&type_name).then(|| {
+                // BigInt and BigDecimal travel as strings
+                let example = match type_name {
+                    "Int" | "Int8" | "Float" => json!(0),
+                    _ => json!("0"),
+                };
+                (
+                    format!("{}_gt", f.name),
+                    format!("{}Gt", f.name),
+                    format!("{}!", type_name),
+                    example,
+                )
+            })
+        })
+    };
+    let boolean_field = || {
+        candidates
+            .iter()
+            .find(|f| base_type_name(&f.field_type) == "Boolean")
+            .map(|f| {
+                (
+                    f.name.clone(),
+                    f.name.clone(),
+                    "Boolean!".to_string(),
+                    json!(true),
+                )
+            })
+    };
+    let string_field = || {
+        candidates
+            .iter()
+            .find(|f| base_type_name(&f.field_type) == "String")
+            .map(|f| {
+                (
+                    format!("{}_contains_nocase", f.name),
+                    format!("{}Contains", f.name),
+                    "String!".to_string(),
+                    json!(""),
+                )
+            })
+    };
+    enum_field
+        .or_else(numeric_field)
+        .or_else(boolean_field)
+        .or_else(string_field)
+}
+
+/// Arguments and variables every collection query of `entity` needs.
+fn required_arguments(entity: &EntitySummary, builder: QueryBuilder) -> (String, QueryBuilder) {
+    if entity.kind == EntityKind::Aggregation {
+        let interval = entity
+            .aggregation_intervals
+            .first()
+            .cloned()
+            .unwrap_or_else(|| "hour".to_string());
+        (
+            "interval: $interval, ".to_string(),
+            builder.variable("interval", "Aggregation_interval!", json!(interval)),
+        )
+    } else {
+        (String::new(), builder)
+    }
+}
+
+/// Builds example queries for `entity`, or for the first entry point of the schema
+/// when no entity is given. `first` is the number of entities the lists return.
+pub fn generate_example_queries(
+    summary: &SchemaSummary,
+    entity: Option<&str>,
+    first: u32,
+) -> Result<ExampleQueries, SubgraphError> {
+    let first = first.clamp(1, MAX_EXAMPLE_FIRST);
+    let entity = match entity {
+        Some(name) => summary.entities.iter().find(|e| e.name == name),
+        None => summary
+            .entry_points
+            .first()
+            .and_then(|collection| {
+                summary
+                    .entities
+                    .iter()
+                    .find(|e| &e.query_fields.collection == collection)
+            })
+            .or_else(|| summary.entities.first()),
+    }
+    .ok_or_else(|| {
+        let names: Vec<&str> = summary.entities.iter().map(|e| e.name.as_str()).collect();
+        SubgraphError::InternalProcessingError(format!(
+            "Entity '{}' not found in the schema. Available entities: {}",
+            entity.unwrap_or_default(),
+            names.join(", ")
+        ))
+    })?;
+
+    let collection = &entity.query_fields.collection;
+    let plural = capitalize(collection);
+    let fields = selection(&entity.fields, MAX_SELECTED_FIELDS, 4);
+    let mut queries = Vec::new();
+
+    // Latest entities
+    let (order_by, order_description) = match recency_field(entity) {
+        Some(field) => (field.name.clone(), format!("by `{}`", field.name)),
+        None => (
+            "id".to_string(),
+            "by `id`, as no timestamp or block field exists".to_string(),
+        ),
+    };
+    let builder = QueryBuilder::new(
+        format!("Latest{}", plural),
+        format!(
+            "The {} most recent {} entities, newest first, ordered {}.",
+            first, entity.name, order_description
+        ),
+    );
+    let (required, builder) = required_arguments(entity, builder);
+    queries.push(
+        builder
+            .variable("first", "Int!", json!(first))
+            .build(format!(
+                "  {}({}first: $first, orderBy: {}, orderDirection: desc) {{\n{}\n  }}",
+                collection, required, order_by, fields
+            )),
+    );
+
+    // Lookup by ID
+    if let Some(single) = &entity.query_fields.single {
+        let id_type = entity
+            .fields
+            .iter()
+            .find(|f| f.name == "id")
+            .map(|f| base_type_name(&f.field_type))
+            .unwrap_or("ID");
+        let example_id = match id_type {
+            "Int8" | "Int" => json!(1),
+            "Bytes" => json!("0x"),
+            _ => json!(""),
+        };
+        queries.push(
+            QueryBuilder::new(
+                format!("{}ById", entity.name),
+                format!(
+                    "A single {} by its `id`. Replace the example value with a real ID, e.g. one returned by Latest{}.",
+                    entity.name, plural
+                ),
+            )
+            .variable("id", "ID!", example_id)
+            .build(format!("  {}(id: $id) {{\n{}\n  }}", single, fields)),
+        );
+    }
+
+    // Filtered list
+    if let Some((filter, variable, variable_type, example)) = filter_condition(summary, entity) {
+        let builder = QueryBuilder::new(
+            format!("Filtered{}", plural),
+            format!(
+                "{} entities matching a `where` filter on `{}`. Other filters follow the same pattern: `field`, `field_not`, `field_gt`, `field_in`, `field_contains` and so on, as listed in the {} input type.",
+                entity.name, filter, entity.query_fields.filter
+            ),
+        );
+        let (required, builder) = required_arguments(entity, builder);
+        queries.push(
+            builder
+                .variable("first", "Int!", json!(first))
+                .variable(&variable, &variable_type, example)
+                .build(format!(
+                    "  {}({}first: $first, where: {{ {}: ${} }}) {{\n{}\n  }}",
+                    collection, required, filter, variable, fields
+                )),
+        );
+    }
+
+    // Relationship traversal, preferring one-to-many relationships
+    let relationship = entity
+        .relationships
+        .iter()
+        .find(|r| r.list)
+        .or_else(|| entity.relationships.first());
+    if let Some(relationship) = relationship {
+        let target_fields = summary
+            .entities
+            .iter()
+            .find(|e| e.name == relationship.target)
+            .map(|target| selection(&target.fields, MAX_NESTED_FIELDS, 6))
+            .unwrap_or_else(|| "      id".to_string());
+        let nested_arguments = if relationship.list {
+            format!("(first: {})", NESTED_FIRST)
+        } else {
+            String::new()
+        };
+        let builder = QueryBuilder::new(
+            format!("{}With{}", plural, capitalize(&relationship.field)),
+            format!(
+                "{} entities with their related {} through `{}`{}.",
+                entity.name,
+                relationship.target,
+                relationship.field,
+                if relationship.list {
+                    format!(", {} per {}", NESTED_FIRST, entity.name)
+                } else {
+                    String::new()
+                }
+            ),
+        );
+        let (required, builder) = required_arguments(entity, builder);
+        queries.push(
+            builder
+                .variable("first", "Int!", json!(first))
+                .build(format!(
+                    "  {}({}first: $first) {{\n    id\n    {}{} {{\n{}\n    }}\n  }}",
+                    collection, required, relationship.field, nested_arguments, target_fields
+                )),
+        );
+    }
+
+    Ok(ExampleQueries {
+        entity: entity.name.clone(),
+        queries,
+    })
+}
@@ -0,0 +1,619 @@
+// SPDX-License-Identifier: Apache-2.0
+use crate::error::SubgraphError;
+use crate::schema_summary::{EntitySummary, SchemaSummary};
+use crate::tabular::{cell_text, csv_record, flatten_row};
+use graphql_parser::query::{
+    parse_query, Definition, Document, Field, Number, OperationDefinition, Selection, SelectionSet,
+    Value as GraphQlValue,
+};
+use rmcp::schemars;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, ErrorKind, Write},
+    path::{Component, Path, PathBuf},
+};
+
+/// Largest page graph-node serves.
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// Number of rows returned inline with an export.
+pub const PREVIEW_ROWS: usize = 5;
+
+/// Where `export_query` writes files and how much it may write.
+#[derive(Clone, Debug)]
+pub struct ExportSettings {
+    /// Directory files are written to. Exports are refused when unset.
+    pub dir: Option<PathBuf>,
+    /// Maximum number of rows in one export. A request may ask for less.
+    pub max_rows: usize,
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            max_rows: 100_000,
+        }
+    }
+}
+
+impl ExportSettings {
+    /// Reads `EXPORT_DIR` and `EXPORT_MAX_ROWS`.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            dir: env::var("EXPORT_DIR")
+                .ok()
+                .filter(|dir| !dir.trim().is_empty())
+                .map(PathBuf::from),
+            max_rows: env::var("EXPORT_MAX_ROWS")
+                .ok()
+                .and_then(|s| s.parse::<usize>().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(defaults.max_rows),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    /// Flattened rows with a header line
+    Csv,
+    /// The rows as returned, one JSON object per line
+    Ndjson,
+    /// Flattened rows with column types taken from the schema
+    Parquet,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+/// How a column is stored, derived from the GraphQL type of its field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnKind {
+    /// `Int`
+    Int,
+    /// `Int8` and `Timestamp`
+    Int8,
+    Boolean,
+    /// `BigInt`, `BigDecimal`, `Bytes`, `ID`, `String` and enums, kept as text so
+    /// no precision is lost
+    Text,
+    /// Lists, as JSON text
+    Json,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Column {
+    pub name: String,
+    pub kind: ColumnKind,
+}
+
+/// A query over one entity collection, paged through by `id` so exports are not
+/// bound by graph-node's `skip` limit.
+#[derive(Clone, Debug)]
+pub struct PagedQuery {
+    document: Document<'static, String>,
+    operation: usize,
+    /// Key of the collection in the response, its alias if it has one.
+    pub response_key: String,
+    /// Name of the collection field on `Query`.
+    pub field_name: String,
+    /// Block the pages are read at, once pinned.
+    block: Option<i32>,
+    /// The query gives its own `block` argument, which is left alone.
+    has_block: bool,
+}
+
+impl PagedQuery {
+    pub fn parse(query: &str) -> Result<Self, SubgraphError> {
+        let rejected = |message: &str| SubgraphError::QueryRejected(message.to_string());
+        let mut document = parse_query::<String>(query)
+            .map_err(|e| {
+                SubgraphError::QueryRejected(format!("Query is not valid GraphQL: {}", e))
+            })?
+            .into_static();
+
+        let operations: Vec<usize> = document
+            .definitions
+            .iter()
+            .enumerate()
+            .filter(|(_, definition)| matches!(definition, Definition::Operation(_)))
+            .map(|(i, _)| i)
+            .collect();
+        let [operation] = operations[..] else {
+            return Err(rejected("An export query must hold exactly one operation"));
+        };
+
+        let selection_set = root_selection_set(&mut document, operation)
+            .ok_or_else(|| rejected("An export query must be a query"))?;
+        let [Selection::Field(field)] = &mut selection_set.items[..] else {
+            return Err(rejected(
+                "An export query must select exactly one entity collection",
+            ));
+        };
+        let mut has_block = false;
+        for (name, value) in &field.arguments {
+            match (name.as_str(), value) {
+                ("block", _) => has_block = true,
+                ("first" | "skip" | "orderBy" | "orderDirection", _) => {
+                    return Err(SubgraphError::QueryRejected(format!(
+                        "Exports page through the whole collection by `id`; remove `{}` and use `max_rows` to bound the export",
+                        name
+                    )))
+                }
+                ("where", GraphQlValue::Object(filter)) if filter.contains_key("id_gt") => {
+                    return Err(rejected("Exports page by `id_gt`; remove it from `where`"))
+                }
+                ("where", GraphQlValue::Object(_)) => {}
+                ("where", _) => return Err(rejected("Write the `where` filter inline, not as a variable")),
+                _ => {}
+            }
+        }
+        // The cursor is the last row's id
+        let selects_id = field.selection_set.items.iter().any(|selection| {
+            matches!(selection, Selection::Field(f) if f.name == "id" && f.alias.is_none())
+        });
+        if !selects_id {
+            field
+                .selection_set
+                .items
+                .insert(0, Selection::Field(leaf_field("id")));
+        }
+
+        Ok(Self {
+            response_key: field.alias.clone().unwrap_or_else(|| field.name.clone()),
+            field_name: field.name.clone(),
+            document,
+            operation,
+            block: None,
+            has_block,
+        })
+    }
+
+    /// Whether the pages still need a block to be read at: neither pinned nor
+    /// given by the query.
+    pub fn needs_block(&self) -> bool {
+        self.block.is_none() && !self.has_block
+    }
+
+    /// Reads the following pages at block `number`, so that rows written or
+    /// removed meanwhile don't make the export inconsistent.
+    pub fn pin_block(&mut self, number: u64) {
+        if !self.has_block {
+            // graph-node takes block numbers as a GraphQL `Int`
+            self.block = i32::try_from(number).ok();
+        }
+    }
+
+    /// The query for the `first` rows after the row with id `after`.
+    pub fn page(&self, first: usize, after: Option<&str>) -> String {
+        let mut document = self.document.clone();
+        if let Some(Selection::Field(field)) = root_selection_set(&mut document, self.operation)
+            .and_then(|selection_set| selection_set.items.first_mut())
+        {
+            let mut filter = BTreeMap::new();
+            field
+                .arguments
+                .retain(|(name, value)| match (name.as_str(), value) {
+                    ("where", GraphQlValue::Object(existing)) => {
+                        filter = existing.clone();
+                        false
+                    }
+                    _ => true,
+                });
+            if let Some(after) = after {
+                let id_gt = GraphQlValue::String(after.to_string());
+                if filter.contains_key("or") {
+                    // graph-node rejects `or` next to other conditions
+                    let user_filter = GraphQlValue::Object(std::mem::take(&mut filter));
+                    let cursor =
+                        GraphQlValue::Object(BTreeMap::from([("id_gt".to_string(), id_gt)]));
+                    filter.insert(
+                        "and".to_string(),
+                        GraphQlValue::List(vec![user_filter, cursor]),
+                    );
+                } else {
+                    filter.insert("id_gt".to_string(), id_gt);
+                }
+            }
+            let first = i32::try_from(first).unwrap_or(i32::MAX);
+            field.arguments.extend([
+                ("first".to_string(), GraphQlValue::Int(Number::from(first))),
+                ("orderBy".to_string(), GraphQlValue::Enum("id".to_string())),
+                (
+                    "orderDirection".to_string(),
+                    GraphQlValue::Enum("asc".to_string()),
+                ),
+            ]);
+            if !filter.is_empty() {
+                field
+                    .arguments
+                    .push(("where".to_string(), GraphQlValue::Object(filter)));
+            }
+            if let Some(number) = self.block {
+                let number = GraphQlValue::Int(Number::from(number));
+                field.arguments.push((
+                    "block".to_string(),
+                    GraphQlValue::Object(BTreeMap::from([("number".to_string(), number)])),
+                ));
+            }
+        }
+        document.to_string()
+    }
+
+    /// The flattened columns of the rows and their types, following the query's
+    /// selection through the schema. Lists become a single JSON column.
+    pub fn columns(&self, summary: &SchemaSummary) -> Result<Vec<Column>, SubgraphError> {
+        let mut document = self.document.clone();
+        let Some(Selection::Field(field)) = root_selection_set(&mut document, self.operation)
+            .and_then(|selection_set| selection_set.items.first())
+        else {
+            return Ok(Vec::new());
+        };
+        let entity = summary
+            .entities
+            .iter()
+            .find(|entity| entity.query_fields.collection == self.field_name)
+            .ok_or_else(|| {
+                SubgraphError::QueryRejected(format!(
+                    "`{}` is not an entity collection of this schema",
+                    self.field_name
+                ))
+            })?;
+
+        let mut columns = Vec::new();
+        collect_columns(summary, entity, &field.selection_set, "", &mut columns)?;
+        Ok(columns)
+    }
+}
+
+fn root_selection_set<'d>(
+    document: &'d mut Document<'static, String>,
+    operation: usize,
+) -> Option<&'d mut SelectionSet<'static, String>> {
+    match document.definitions.get_mut(operation)? {
+        Definition::Operation(OperationDefinition::SelectionSet(set)) => Some(set),
+        Definition::Operation(OperationDefinition::Query(query)) => Some(&mut query.selection_set),
+        _ => None,
+    }
+}
+
+fn leaf_field(name: &str) -> Field<'static, String> {
+    Field {
+        position: Default::default(),
+        alias: None,
+        name: name.to_string(),
+        arguments: Vec::new(),
+        directives: Vec::new(),
+        selection_set: SelectionSet {
+            span: Default::default(),
+            items: Vec::new(),
+        },
+    }
+}
+
+fn collect_columns(
+    summary: &SchemaSummary,
+    entity: &EntitySummary,
+    selection_set: &SelectionSet<'static, String>,
+    prefix: &str,
+    columns: &mut Vec<Column>,
+) -> Result<(), SubgraphError> {
+    for selection in &selection_set.items {
+        let Selection::Field(field) = selection else {
+            return Err(SubgraphError::QueryRejected(
+                "CSV and Parquet exports need fields selected directly, without fragments; use ndjson otherwise".to_string(),
+            ));
+        };
+        let key = field.alias.as_ref().unwrap_or(&field.name);
+        let name = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+
+        let relationship = entity.relationships.iter().find(|r| r.field == field.name);
+        let target = relationship
+            .filter(|r| !r.list)
+            .and_then(|r| summary.entities.iter().find(|e| e.name == r.target));
+        match (relationship, target) {
+            (Some(_), Some(target)) if !field.selection_set.items.is_empty() => {
+                collect_columns(summary, target, &field.selection_set, &name, columns)?
+            }
+            (Some(r), _) if r.list => columns.push(Column {
+                name,
+                kind: ColumnKind::Json,
+            }),
+            _ => {
+                let field_type = entity
+                    .fields
+                    .iter()
+                    .find(|f| f.name == field.name)
+                    .map(|f| f.field_type.as_str())
+                    .unwrap_or("String");
+                columns.push(Column {
+                    name,
+                    kind: column_kind(field_type),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn column_kind(field_type: &str) -> ColumnKind {
+    if field_type.starts_with('[') {
+        return ColumnKind::Json;
+    }
+    match field_type.trim_end_matches('!') {
+        "Int" => ColumnKind::Int,
+        "Int8" | "Timestamp" => ColumnKind::Int8,
+        "Boolean" => ColumnKind::Boolean,
+        _ => ColumnKind::Text,
+    }
+}
+
+/// Resolves `file_name` inside `root`, refusing absolute paths, `..` and symlinks
+/// leading out of it. Missing directories under `root` are created.
+pub fn resolve_export_path(root: &Path, file_name: &str) -> Result<PathBuf, SubgraphError> {
+    let outside =
+        || SubgraphError::ExportRefused(format!("`{}` is outside the export directory", file_name));
+    let relative = Path::new(file_name);
+    if file_name.trim().is_empty()
+        || !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(outside());
+    }
+    let io_error = |e: std::io::Error| {
+        SubgraphError::InternalProcessingError(format!(
+            "Failed to prepare the export directory: {}",
+            e
+        ))
+    };
+
+    // Directories are checked one level at a time, so none is ever created
+    // through a symlink leading elsewhere
+    let root = fs::canonicalize(root).map_err(io_error)?;
+    let mut dir = root.clone();
+    let file = relative.file_name().ok_or_else(outside)?;
+    for component in relative.parent().into_iter().flat_map(Path::components) {
+        let next = dir.join(component);
+        if !next.exists() {
+            fs::create_dir(&next).map_err(io_error)?;
+        }
+        dir = fs::canonicalize(&next).map_err(io_error)?;
+        if !dir.starts_with(&root) {
+            return Err(outside());
+        }
+    }
+    Ok(dir.join(file))
+}
+
+/// Streams rows to a new file, page by page.
+pub struct ExportWriter {
+    path: PathBuf,
+    sink: Sink,
+}
+
+enum Sink {
+    Csv(BufWriter<File>, Vec<Column>),
+    Ndjson(BufWriter<File>),
+    #[cfg(feature = "parquet")]
+    Parquet(Box<parquet_sink::ParquetSink>),
+}
+
+impl ExportWriter {
+    /// Creates the file, failing if it already exists. `columns` is required for
+    /// CSV and Parquet.
+    pub fn create(
+        path: &Path,
+        format: ExportFormat,
+        columns: Vec<Column>,
+    ) -> Result<Self, SubgraphError> {
+        #[cfg(not(feature = "parquet"))]
+        if format == ExportFormat::Parquet {
+            return Err(SubgraphError::ExportRefused(
+                "This build has no Parquet support; use csv or ndjson".to_string(),
+            ));
+        }
+
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .map_err(|e| match e.kind() {
+                ErrorKind::AlreadyExists => SubgraphError::ExportRefused(format!(
+                    "{} already exists; exports never overwrite a file",
+                    path.display()
+                )),
+                _ => write_error(path, e),
+            })?;
+        let sink = match format {
+            ExportFormat::Csv => {
+                let mut writer = BufWriter::new(file);
+                let header = csv_record(columns.iter().map(|column| column.name.clone()));
+                writeln!(writer, "{}", header).map_err(|e| write_error(path, e))?;
+                Sink::Csv(writer, columns)
+            }
+            ExportFormat::Ndjson => Sink::Ndjson(BufWriter::new(file)),
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet => Sink::Parquet(Box::new(parquet_sink::ParquetSink::new(
+                file, path, columns,
+            )?)),
+            #[cfg(not(feature = "parquet"))]
+            ExportFormat::Parquet => unreachable!("refused above"),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            sink,
+        })
+    }
+
+    pub fn write_rows(&mut self, rows: &[Value]) -> Result<(), SubgraphError> {
+        let path = &self.path;
+        match &mut self.sink {
+            Sink::Csv(writer, columns) => {
+                for row in rows {
+                    let row = flatten_row(row);
+                    let record = csv_record(
+                        columns
+                            .iter()
+                            .map(|column| cell_text(row.get(&column.name))),
+                    );
+                    writeln!(writer, "{}", record).map_err(|e| write_error(path, e))?;
+                }
+                Ok(())
+            }
+            Sink::Ndjson(writer) => {
+                for row in rows {
+                    writeln!(writer, "{}", row).map_err(|e| write_error(path, e))?;
+                }
+                Ok(())
+            }
+            #[cfg(feature = "parquet")]
+            Sink::Parquet(sink) => sink.write_rows(rows),
+        }
+    }
+
+    /// Flushes the file and returns its size in bytes.
+    pub fn finish(self) -> Result<u64, SubgraphError> {
+        let path = self.path;
+        match self.sink {
+            Sink::Csv(mut writer, _) | Sink::Ndjson(mut writer) => {
+                writer.flush().map_err(|e| write_error(&path, e))?
+            }
+            #[cfg(feature = "parquet")]
+            Sink::Parquet(sink) => sink.close()?,
+        }
+        Ok(fs::metadata(&path)
+            .map_err(|e| write_error(&path, e))?
+            .len())
+    }
+}
+
+fn write_error(path: &Path, e: impl std::fmt::Display) -> SubgraphError {
+    SubgraphError::InternalProcessingError(format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(feature = "parquet")]
+mod parquet_sink {
+    use super::{write_error, Column, ColumnKind};
+    use crate::error::SubgraphError;
+    use crate::tabular::flatten_row;
+    use arrow_array::{ArrayRef, BooleanArray, Int32Array, Int64Array, RecordBatch, StringArray};
+    use arrow_schema::{DataType, Field, Schema, SchemaRef};
+    use parquet::arrow::ArrowWriter;
+    use serde_json::Value;
+    use std::{
+        fs::File,
+        path::{Path, PathBuf},
+        sync::Arc,
+    };
+
+    pub(super) struct ParquetSink {
+        path: PathBuf,
+        writer: ArrowWriter<File>,
+        columns: Vec<Column>,
+        schema: SchemaRef,
+    }
+
+    impl ParquetSink {
+        pub(super) fn new(
+            file: File,
+            path: &Path,
+            columns: Vec<Column>,
+        ) -> Result<Self, SubgraphError> {
+            let fields: Vec<Field> = columns
+                .iter()
+                .map(|column| {
+                    let data_type = match column.kind {
+                        ColumnKind::Int => DataType::Int32,
+                        ColumnKind::Int8 => DataType::Int64,
+                        ColumnKind::Boolean => DataType::Boolean,
+                        ColumnKind::Text | ColumnKind::Json => DataType::Utf8,
+                    };
+                    Field::new(&column.name, data_type, true)
+                })
+                .collect();
+            let schema = Arc::new(Schema::new(fields));
+            let writer = ArrowWriter::try_new(file, schema.clone(), None)
+                .map_err(|e| write_error(path, e))?;
+            Ok(Self {
+                path: path.to_path_buf(),
+                writer,
+                columns,
+                schema,
+            })
+        }
+
+        pub(super) fn write_rows(&mut self, rows: &[Value]) -> Result<(), SubgraphError> {
+            let rows: Vec<_> = rows.iter().map(flatten_row).collect();
+            let arrays: Vec<ArrayRef> = self
+                .columns
+                .iter()
+                .map(|column| {
+                    let values = rows
+                        .iter()
+                        .map(|row| row.get(&column.name).filter(|v| !v.is_null()));
+                    let array: ArrayRef = match column.kind {
+                        ColumnKind::Int => Arc::new(Int32Array::from_iter(
+                            values.map(|v| integer(v?).and_then(|n| i32::try_from(n).ok())),
+                        )),
+                        ColumnKind::Int8 => {
+                            Arc::new(Int64Array::from_iter(values.map(|v| integer(v?))))
+                        }
+                        ColumnKind::Boolean => {
+                            Arc::new(BooleanArray::from_iter(values.map(|v| v?.as_bool())))
+                        }
+                        ColumnKind::Text | ColumnKind::Json => {
+                            Arc::new(StringArray::from_iter(values.map(|v| {
+                                Some(match v? {
+                                    Value::String(s) => s.clone(),
+                                    value => value.to_string(),
+                                })
+                            })))
+                        }
+                    };
+                    array
+                })
+                .collect();
+            let batch = RecordBatch::try_new(self.schema.clone(), arrays)
+                .map_err(|e| write_error(&self.path, e))?;
+            self.writer
+                .write(&batch)
+                .map_err(|e| write_error(&self.path, e))
+        }
+
+        pub(super) fn close(self) -> Result<(), SubgraphError> {
+            self.writer
+                .close()
+                .map(|_| ())
+                .map_err(|e| write_error(&self.path, e))
+        }
+    }
+
+    /// graph-node returns `Int8` and `Timestamp` values as strings.
+    fn integer(value: &Value) -> Option<i64> {
+        match value {
+            Value::Number(n) => n.as_i64(),
+            Value::String(s) => s.parse().ok(),
+            _ => None,
+        }
+    }
+}
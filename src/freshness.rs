@@ -0,0 +1,157 @@
+// SPDX-License-Identifier: Apache-2.0
+use graphql_parser::query::{parse_query, Definition, OperationDefinition, Selection};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    env,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Alias of the `_meta` selection added to queries, so it cannot clash with a
+/// `_meta` field the query selects itself.
+pub const FRESHNESS_ALIAS: &str = "_freshness";
+
+const META_SELECTION: &str =
+    "{ _freshness: _meta { block { number hash timestamp } hasIndexingErrors deployment } }";
+
+const DEFAULT_MAX_STALENESS: Duration = Duration::from_secs(15 * 60);
+
+/// Whether query results report how far the deployment is behind, and when that
+/// is worth a warning.
+#[derive(Clone, Copy, Debug)]
+pub struct FreshnessSettings {
+    /// Used when a request does not say whether it wants freshness.
+    pub enabled: bool,
+    /// Age of the latest indexed block beyond which the result carries a
+    /// warning. `None` never warns about staleness.
+    pub max_staleness: Option<Duration>,
+}
+
+impl Default for FreshnessSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_staleness: Some(DEFAULT_MAX_STALENESS),
+        }
+    }
+}
+
+impl FreshnessSettings {
+    /// Reads `QUERY_FRESHNESS` (`true` or `false`) and `QUERY_MAX_STALENESS_SECONDS`,
+    /// where `0` disables the staleness warning.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            enabled: env::var("QUERY_FRESHNESS")
+                .ok()
+                .and_then(|s| s.parse::<bool>().ok())
+                .unwrap_or(defaults.enabled),
+            max_staleness: match env::var("QUERY_MAX_STALENESS_SECONDS")
+                .ok()
+                .and_then(|s| s.parse::<u64>().ok())
+            {
+                Some(0) => None,
+                Some(seconds) => Some(Duration::from_secs(seconds)),
+                None => defaults.max_staleness,
+            },
+        }
+    }
+}
+
+/// State of the deployment that answered a query, taken from its `_meta` field.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Freshness {
+    pub block_number: Option<u64>,
+    pub block_hash: Option<String>,
+    /// Unix timestamp of the latest indexed block.
+    pub block_timestamp: Option<u64>,
+    /// Seconds between the latest indexed block and now.
+    pub seconds_behind: Option<u64>,
+    pub has_indexing_errors: bool,
+    pub deployment: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Adds the aliased `_meta` selection to every query operation of `query`.
+/// Returns `None` when the query cannot be parsed, is not a query or only
+/// introspects the schema, in which case it is sent unchanged.
+pub fn add_meta_selection(query: &str) -> Option<String> {
+    let meta = parse_query::<String>(META_SELECTION).ok()?.into_static();
+    let Definition::Operation(OperationDefinition::SelectionSet(meta)) = &meta.definitions[0]
+    else {
+        return None;
+    };
+
+    let mut document = parse_query::<String>(query).ok()?.into_static();
+    let mut added = false;
+    for definition in &mut document.definitions {
+        let selection_set = match definition {
+            Definition::Operation(OperationDefinition::SelectionSet(set)) => set,
+            Definition::Operation(OperationDefinition::Query(q)) => &mut q.selection_set,
+            _ => continue,
+        };
+        let introspection_only = selection_set.items.iter().all(|selection| {
+            matches!(selection, Selection::Field(field) if field.name.starts_with("__"))
+        });
+        if introspection_only {
+            continue;
+        }
+        selection_set.items.extend(meta.items.iter().cloned());
+        added = true;
+    }
+    added.then(|| document.to_string())
+}
+
+/// Removes the aliased `_meta` selection from `response["data"]` and turns it
+/// into a `Freshness` report, warning when the deployment is more than
+/// `max_staleness` behind `now` or has indexing errors.
+pub fn take_freshness(
+    response: &mut Value,
+    max_staleness: Option<Duration>,
+    now: SystemTime,
+) -> Option<Freshness> {
+    let meta = response
+        .get_mut("data")?
+        .as_object_mut()?
+        .remove(FRESHNESS_ALIAS)?;
+
+    let block = &meta["block"];
+    let block_timestamp = block["timestamp"].as_u64();
+    let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let mut freshness = Freshness {
+        block_number: block["number"].as_u64(),
+        block_hash: block["hash"].as_str().map(str::to_string),
+        block_timestamp,
+        seconds_behind: block_timestamp.map(|timestamp| now.saturating_sub(timestamp)),
+        has_indexing_errors: meta["hasIndexingErrors"].as_bool().unwrap_or(false),
+        deployment: meta["deployment"].as_str().map(str::to_string),
+        warnings: Vec::new(),
+    };
+
+    if let (Some(behind), Some(max_staleness)) = (freshness.seconds_behind, max_staleness) {
+        if behind > max_staleness.as_secs() {
+            freshness.warnings.push(format!(
+                "The deployment's latest indexed block is {} behind, more than the {} threshold. The data may be out of date.",
+                format_age(behind),
+                format_age(max_staleness.as_secs())
+            ));
+        }
+    }
+    if freshness.has_indexing_errors {
+        freshness.warnings.push(
+            "The deployment has indexing errors. Results may be incomplete or stop advancing."
+                .to_string(),
+        );
+    }
+    Some(freshness)
+}
+
+fn format_age(seconds: u64) -> String {
+    match seconds {
+        s if s >= 86_400 => format!("{}d {}h", s / 86_400, s % 86_400 / 3600),
+        s if s >= 3600 => format!("{}h {}m", s / 3600, s % 3600 / 60),
+        s if s >= 60 => format!("{}m {}s", s / 60, s % 60),
+        s => format!("{}s", s),
+    }
+}
@@ -0,0 +1,140 @@
+// SPDX-License-Identifier: Apache-2.0
+use crate::constants::{DEFAULT_GATEWAY_ID, GATEWAY_REGISTRY};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    env,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// Commit the binary was built from, set by `build.rs`.
+pub const GIT_COMMIT: &str = env!("GIT_COMMIT");
+
+/// State behind the `/readyz` probe, shared between the SSE server, the
+/// gateway probe and the metrics server.
+#[derive(Debug)]
+pub struct HealthState {
+    sse_listener_bound: AtomicBool,
+    gateway_window: Option<Duration>,
+    last_gateway_success: Mutex<Option<Instant>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GatewayCheck {
+    pub ok: bool,
+    pub window_seconds: u64,
+    pub last_success_seconds_ago: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadinessReport {
+    pub ready: bool,
+    pub sse_listener_bound: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gateway: Option<GatewayCheck>,
+}
+
+impl HealthState {
+    /// `gateway_window` enables the gateway check: the default gateway must have
+    /// answered a network subgraph query within that window to be ready.
+    pub fn new(gateway_window: Option<Duration>) -> Self {
+        Self {
+            sse_listener_bound: AtomicBool::new(false),
+            gateway_window,
+            last_gateway_success: Mutex::new(None),
+        }
+    }
+
+    /// Reads the gateway window from `READINESS_GATEWAY_WINDOW_SECONDS`; unset or
+    /// `0` leaves the gateway out of readiness.
+    pub fn from_env() -> Self {
+        let window = env::var("READINESS_GATEWAY_WINDOW_SECONDS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs);
+        Self::new(window)
+    }
+
+    pub fn gateway_window(&self) -> Option<Duration> {
+        self.gateway_window
+    }
+
+    pub fn set_sse_listener_bound(&self, bound: bool) {
+        self.sse_listener_bound.store(bound, Ordering::SeqCst);
+    }
+
+    pub fn record_gateway_success(&self) {
+        *self
+            .last_gateway_success
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+    }
+
+    pub fn readiness(&self) -> ReadinessReport {
+        let sse_listener_bound = self.sse_listener_bound.load(Ordering::SeqCst);
+        let gateway = self.gateway_window.map(|window| {
+            let elapsed = self
+                .last_gateway_success
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .map(|at| at.elapsed());
+            GatewayCheck {
+                ok: elapsed.is_some_and(|elapsed| elapsed <= window),
+                window_seconds: window.as_secs(),
+                last_success_seconds_ago: elapsed.map(|elapsed| elapsed.as_secs()),
+            }
+        });
+
+        ReadinessReport {
+            ready: sse_listener_bound && gateway.as_ref().is_none_or(|check| check.ok),
+            sse_listener_bound,
+            gateway,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct VersionInfo {
+    pub name: &'static str,
+    pub version: &'static str,
+    pub git_commit: &'static str,
+    pub default_gateway: &'static str,
+    pub gateways: BTreeMap<&'static str, &'static str>,
+}
+
+pub fn version_info() -> VersionInfo {
+    VersionInfo {
+        name: env!("CARGO_PKG_NAME"),
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit: GIT_COMMIT,
+        default_gateway: DEFAULT_GATEWAY_ID,
+        gateways: GATEWAY_REGISTRY
+            .iter()
+            .map(|(id, url)| (*id, *url))
+            .collect(),
+    }
+}
+
+pub async fn healthz_handler() -> impl IntoResponse {
+    (StatusCode::OK, "ok")
+}
+
+pub async fn readyz_handler(State(health): State<Arc<HealthState>>) -> impl IntoResponse {
+    let report = health.readiness();
+    let status = if report.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
+
+pub async fn version_handler() -> impl IntoResponse {
+    Json(version_info())
+}
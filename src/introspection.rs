@@ -0,0 +1,270 @@
+// SPDX-License-Identifier: Apache-2.0
+use crate::error::SubgraphError;
+use rmcp::schemars;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The standard introspection query, as sent by GraphQL tooling.
+pub const INTROSPECTION_QUERY: &str = r#"
+query IntrospectionQuery {
+  __schema {
+    queryType { name }
+    mutationType { name }
+    subscriptionType { name }
+    types { ...FullType }
+    directives {
+      name
+      description
+      locations
+      args { ...InputValue }
+    }
+  }
+}
+
+fragment FullType on __Type {
+  kind
+  name
+  description
+  fields(includeDeprecated: true) {
+    name
+    description
+    args { ...InputValue }
+    type { ...TypeRef }
+    isDeprecated
+    deprecationReason
+  }
+  inputFields { ...InputValue }
+  interfaces { ...TypeRef }
+  enumValues(includeDeprecated: true) {
+    name
+    description
+    isDeprecated
+    deprecationReason
+  }
+  possibleTypes { ...TypeRef }
+}
+
+fragment InputValue on __InputValue {
+  name
+  description
+  type { ...TypeRef }
+  defaultValue
+}
+
+fragment TypeRef on __Type {
+  kind
+  name
+  ofType {
+    kind
+    name
+    ofType {
+      kind
+      name
+      ofType {
+        kind
+        name
+        ofType {
+          kind
+          name
+          ofType {
+            kind
+            name
+            ofType {
+              kind
+              name
+              ofType {
+                kind
+                name
+              }
+            }
+          }
+        }
+      }
+    }
+  }
+}
+"#;
+
+const BUILT_IN_SCALARS: [&str; 5] = ["String", "Int", "Float", "Boolean", "ID"];
+
+/// Which form the introspection schema tool returns.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum IntrospectionFormat {
+    /// The query API printed as SDL.
+    #[default]
+    Sdl,
+    /// The `data` of the introspection response.
+    Json,
+}
+
+fn type_ref(value: &Value) -> String {
+    match value["kind"].as_str() {
+        Some("NON_NULL") => format!("{}!", type_ref(&value["ofType"])),
+        Some("LIST") => format!("[{}]", type_ref(&value["ofType"])),
+        _ => value["name"].as_str().unwrap_or_default().to_string(),
+    }
+}
+
+fn description(value: &Value, indent: &str) -> String {
+    match value["description"].as_str().map(str::trim) {
+        Some(text) if !text.is_empty() => {
+            if text.contains('\n') || text.contains('"') {
+                let body = text.replace("\"\"\"", "\\\"\"\"");
+                let body = body
+                    .lines()
+                    .map(|line| format!("{}{}", indent, line))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                format!("{}\"\"\"\n{}\n{}\"\"\"\n", indent, body, indent)
+            } else {
+                format!("{}\"{}\"\n", indent, text)
+            }
+        }
+        _ => String::new(),
+    }
+}
+
+fn deprecation(value: &Value) -> String {
+    if !value["isDeprecated"].as_bool().unwrap_or(false) {
+        return String::new();
+    }
+    match value["deprecationReason"].as_str() {
+        Some(reason) => format!(" @deprecated(reason: {})", Value::from(reason)),
+        None => " @deprecated".to_string(),
+    }
+}
+
+fn input_value(value: &Value) -> String {
+    let mut printed = format!(
+        "{}: {}",
+        value["name"].as_str().unwrap_or_default(),
+        type_ref(&value["type"])
+    );
+    if let Some(default) = value["defaultValue"].as_str() {
+        printed.push_str(" = ");
+        printed.push_str(default);
+    }
+    printed
+}
+
+fn names(values: &Value) -> Vec<String> {
+    values
+        .as_array()
+        .map(|values| values.iter().map(type_ref).collect())
+        .unwrap_or_default()
+}
+
+fn print_type(value: &Value) -> Option<String> {
+    let name = value["name"].as_str()?;
+    let mut printed = description(value, "");
+    match value["kind"].as_str()? {
+        "SCALAR" => printed.push_str(&format!("scalar {}", name)),
+        "UNION" => printed.push_str(&format!(
+            "union {} = {}",
+            name,
+            names(&value["possibleTypes"]).join(" | ")
+        )),
+        "ENUM" => {
+            printed.push_str(&format!("enum {} {{\n", name));
+            for enum_value in value["enumValues"].as_array()? {
+                printed.push_str(&description(enum_value, "  "));
+                printed.push_str(&format!(
+                    "  {}{}\n",
+                    enum_value["name"].as_str()?,
+                    deprecation(enum_value)
+                ));
+            }
+            printed.push('}');
+        }
+        "INPUT_OBJECT" => {
+            printed.push_str(&format!("input {} {{\n", name));
+            for field in value["inputFields"].as_array()? {
+                printed.push_str(&description(field, "  "));
+                printed.push_str(&format!("  {}\n", input_value(field)));
+            }
+            printed.push('}');
+        }
+        kind @ ("OBJECT" | "INTERFACE") => {
+            let keyword = if kind == "OBJECT" {
+                "type"
+            } else {
+                "interface"
+            };
+            let interfaces = names(&value["interfaces"]);
+            printed.push_str(&format!("{} {}", keyword, name));
+            if !interfaces.is_empty() {
+                printed.push_str(&format!(" implements {}", interfaces.join(" & ")));
+            }
+            printed.push_str(" {\n");
+            for field in value["fields"].as_array()? {
+                printed.push_str(&description(field, "  "));
+                let args: Vec<String> = field["args"]
+                    .as_array()
+                    .map(|args| args.iter().map(input_value).collect())
+                    .unwrap_or_default();
+                let args = if args.is_empty() {
+                    String::new()
+                } else {
+                    format!("({})", args.join(", "))
+                };
+                printed.push_str(&format!(
+                    "  {}{}: {}{}\n",
+                    field["name"].as_str()?,
+                    args,
+                    type_ref(&field["type"]),
+                    deprecation(field)
+                ));
+            }
+            printed.push('}');
+        }
+        _ => return None,
+    }
+    Some(printed)
+}
+
+/// Prints the schema described by an introspection result (the response `data`)
+/// as SDL. Introspection types and built-in scalars are left out.
+pub fn print_introspection_sdl(data: &Value) -> Result<String, SubgraphError> {
+    let schema = &data["__schema"];
+    let types = schema["types"].as_array().ok_or_else(|| {
+        SubgraphError::InternalProcessingError(
+            "Introspection result has no __schema.types".to_string(),
+        )
+    })?;
+
+    let mut printed = Vec::new();
+    let roots = [
+        ("query", &schema["queryType"]["name"], "Query"),
+        ("mutation", &schema["mutationType"]["name"], "Mutation"),
+        (
+            "subscription",
+            &schema["subscriptionType"]["name"],
+            "Subscription",
+        ),
+    ];
+    if roots
+        .iter()
+        .any(|(_, name, default)| name.as_str().is_some_and(|name| name != *default))
+    {
+        let operations: Vec<String> = roots
+            .iter()
+            .filter_map(|(operation, name, _)| Some(format!("  {}: {}", operation, name.as_str()?)))
+            .collect();
+        printed.push(format!("schema {{\n{}\n}}", operations.join("\n")));
+    }
+
+    printed.extend(
+        types
+            .iter()
+            .filter(|t| {
+                t["name"].as_str().is_some_and(|name| {
+                    !name.starts_with("__") && !BUILT_IN_SCALARS.contains(&name)
+                })
+            })
+            .filter_map(print_type),
+    );
+    Ok(printed.join("\n\n") + "\n")
+}
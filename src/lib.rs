@@ -1,11 +1,35 @@
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod audit;
+pub mod backend;
+pub mod batch;
+pub mod cli;
 pub mod constants;
+pub mod cross_chain;
+pub mod decode;
 pub mod error;
+pub mod example_queries;
+pub mod export;
+pub mod freshness;
+pub mod health;
+pub mod introspection;
 pub mod metrics;
+pub mod network_subgraph;
+pub mod policy;
+pub mod query_analysis;
+pub mod rate_limit;
+pub mod recording;
+pub mod schema_cache;
+pub mod schema_summary;
 pub mod server;
+pub mod server_builder;
 pub mod server_helpers;
+pub mod tabular;
+pub mod telemetry;
+pub mod transform;
 pub mod types;
+pub mod usage;
 
 pub use error::SubgraphError;
 pub use server::SubgraphServer;
+pub use server_builder::SubgraphServerBuilder;
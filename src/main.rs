@@ -1,11 +1,38 @@
 // SPDX-License-Identifier: Apache-2.0
+pub mod audit;
+pub mod backend;
+pub mod batch;
+pub mod cli;
 pub mod constants;
+pub mod cross_chain;
+pub mod decode;
 pub mod error;
+pub mod example_queries;
+pub mod export;
+pub mod freshness;
+pub mod health;
+pub mod introspection;
 pub mod metrics;
+pub mod network_subgraph;
+pub mod policy;
+pub mod query_analysis;
+pub mod rate_limit;
+pub mod recording;
+pub mod schema_cache;
+pub mod schema_summary;
 pub mod server;
+pub mod server_builder;
 pub mod server_helpers;
+pub mod tabular;
+pub mod telemetry;
+pub mod transform;
 pub mod types;
+pub mod usage;
+use crate::cli::{Command, OutputFormat};
+use crate::constants::{DEFAULT_GATEWAY_ID, GATEWAY_REGISTRY};
+use crate::health::HealthState;
 use crate::metrics::METRICS;
+use crate::rate_limit::{RateLimitConfig, RateLimiter};
 use anyhow::Result;
 use axum::{
     body::Body,
@@ -20,14 +47,26 @@ use rmcp::{
     ServiceExt,
 };
 pub use server::SubgraphServer;
-use std::{env, net::SocketAddr, sync::Arc, time::Duration};
+use std::{env, net::SocketAddr, process::ExitCode, sync::Arc, time::Duration};
 use tokio::io;
 use tokio_util::sync::CancellationToken;
-use tracing::info;
+use tracing::{info, warn};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
+    /// Run a single command and exit instead of starting a server
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Output format of commands
+    #[arg(long, value_enum, default_value_t = OutputFormat::Json, global = true)]
+    format: OutputFormat,
+
+    /// Gateway ID used by commands, instead of the default gateway
+    #[arg(long, global = true)]
+    gateway: Option<String>,
+
     /// Start the server in SSE mode
     #[arg(long)]
     sse: bool,
@@ -38,23 +77,45 @@ struct Cli {
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<ExitCode> {
     let cli = Cli::parse();
 
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
-        .try_init()
-        .unwrap_or_else(|e| eprintln!("env_logger init failed: {}", e));
+    let _telemetry = telemetry::init_tracing();
 
     if cli.init_config {
         println!("Configuration initialization logic goes here.");
-        return Ok(());
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(command) = cli.command {
+        let server = SubgraphServer::new();
+        return match cli::run(&server, command, cli.gateway.as_deref(), cli.format).await {
+            Ok(output) => {
+                println!("{}", output);
+                Ok(ExitCode::SUCCESS)
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                Ok(ExitCode::from(cli::exit_code(&e)))
+            }
+        };
     }
 
     if cli.sse {
         let shutdown_token = CancellationToken::new();
+        let health = Arc::new(HealthState::from_env());
 
-        let sse_server_handle = tokio::spawn(start_sse_server(shutdown_token.clone()));
-        let metrics_server_handle = tokio::spawn(start_metrics_server(shutdown_token.clone()));
+        let sse_server_handle =
+            tokio::spawn(start_sse_server(shutdown_token.clone(), health.clone()));
+        let metrics_server_handle =
+            tokio::spawn(start_metrics_server(shutdown_token.clone(), health.clone()));
+        if let Some(window) = health.gateway_window() {
+            tokio::spawn(run_gateway_probe(
+                health.clone(),
+                window,
+                shutdown_token.clone(),
+            ));
+        }
 
         let mut sigterm =
             tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
@@ -76,9 +137,10 @@ async fn main() -> Result<()> {
         tokio::time::sleep(Duration::from_secs(1)).await;
 
         info!("All services shutdown complete.");
-        Ok(())
+        Ok(ExitCode::SUCCESS)
     } else {
-        start_stdio_server().await
+        start_stdio_server().await?;
+        Ok(ExitCode::SUCCESS)
     }
 }
 
@@ -92,7 +154,10 @@ async fn start_stdio_server() -> Result<()> {
     Ok(())
 }
 
-async fn start_sse_server(shutdown_token: CancellationToken) -> Result<()> {
+async fn start_sse_server(
+    shutdown_token: CancellationToken,
+    health: Arc<HealthState>,
+) -> Result<()> {
     info!("Starting SSE Subgraph MCP Server");
     let host = env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
     let port = env::var("PORT").unwrap_or_else(|_| "8000".to_string());
@@ -113,11 +178,27 @@ async fn start_sse_server(shutdown_token: CancellationToken) -> Result<()> {
 
     let sse_server = SseServer::serve_with_config(config).await?;
     info!("SSE Server listening on {}", sse_server.config.bind);
+    health.set_sse_listener_bound(true);
+
+    let rate_limiter = RateLimitConfig::from_env().map(|config| {
+        info!(
+            "Rate limiting enabled: {:?} (query tools), {:?} (metadata tools)",
+            config.query, config.metadata
+        );
+        Arc::new(RateLimiter::new(config))
+    });
 
-    let service_shutdown_token = sse_server.with_service_directly(SubgraphServer::new);
+    let service_shutdown_token = sse_server.with_service_directly(move || {
+        let server = SubgraphServer::new();
+        match &rate_limiter {
+            Some(rate_limiter) => server.with_rate_limiter(rate_limiter.clone()),
+            None => server,
+        }
+    });
     info!("Subgraph MCP Service attached to SSE server");
 
     shutdown_token.cancelled().await;
+    health.set_sse_listener_bound(false);
 
     info!("SSE Server shutdown signal received. Giving tasks a moment to finish...");
     service_shutdown_token.cancel();
@@ -127,6 +208,38 @@ async fn start_sse_server(shutdown_token: CancellationToken) -> Result<()> {
     Ok(())
 }
 
+/// Periodically queries the network subgraph through the default gateway so that
+/// `/readyz` can report whether the gateway answered within `window`.
+async fn run_gateway_probe(
+    health: Arc<HealthState>,
+    window: Duration,
+    shutdown_token: CancellationToken,
+) {
+    let Ok(api_key) = env::var("GATEWAY_API_KEY") else {
+        warn!("READINESS_GATEWAY_WINDOW_SECONDS is set but GATEWAY_API_KEY is not; the gateway readiness check cannot pass");
+        return;
+    };
+    let Some(gateway_url) = GATEWAY_REGISTRY.get(DEFAULT_GATEWAY_ID) else {
+        warn!("Default gateway not found in registry; the gateway readiness check cannot pass");
+        return;
+    };
+
+    // Probe several times per window so a single failed attempt does not flip readiness
+    let period = (window / 3).clamp(Duration::from_secs(5), Duration::from_secs(60));
+    let server = SubgraphServer::new();
+    let mut interval = tokio::time::interval(period);
+    loop {
+        tokio::select! {
+            _ = shutdown_token.cancelled() => break,
+            _ = interval.tick() => {}
+        }
+        match server.probe_gateway(&api_key, gateway_url).await {
+            Ok(()) => health.record_gateway_success(),
+            Err(e) => warn!("Gateway readiness probe failed: {}", e),
+        }
+    }
+}
+
 async fn metrics_handler(State(registry): State<Arc<Registry>>) -> impl IntoResponse {
     let mut buffer = String::new();
     if let Err(e) = encode(&mut buffer, &registry) {
@@ -146,7 +259,10 @@ async fn metrics_handler(State(registry): State<Arc<Registry>>) -> impl IntoResp
         .unwrap()
 }
 
-async fn start_metrics_server(shutdown_token: CancellationToken) -> Result<()> {
+async fn start_metrics_server(
+    shutdown_token: CancellationToken,
+    health: Arc<HealthState>,
+) -> Result<()> {
     let mut registry = <Registry as Default>::default();
     METRICS.register(&mut registry);
     let registry = Arc::new(registry);
@@ -164,7 +280,14 @@ async fn start_metrics_server(shutdown_token: CancellationToken) -> Result<()> {
 
     let app = axum::Router::new()
         .route("/metrics", axum::routing::get(metrics_handler))
-        .with_state(registry);
+        .with_state(registry)
+        .merge(
+            axum::Router::new()
+                .route("/readyz", axum::routing::get(health::readyz_handler))
+                .with_state(health),
+        )
+        .route("/healthz", axum::routing::get(health::healthz_handler))
+        .route("/version", axum::routing::get(health::version_handler));
 
     info!("Metrics server listening on {}", bind_addr);
     let listener = tokio::net::TcpListener::bind(bind_addr).await?;
@@ -1,19 +1,64 @@
 // SPDX-License-Identifier: Apache-2.0
+use crate::constants::{POLICY_VIOLATION_ERROR_CODE, RATE_LIMITED_ERROR_CODE};
+use crate::error::SubgraphError;
 use once_cell::sync::Lazy;
 use prometheus_client::{
     encoding::EncodeLabelSet,
-    metrics::{counter::Counter, family::Family, histogram::Histogram},
+    metrics::{counter::Counter, family::Family, gauge::Gauge, histogram::Histogram},
     registry::Registry,
 };
+use std::{cell::Cell, env};
+use tracing::{field::Empty, Instrument};
 
-const DEFAULT_BUCKETS: [f64; 11] = [
-    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+tokio::task_local! {
+    static TOOL_ERROR_KIND: Cell<Option<&'static str>>;
+}
+
+/// Notes the `SubgraphError` a tool call is failing with, before the tool maps it
+/// to an MCP error. The tool metrics are labelled with its kind, which tells
+/// timeouts, HTTP and GraphQL errors apart where the MCP error code cannot.
+pub fn note_tool_error(error: &SubgraphError) {
+    let _ = TOOL_ERROR_KIND.try_with(|kind| kind.set(Some(error.error_kind())));
+}
+
+// Gateway requests time out after 120 seconds by default, so the buckets reach past that
+const DEFAULT_DURATION_BUCKETS: [f64; 14] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0,
 ];
 
+const DEFAULT_RESPONSE_SIZE_BUCKETS: [f64; 9] = [
+    256.0, 1024.0, 4096.0, 16384.0, 65536.0, 262144.0, 1048576.0, 4194304.0, 16777216.0,
+];
+
+/// Reads histogram buckets as a comma-separated list of upper bounds.
+pub fn buckets_from_env(name: &str, default: &[f64]) -> Vec<f64> {
+    let parsed = env::var(name).ok().and_then(|value| {
+        let mut buckets = value
+            .split(',')
+            .map(|bound| bound.trim().parse::<f64>().ok())
+            .collect::<Option<Vec<f64>>>()?;
+        buckets.sort_by(f64::total_cmp);
+        buckets.dedup();
+        (!buckets.is_empty()).then_some(buckets)
+    });
+    parsed.unwrap_or_else(|| default.to_vec())
+}
+
+static DURATION_BUCKETS: Lazy<Vec<f64>> =
+    Lazy::new(|| buckets_from_env("METRICS_DURATION_BUCKETS", &DEFAULT_DURATION_BUCKETS));
+
+static RESPONSE_SIZE_BUCKETS: Lazy<Vec<f64>> = Lazy::new(|| {
+    buckets_from_env(
+        "METRICS_RESPONSE_SIZE_BUCKETS",
+        &DEFAULT_RESPONSE_SIZE_BUCKETS,
+    )
+});
+
 #[derive(Clone, Hash, PartialEq, Eq, Debug, EncodeLabelSet)]
 pub struct ToolCallLabels {
     pub tool_name: String,
     pub status: String,
+    pub error_kind: String,
     pub api_key: String,
 }
 
@@ -26,13 +71,58 @@ pub struct ToolCallDurationLabels {
 #[derive(Clone, Hash, PartialEq, Eq, Debug, EncodeLabelSet)]
 pub struct GatewayRequestLabels {
     pub endpoint_type: String,
+    pub gateway: String,
     pub status: String,
+    pub error_kind: String,
     pub api_key: String,
 }
 
 #[derive(Clone, Hash, PartialEq, Eq, Debug, EncodeLabelSet)]
 pub struct GatewayRequestDurationLabels {
     pub endpoint_type: String,
+    pub gateway: String,
+    pub api_key: String,
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, Debug, EncodeLabelSet)]
+pub struct ToolInFlightLabels {
+    pub tool_name: String,
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, Debug, EncodeLabelSet)]
+pub struct GatewayInFlightLabels {
+    pub endpoint_type: String,
+    pub gateway: String,
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, Debug, EncodeLabelSet)]
+pub struct ToolResponseSizeLabels {
+    pub tool_name: String,
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, Debug, EncodeLabelSet)]
+pub struct RateLimitedLabels {
+    pub tool_name: String,
+    pub tool_class: String,
+    pub reason: String,
+    pub api_key: String,
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, Debug, EncodeLabelSet)]
+pub struct PolicyViolationLabels {
+    pub tool_name: String,
+    pub api_key: String,
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, Debug, EncodeLabelSet)]
+pub struct BatchItemLabels {
+    pub status: String,
+    pub error_kind: String,
+    pub api_key: String,
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, Debug, EncodeLabelSet)]
+pub struct BatchItemDurationLabels {
     pub api_key: String,
 }
 
@@ -42,6 +132,29 @@ pub struct Metrics {
     pub mcp_tool_call_duration_seconds: Family<ToolCallDurationLabels, Histogram>,
     pub gateway_requests_total: Family<GatewayRequestLabels, Counter>,
     pub gateway_request_duration_seconds: Family<GatewayRequestDurationLabels, Histogram>,
+    pub rate_limited_calls_total: Family<RateLimitedLabels, Counter>,
+    pub policy_violations_total: Family<PolicyViolationLabels, Counter>,
+    pub mcp_tool_calls_in_flight: Family<ToolInFlightLabels, Gauge>,
+    pub gateway_requests_in_flight: Family<GatewayInFlightLabels, Gauge>,
+    pub mcp_tool_response_size_bytes: Family<ToolResponseSizeLabels, Histogram>,
+    pub batch_items_total: Family<BatchItemLabels, Counter>,
+    pub batch_item_duration_seconds: Family<BatchItemDurationLabels, Histogram>,
+}
+
+/// Decrements an in-flight gauge when dropped, so cancelled calls are released too.
+struct InFlightGuard(Gauge);
+
+impl InFlightGuard {
+    fn new(gauge: &Gauge) -> Self {
+        gauge.inc();
+        Self(gauge.clone())
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
 }
 
 impl Metrics {
@@ -50,12 +163,25 @@ impl Metrics {
             mcp_tool_calls_total: Family::<ToolCallLabels, Counter>::default(),
             mcp_tool_call_duration_seconds:
                 Family::<ToolCallDurationLabels, Histogram>::new_with_constructor(|| {
-                    Histogram::new(DEFAULT_BUCKETS)
+                    Histogram::new(DURATION_BUCKETS.iter().copied())
                 }),
             gateway_requests_total: Family::<GatewayRequestLabels, Counter>::default(),
             gateway_request_duration_seconds:
                 Family::<GatewayRequestDurationLabels, Histogram>::new_with_constructor(|| {
-                    Histogram::new(DEFAULT_BUCKETS)
+                    Histogram::new(DURATION_BUCKETS.iter().copied())
+                }),
+            rate_limited_calls_total: Family::<RateLimitedLabels, Counter>::default(),
+            policy_violations_total: Family::<PolicyViolationLabels, Counter>::default(),
+            mcp_tool_calls_in_flight: Family::<ToolInFlightLabels, Gauge>::default(),
+            gateway_requests_in_flight: Family::<GatewayInFlightLabels, Gauge>::default(),
+            mcp_tool_response_size_bytes:
+                Family::<ToolResponseSizeLabels, Histogram>::new_with_constructor(|| {
+                    Histogram::new(RESPONSE_SIZE_BUCKETS.iter().copied())
+                }),
+            batch_items_total: Family::<BatchItemLabels, Counter>::default(),
+            batch_item_duration_seconds:
+                Family::<BatchItemDurationLabels, Histogram>::new_with_constructor(|| {
+                    Histogram::new(DURATION_BUCKETS.iter().copied())
                 }),
         }
     }
@@ -84,6 +210,48 @@ impl Metrics {
             "Duration of Graph Gateway requests in seconds",
             self.gateway_request_duration_seconds.clone(),
         );
+
+        registry.register(
+            "subgraph_mcp_rate_limited_calls",
+            "Total number of MCP tool calls rejected by rate or concurrency limits",
+            self.rate_limited_calls_total.clone(),
+        );
+
+        registry.register(
+            "subgraph_mcp_policy_violations",
+            "Total number of tool calls rejected by the deployment policy",
+            self.policy_violations_total.clone(),
+        );
+
+        registry.register(
+            "subgraph_mcp_tool_calls_in_flight",
+            "Number of MCP tool calls currently executing",
+            self.mcp_tool_calls_in_flight.clone(),
+        );
+
+        registry.register(
+            "subgraph_mcp_gateway_requests_in_flight",
+            "Number of Graph Gateway requests currently awaiting a response",
+            self.gateway_requests_in_flight.clone(),
+        );
+
+        registry.register(
+            "subgraph_mcp_tool_response_size_bytes",
+            "Size of the content returned by MCP tool calls in bytes",
+            self.mcp_tool_response_size_bytes.clone(),
+        );
+
+        registry.register(
+            "subgraph_mcp_batch_items",
+            "Total number of queries run by execute_queries_batch calls",
+            self.batch_items_total.clone(),
+        );
+
+        registry.register(
+            "subgraph_mcp_batch_item_duration_seconds",
+            "Duration of the individual queries of execute_queries_batch calls in seconds",
+            self.batch_item_duration_seconds.clone(),
+        );
     }
 
     pub async fn observe_tool_call<F, Fut, T>(&self, tool_name: &str, api_key: &str, f: F) -> T
@@ -92,20 +260,42 @@ impl Metrics {
         Fut: std::future::Future<Output = T>,
         T: IsSuccess,
     {
+        let span = tracing::info_span!(
+            "mcp.tool",
+            otel.name = %format!("tool {}", tool_name),
+            tool.name = %tool_name,
+            subgraph.deployment = Empty,
+            status = Empty,
+            latency_ms = Empty,
+            otel.status_code = Empty,
+        );
+        let in_flight = InFlightGuard::new(&self.mcp_tool_calls_in_flight.get_or_create(
+            &ToolInFlightLabels {
+                tool_name: tool_name.to_string(),
+            },
+        ));
         let start_time = std::time::Instant::now();
-        let result = f().await;
+        let (result, noted_kind) = TOOL_ERROR_KIND
+            .scope(Cell::new(None), async {
+                let result = f().instrument(span.clone()).await;
+                (result, TOOL_ERROR_KIND.with(Cell::get))
+            })
+            .await;
         let duration = start_time.elapsed();
+        drop(in_flight);
 
-        let status = if result.is_success() {
-            "success"
+        let (status, error_kind) = if result.is_success() {
+            ("success", result.error_kind())
         } else {
-            "error"
+            ("error", noted_kind.unwrap_or_else(|| result.error_kind()))
         };
+        record_span_outcome(&span, status, duration);
 
         self.mcp_tool_calls_total
             .get_or_create(&ToolCallLabels {
                 tool_name: tool_name.to_string(),
                 status: status.to_string(),
+                error_kind: error_kind.to_string(),
                 api_key: api_key.to_string(),
             })
             .inc();
@@ -120,9 +310,60 @@ impl Metrics {
         result
     }
 
+    /// Observes one query of a batch, which shares its tool call with the others.
+    pub async fn observe_batch_item<F, Fut, T>(
+        &self,
+        index: usize,
+        deployment: &str,
+        api_key: &str,
+        f: F,
+    ) -> T
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = T>,
+        T: IsSuccess,
+    {
+        let span = tracing::info_span!(
+            "mcp.batch_item",
+            otel.name = %format!("batch item {}", index),
+            batch.index = index,
+            subgraph.deployment = %deployment,
+            status = Empty,
+            latency_ms = Empty,
+            otel.status_code = Empty,
+        );
+        let start_time = std::time::Instant::now();
+        let result = f().instrument(span.clone()).await;
+        let duration = start_time.elapsed();
+
+        let status = if result.is_success() {
+            "success"
+        } else {
+            "error"
+        };
+        record_span_outcome(&span, status, duration);
+
+        self.batch_items_total
+            .get_or_create(&BatchItemLabels {
+                status: status.to_string(),
+                error_kind: result.error_kind().to_string(),
+                api_key: api_key.to_string(),
+            })
+            .inc();
+
+        self.batch_item_duration_seconds
+            .get_or_create(&BatchItemDurationLabels {
+                api_key: api_key.to_string(),
+            })
+            .observe(duration.as_secs_f64());
+
+        result
+    }
+
     pub async fn observe_gateway_request<F, Fut, T>(
         &self,
         endpoint_type: &str,
+        gateway: &str,
         api_key: &str,
         f: F,
     ) -> T
@@ -131,20 +372,42 @@ impl Metrics {
         Fut: std::future::Future<Output = T>,
         T: IsSuccess,
     {
+        let span = tracing::info_span!(
+            "gateway.request",
+            otel.name = %format!("gateway {}", endpoint_type),
+            otel.kind = "client",
+            gateway.endpoint_type = %endpoint_type,
+            subgraph.deployment = Empty,
+            http.response.status_code = Empty,
+            status = Empty,
+            latency_ms = Empty,
+            otel.status_code = Empty,
+        );
+        let gateway = gateway.to_string();
+        let in_flight = InFlightGuard::new(&self.gateway_requests_in_flight.get_or_create(
+            &GatewayInFlightLabels {
+                endpoint_type: endpoint_type.to_string(),
+                gateway: gateway.clone(),
+            },
+        ));
         let start_time = std::time::Instant::now();
-        let result = f().await;
+        let result = f().instrument(span.clone()).await;
         let duration = start_time.elapsed();
+        drop(in_flight);
 
         let status = if result.is_success() {
             "success"
         } else {
             "error"
         };
+        record_span_outcome(&span, status, duration);
 
         self.gateway_requests_total
             .get_or_create(&GatewayRequestLabels {
                 endpoint_type: endpoint_type.to_string(),
+                gateway: gateway.clone(),
                 status: status.to_string(),
+                error_kind: result.error_kind().to_string(),
                 api_key: api_key.to_string(),
             })
             .inc();
@@ -152,22 +415,119 @@ impl Metrics {
         self.gateway_request_duration_seconds
             .get_or_create(&GatewayRequestDurationLabels {
                 endpoint_type: endpoint_type.to_string(),
+                gateway,
                 api_key: api_key.to_string(),
             })
             .observe(duration.as_secs_f64());
 
         result
     }
+
+    pub fn record_rate_limited(
+        &self,
+        tool_name: &str,
+        tool_class: &str,
+        reason: &str,
+        api_key: &str,
+    ) {
+        self.rate_limited_calls_total
+            .get_or_create(&RateLimitedLabels {
+                tool_name: tool_name.to_string(),
+                tool_class: tool_class.to_string(),
+                reason: reason.to_string(),
+                api_key: api_key.to_string(),
+            })
+            .inc();
+    }
+
+    pub fn record_tool_response_size(&self, tool_name: &str, bytes: usize) {
+        self.mcp_tool_response_size_bytes
+            .get_or_create(&ToolResponseSizeLabels {
+                tool_name: tool_name.to_string(),
+            })
+            .observe(bytes as f64);
+    }
+
+    pub fn record_policy_violation(&self, tool_name: &str, api_key: &str) {
+        self.policy_violations_total
+            .get_or_create(&PolicyViolationLabels {
+                tool_name: tool_name.to_string(),
+                api_key: api_key.to_string(),
+            })
+            .inc();
+    }
+}
+
+fn record_span_outcome(span: &tracing::Span, status: &str, duration: std::time::Duration) {
+    span.record("status", status);
+    span.record("latency_ms", duration.as_millis() as u64);
+    span.record(
+        "otel.status_code",
+        if status == "success" { "OK" } else { "ERROR" },
+    );
 }
 
 pub trait IsSuccess {
     fn is_success(&self) -> bool;
+    /// Class of the failure used as the `error_kind` label, `none` on success.
+    fn error_kind(&self) -> &'static str;
 }
 
-impl<T, E> IsSuccess for Result<T, E> {
+impl<T, E: ErrorKind> IsSuccess for Result<T, E> {
     fn is_success(&self) -> bool {
         self.is_ok()
     }
+
+    fn error_kind(&self) -> &'static str {
+        match self {
+            Ok(_) => "none",
+            Err(e) => e.error_kind(),
+        }
+    }
+}
+
+pub trait ErrorKind {
+    fn error_kind(&self) -> &'static str;
+}
+
+impl ErrorKind for SubgraphError {
+    fn error_kind(&self) -> &'static str {
+        match self {
+            SubgraphError::ApiKeyNotSet => "api_key_not_set",
+            SubgraphError::HttpError(e) if e.is_timeout() => "timeout",
+            SubgraphError::HttpError(e) if e.is_connect() => "connect",
+            SubgraphError::HttpError(e) if e.is_status() => "http_status",
+            SubgraphError::HttpError(e) if e.is_decode() => "decode",
+            SubgraphError::HttpError(_) => "http",
+            SubgraphError::HttpStatus(_) => "http_status",
+            SubgraphError::GraphQlError(_) => "graphql",
+            SubgraphError::JsonError(_) => "json",
+            SubgraphError::InternalProcessingError(_) => "internal",
+            SubgraphError::InvalidGatewayId(_) => "invalid_gateway",
+            SubgraphError::BudgetExceeded(_) => "budget_exceeded",
+            SubgraphError::PolicyViolation(_) => "policy_violation",
+            SubgraphError::QueryRejected(_) => "query_rejected",
+            SubgraphError::InvalidTransform(_) => "invalid_transform",
+            SubgraphError::ExportRefused(_) => "export_refused",
+        }
+    }
+}
+
+// Errors a tool raises without noting a `SubgraphError`, such as invalid
+// parameters or rate limiting, are classified by their MCP error code.
+impl ErrorKind for rmcp::Error {
+    fn error_kind(&self) -> &'static str {
+        match self.code.0 {
+            -32600 => "invalid_request",
+            -32601 => "method_not_found",
+            -32602 => "invalid_params",
+            -32603 => "internal_error",
+            -32002 => "resource_not_found",
+            RATE_LIMITED_ERROR_CODE => "rate_limited",
+            POLICY_VIOLATION_ERROR_CODE => "policy_violation",
+            _ => "other",
+        }
+    }
 }
 
 pub static METRICS: Lazy<Metrics> = Lazy::new(Metrics::new);
@@ -0,0 +1,542 @@
+// SPDX-License-Identifier: Apache-2.0
+use crate::backend::{GatewayBackend, GatewayResponse};
+use crate::error::SubgraphError;
+use crate::metrics::METRICS;
+use crate::server::SubgraphServer;
+use crate::types::{GraphQLRequest, GraphQLResponse};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+
+// Entities mirror the network subgraph schema. Fields are optional because each
+// query selects only what it needs; unselected fields are left out when serialized.
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Subgraph {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<SubgraphMetadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_version: Option<SubgraphVersion>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubgraphMetadata {
+    pub display_name: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubgraphVersion {
+    pub subgraph_deployment: SubgraphDeployment,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubgraphDeployment {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipfs_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<SubgraphManifest>,
+    /// Total query fees in wei, as a decimal string.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_fees_amount: Option<String>,
+    /// The versions publishing the deployment, one per subgraph it belongs to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub versions: Option<Vec<DeploymentVersion>>,
+}
+
+impl SubgraphDeployment {
+    /// The IDs of the subgraphs the deployment belongs to, as far as selected.
+    pub fn subgraph_ids(&self) -> Vec<String> {
+        self.versions
+            .iter()
+            .flatten()
+            .filter_map(|version| version.subgraph.as_ref()?.id.clone())
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DeploymentVersion {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subgraph: Option<Subgraph>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubgraphManifest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema: Option<ManifestSchema>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ManifestSchema {
+    pub schema: String,
+}
+
+/// A deployment's daily query counts, as recorded by the QoS oracle subgraph.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QosDeployment {
+    pub id: String,
+    pub query_daily_data_points: Vec<QueryDailyDataPoint>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryDailyDataPoint {
+    #[serde(rename = "query_count")]
+    pub query_count: String,
+    pub day_start: String,
+}
+
+#[derive(Deserialize)]
+struct SubgraphData {
+    subgraph: Option<Subgraph>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SubgraphDeploymentData {
+    subgraph_deployment: Option<SubgraphDeployment>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SubgraphDeploymentsData {
+    subgraph_deployments: Vec<SubgraphDeployment>,
+}
+
+#[derive(Deserialize)]
+struct SubgraphsData {
+    subgraphs: Vec<Subgraph>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QosDeploymentsData {
+    subgraph_deployments: Vec<QosDeployment>,
+}
+
+enum InternalSubgraph {
+    Network,
+    QosOracle,
+}
+
+/// Queries a subgraph that the server itself relies on (the network subgraph or the
+/// QoS oracle) through the gateway, deserializing the `data` into typed responses.
+pub struct NetworkSubgraphClient<'a> {
+    backend: &'a dyn GatewayBackend,
+    api_key: &'a str,
+    gateway_url: &'a str,
+    /// The gateway's ID in the server's registry, for metrics.
+    gateway: String,
+    subgraph: InternalSubgraph,
+}
+
+impl<'a> NetworkSubgraphClient<'a> {
+    /// A client for the network subgraph.
+    pub fn new(server: &'a SubgraphServer, api_key: &'a str, gateway_url: &'a str) -> Self {
+        Self {
+            backend: server.backend.as_ref(),
+            api_key,
+            gateway_url,
+            gateway: server.gateway_label(gateway_url),
+            subgraph: InternalSubgraph::Network,
+        }
+    }
+
+    /// A client for the QoS oracle subgraph, which holds query volume statistics.
+    pub fn qos_oracle(server: &'a SubgraphServer, api_key: &'a str, gateway_url: &'a str) -> Self {
+        Self {
+            backend: server.backend.as_ref(),
+            api_key,
+            gateway_url,
+            gateway: server.gateway_label(gateway_url),
+            subgraph: InternalSubgraph::QosOracle,
+        }
+    }
+
+    /// Runs `query` and deserializes its `data` into `T`. The first GraphQL error is
+    /// returned as `GraphQlError`, even when the gateway answers with an error status.
+    pub async fn query<T: DeserializeOwned>(
+        &self,
+        query: &str,
+        variables: Value,
+    ) -> Result<T, SubgraphError> {
+        let endpoint_type = match self.subgraph {
+            InternalSubgraph::Network => "network_subgraph_query",
+            InternalSubgraph::QosOracle => "qos_oracle_query",
+        };
+        let request = GraphQLRequest {
+            query: query.to_string(),
+            variables: Some(variables),
+        };
+
+        METRICS
+            .observe_gateway_request(endpoint_type, &self.gateway, self.api_key, || async {
+                let response = match self.subgraph {
+                    InternalSubgraph::Network => {
+                        self.backend
+                            .query_network_subgraph(self.api_key, self.gateway_url, &request)
+                            .await?
+                    }
+                    InternalSubgraph::QosOracle => {
+                        self.backend
+                            .query_qos_oracle(self.api_key, self.gateway_url, &request)
+                            .await?
+                    }
+                };
+                Self::decode(response)
+            })
+            .await
+    }
+
+    fn decode<T: DeserializeOwned>(response: GatewayResponse) -> Result<T, SubgraphError> {
+        let parsed = serde_json::from_slice::<GraphQLResponse<T>>(&response.body);
+        if let Ok(GraphQLResponse {
+            errors: Some(errors),
+            ..
+        }) = &parsed
+        {
+            if let Some(error) = errors.first() {
+                return Err(SubgraphError::GraphQlError(error.message.clone()));
+            }
+        }
+        if !response.status.is_success() {
+            return Err(SubgraphError::HttpStatus(response.status.as_u16()));
+        }
+
+        parsed?.data.ok_or_else(|| {
+            SubgraphError::GraphQlError("No data returned from the GraphQL API".to_string())
+        })
+    }
+
+    /// Checks that the gateway answers a minimal query.
+    pub async fn probe(&self) -> Result<(), SubgraphError> {
+        self.query::<Value>("{ _meta { block { number } } }", json!({}))
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn deployment_schema(&self, deployment_id: &str) -> Result<String, SubgraphError> {
+        let query = r#"
+            query SubgraphDeploymentSchema($id: String!) {
+                subgraphDeployment(id: $id) {
+                    manifest {
+                        schema {
+                            schema
+                        }
+                    }
+                }
+            }
+            "#;
+
+        let data: SubgraphDeploymentData =
+            self.query(query, json!({ "id": deployment_id })).await?;
+        data.subgraph_deployment
+            .and_then(|deployment| deployment.manifest)
+            .and_then(|manifest| manifest.schema)
+            .map(|schema| schema.schema)
+            .ok_or_else(|| {
+                SubgraphError::GraphQlError("Schema not found in the response".to_string())
+            })
+    }
+
+    /// Returns the schema of the subgraph's current version.
+    pub async fn subgraph_schema(&self, subgraph_id: &str) -> Result<String, SubgraphError> {
+        let query = r#"
+            query SubgraphSchema($id: String!) {
+              subgraph(id: $id) {
+                currentVersion {
+                  subgraphDeployment {
+                    manifest {
+                      schema {
+                        schema
+                      }
+                    }
+                  }
+                }
+              }
+            }
+            "#;
+
+        let data: SubgraphData = self.query(query, json!({ "id": subgraph_id })).await?;
+        data.subgraph
+            .and_then(|subgraph| subgraph.current_version)
+            .and_then(|version| version.subgraph_deployment.manifest)
+            .and_then(|manifest| manifest.schema)
+            .map(|schema| schema.schema)
+            .ok_or_else(|| {
+                SubgraphError::GraphQlError(
+                    "Schema not found for current version in the response".to_string(),
+                )
+            })
+    }
+
+    pub async fn schema_by_ipfs_hash(&self, ipfs_hash: &str) -> Result<String, SubgraphError> {
+        let query = r#"
+            query DeploymentSchemaByIpfsHash($hash: String!) {
+              subgraphDeployments(where: {ipfsHash: $hash}, first: 1) {
+                manifest {
+                  schema {
+                    schema
+                  }
+                }
+              }
+            }
+            "#;
+
+        let data: SubgraphDeploymentsData = self.query(query, json!({ "hash": ipfs_hash })).await?;
+        data.subgraph_deployments
+            .into_iter()
+            .next()
+            .and_then(|deployment| deployment.manifest)
+            .and_then(|manifest| manifest.schema)
+            .map(|schema| schema.schema)
+            .ok_or_else(|| {
+                SubgraphError::GraphQlError(
+                    "Schema not found for the given IPFS hash in the response".to_string(),
+                )
+            })
+    }
+
+    /// The deployment currently published for a subgraph, with its network and
+    /// every subgraph it belongs to.
+    pub async fn current_deployment(
+        &self,
+        subgraph_id: &str,
+    ) -> Result<Option<SubgraphDeployment>, SubgraphError> {
+        let query = r#"
+            query ResolveSubgraphDeployment($id: String!) {
+              subgraph(id: $id) {
+                currentVersion {
+                  subgraphDeployment {
+                    ipfsHash
+                    manifest {
+                      network
+                    }
+                    versions(first: 1000) {
+                      subgraph {
+                        id
+                      }
+                    }
+                  }
+                }
+              }
+            }
+            "#;
+
+        let data: SubgraphData = self.query(query, json!({ "id": subgraph_id })).await?;
+        Ok(data
+            .subgraph
+            .and_then(|subgraph| subgraph.current_version)
+            .map(|version| version.subgraph_deployment))
+    }
+
+    /// Looks up a deployment, its network and the subgraphs it belongs to by IPFS hash.
+    pub async fn deployment_by_ipfs_hash(
+        &self,
+        ipfs_hash: &str,
+    ) -> Result<Option<SubgraphDeployment>, SubgraphError> {
+        let query = r#"
+            query ResolveDeployment($hash: String!) {
+              subgraphDeployments(where: {ipfsHash: $hash}, first: 1) {
+                ipfsHash
+                manifest {
+                  network
+                }
+                versions(first: 1000) {
+                  subgraph {
+                    id
+                  }
+                }
+              }
+            }
+            "#;
+
+        let data: SubgraphDeploymentsData = self.query(query, json!({ "hash": ipfs_hash })).await?;
+        Ok(data.subgraph_deployments.into_iter().next())
+    }
+
+    /// The three deployments on `network` indexing `contract_address` with the most query fees.
+    pub async fn top_deployments_for_contract(
+        &self,
+        contract_address: &str,
+        network: &str,
+    ) -> Result<Vec<SubgraphDeployment>, SubgraphError> {
+        let query = r#"
+            query TopSubgraphDeploymentsForContract($network: String!, $contractAddress: String!) {
+              subgraphDeployments(
+                where: {manifest_: {network: $network, manifest_contains: $contractAddress}}
+                orderBy: queryFeesAmount
+                orderDirection: desc
+                first: 3
+              ) {
+                ipfsHash
+                manifest {
+                  network
+                }
+                queryFeesAmount
+              }
+            }
+            "#;
+
+        let variables = json!({
+            "network": network,
+            "contractAddress": contract_address
+        });
+        let data: SubgraphDeploymentsData = self.query(query, variables).await?;
+        Ok(data.subgraph_deployments)
+    }
+
+    /// Subgraphs whose display name contains `keyword`, by descending signal.
+    pub async fn search_subgraphs(&self, keyword: &str) -> Result<Vec<Subgraph>, SubgraphError> {
+        let query = r#"
+            query SearchSubgraphsByKeyword($keyword: String!) {
+              subgraphs(
+                where: {metadata_: {displayName_contains_nocase: $keyword}}
+                orderBy: currentSignalledTokens
+                orderDirection: desc
+                first: 1000
+              ) {
+                id
+                metadata {
+                  displayName
+                }
+                currentVersion {
+                  subgraphDeployment {
+                    ipfsHash
+                  }
+                }
+              }
+            }
+            "#;
+
+        let data: SubgraphsData = self.query(query, json!({ "keyword": keyword })).await?;
+        Ok(data.subgraphs)
+    }
+
+    /// The given subgraphs with the network and schema of their current deployment.
+    pub async fn subgraphs_with_schemas(
+        &self,
+        subgraph_ids: &[String],
+    ) -> Result<Vec<Subgraph>, SubgraphError> {
+        let query = r#"
+            query SubgraphsWithSchemas($ids: [String!]!) {
+              subgraphs(where: {id_in: $ids}, first: 1000) {
+                id
+                metadata {
+                  displayName
+                }
+                currentVersion {
+                  subgraphDeployment {
+                    ipfsHash
+                    manifest {
+                      network
+                      schema {
+                        schema
+                      }
+                    }
+                    versions(first: 1000) {
+                      subgraph {
+                        id
+                      }
+                    }
+                  }
+                }
+              }
+            }
+            "#;
+
+        let data: SubgraphsData = self.query(query, json!({ "ids": subgraph_ids })).await?;
+        Ok(data.subgraphs)
+    }
+
+    /// The `first` subgraphs by descending signal whose display name contains `keyword`,
+    /// with the network and schema of their current deployment.
+    pub async fn search_subgraphs_with_schemas(
+        &self,
+        keyword: &str,
+        first: usize,
+    ) -> Result<Vec<Subgraph>, SubgraphError> {
+        let query = r#"
+            query SearchSubgraphsWithSchemas($keyword: String!, $first: Int!) {
+              subgraphs(
+                where: {metadata_: {displayName_contains_nocase: $keyword}, currentVersion_not: null}
+                orderBy: currentSignalledTokens
+                orderDirection: desc
+                first: $first
+              ) {
+                id
+                metadata {
+                  displayName
+                }
+                currentVersion {
+                  subgraphDeployment {
+                    ipfsHash
+                    manifest {
+                      network
+                      schema {
+                        schema
+                      }
+                    }
+                    versions(first: 1000) {
+                      subgraph {
+                        id
+                      }
+                    }
+                  }
+                }
+              }
+            }
+            "#;
+
+        let data: SubgraphsData = self
+            .query(query, json!({ "keyword": keyword, "first": first }))
+            .await?;
+        Ok(data.subgraphs)
+    }
+
+    /// Daily query counts since `since` (a UNIX timestamp) for the given deployments.
+    /// Only meaningful on the QoS oracle client.
+    pub async fn daily_query_counts(
+        &self,
+        ipfs_hashes: &[String],
+        since: u64,
+    ) -> Result<Vec<QosDeployment>, SubgraphError> {
+        let query = r#"
+            query GetSubgraphDeployment30DayQueryCounts(
+              $deploymentIDs: [ID!]!,
+              $thirtyDaysAgoTimestamp: BigInt!
+            ) {
+              subgraphDeployments(where: { id_in: $deploymentIDs }) {
+                id
+                queryDailyDataPoints(
+                  where: { dayStart_gte: $thirtyDaysAgoTimestamp }
+                  orderBy: dayStart
+                  orderDirection: asc
+                  first: 31
+                ) {
+                  query_count
+                  dayStart
+                }
+              }
+            }
+            "#;
+
+        let variables = json!({
+            "deploymentIDs": ipfs_hashes,
+            "thirtyDaysAgoTimestamp": since.to_string()
+        });
+        let data: QosDeploymentsData = self.query(query, variables).await?;
+        Ok(data.subgraph_deployments)
+    }
+}
@@ -0,0 +1,417 @@
+// SPDX-License-Identifier: Apache-2.0
+use crate::error::SubgraphError;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, Instant, SystemTime},
+};
+
+// How often the policy file's modification time is checked for hot reloading
+const POLICY_RELOAD_INTERVAL: Duration = Duration::from_secs(5);
+
+/// One set of identifiers to match a deployment against. Every list is optional;
+/// IPFS hashes and deployment IDs are interchangeable since both name the same manifest.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PolicyRules {
+    #[serde(default)]
+    pub ipfs_hashes: Vec<String>,
+    #[serde(default)]
+    pub deployment_ids: Vec<String>,
+    #[serde(default)]
+    pub subgraph_ids: Vec<String>,
+    #[serde(default)]
+    pub networks: Vec<String>,
+}
+
+impl PolicyRules {
+    fn is_empty(&self) -> bool {
+        self.ipfs_hashes.is_empty()
+            && self.deployment_ids.is_empty()
+            && self.subgraph_ids.is_empty()
+            && self.networks.is_empty()
+    }
+
+    fn has_network_rules(&self) -> bool {
+        !self.networks.is_empty()
+    }
+
+    fn has_subgraph_rules(&self) -> bool {
+        !self.subgraph_ids.is_empty()
+    }
+
+    /// Returns a description of the first rule matching `target`. When none
+    /// matches but a rule could not be checked because the target's IPFS hash,
+    /// subgraphs or network are unknown, returns what was missing instead.
+    fn matches(&self, target: &DeploymentTarget) -> Result<Option<String>, &'static str> {
+        let mut unresolved = None;
+        if let Some(ipfs_hash) = &target.ipfs_hash {
+            if self.ipfs_hashes.iter().any(|h| h == ipfs_hash) {
+                return Ok(Some(format!("ipfs_hash {}", ipfs_hash)));
+            }
+            if self
+                .deployment_ids
+                .iter()
+                .filter_map(|id| deployment_id_to_ipfs_hash(id))
+                .any(|h| &h == ipfs_hash)
+            {
+                return Ok(Some(format!("deployment_id for {}", ipfs_hash)));
+            }
+        } else if !self.ipfs_hashes.is_empty() || !self.deployment_ids.is_empty() {
+            unresolved = Some("IPFS hash");
+        }
+        if let Some(subgraph_ids) = &target.subgraph_ids {
+            if let Some(subgraph_id) = subgraph_ids
+                .iter()
+                .find(|id| self.subgraph_ids.contains(id))
+            {
+                return Ok(Some(format!("subgraph_id {}", subgraph_id)));
+            }
+        } else if self.has_subgraph_rules() {
+            unresolved = Some("subgraph");
+        }
+        if let Some(network) = &target.network {
+            if self
+                .networks
+                .iter()
+                .any(|n| n.eq_ignore_ascii_case(network))
+            {
+                return Ok(Some(format!("network {}", network)));
+            }
+        } else if self.has_network_rules() {
+            unresolved = Some("network");
+        }
+        unresolved.map_or(Ok(None), Err)
+    }
+}
+
+/// Allow and deny lists for deployments. Deny rules always win; when any allow rule
+/// is configured, a deployment must match at least one of them.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DeploymentPolicy {
+    #[serde(default)]
+    pub allow: PolicyRules,
+    #[serde(default)]
+    pub deny: PolicyRules,
+    /// Set when the configured policy could not be loaded; every deployment is then denied.
+    #[serde(skip)]
+    pub load_error: Option<String>,
+}
+
+/// The deployment a tool call is about to touch, as far as it is known.
+#[derive(Clone, Debug, Default)]
+pub struct DeploymentTarget {
+    pub ipfs_hash: Option<String>,
+    /// The subgraph ID the call addressed the deployment by, if any.
+    pub subgraph_id: Option<String>,
+    /// Every subgraph the deployment belongs to, including `subgraph_id`. `None`
+    /// when they could not be resolved.
+    pub subgraph_ids: Option<Vec<String>>,
+    pub network: Option<String>,
+}
+
+impl DeploymentPolicy {
+    /// Whether checking this policy needs the deployment's network.
+    pub fn needs_network(&self) -> bool {
+        self.allow.has_network_rules() || self.deny.has_network_rules()
+    }
+
+    /// Whether checking this policy needs the subgraphs owning the deployment.
+    pub fn needs_subgraphs(&self) -> bool {
+        self.allow.has_subgraph_rules() || self.deny.has_subgraph_rules()
+    }
+
+    pub fn check(&self, target: &DeploymentTarget) -> Result<(), SubgraphError> {
+        if let Some(load_error) = &self.load_error {
+            return Err(SubgraphError::PolicyViolation(format!(
+                "Deployment policy could not be loaded, all deployments are denied: {}",
+                load_error
+            )));
+        }
+
+        let describe = || {
+            target
+                .ipfs_hash
+                .clone()
+                .or_else(|| target.subgraph_id.clone())
+                .unwrap_or_else(|| "unknown deployment".to_string())
+        };
+
+        // A rule that cannot be checked fails closed, for deny and allow rules alike
+        let unresolved = |field: &str, rules: &str| {
+            SubgraphError::PolicyViolation(format!(
+                "Deployment {} is denied because its {} could not be resolved to check the {} rules",
+                describe(),
+                field,
+                rules
+            ))
+        };
+
+        match self.deny.matches(target) {
+            Ok(Some(rule)) => {
+                return Err(SubgraphError::PolicyViolation(format!(
+                    "Deployment {} is denied by policy rule: {}",
+                    describe(),
+                    rule
+                )))
+            }
+            Ok(None) => {}
+            Err(field) => return Err(unresolved(field, "deny")),
+        }
+
+        if !self.allow.is_empty() {
+            match self.allow.matches(target) {
+                Ok(Some(_)) => {}
+                Ok(None) => {
+                    return Err(SubgraphError::PolicyViolation(format!(
+                        "Deployment {} is not on the allowlist of vetted deployments",
+                        describe()
+                    )))
+                }
+                Err(field) => return Err(unresolved(field, "allow")),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A policy loaded from a JSON file and reloaded when the file changes.
+pub struct PolicyStore {
+    path: Option<PathBuf>,
+    state: RwLock<PolicyState>,
+}
+
+struct PolicyState {
+    policy: Arc<DeploymentPolicy>,
+    modified: Option<SystemTime>,
+    last_checked: Instant,
+}
+
+impl PolicyStore {
+    pub fn from_policy(policy: DeploymentPolicy) -> Self {
+        Self {
+            path: None,
+            state: RwLock::new(PolicyState {
+                policy: Arc::new(policy),
+                modified: None,
+                last_checked: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn from_file(path: impl Into<PathBuf>) -> Result<Self, SubgraphError> {
+        let path = path.into();
+        let (policy, modified) = load_policy_file(&path)?;
+        Ok(Self {
+            path: Some(path),
+            state: RwLock::new(PolicyState {
+                policy: Arc::new(policy),
+                modified,
+                last_checked: Instant::now(),
+            }),
+        })
+    }
+
+    /// Returns the current policy, reloading the file first if it changed. A file
+    /// that fails to parse keeps the previously loaded policy in place.
+    pub fn current(&self) -> Arc<DeploymentPolicy> {
+        {
+            let state = self.state.read().unwrap_or_else(|e| e.into_inner());
+            if self.path.is_none() || state.last_checked.elapsed() < POLICY_RELOAD_INTERVAL {
+                return state.policy.clone();
+            }
+        }
+
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        state.last_checked = Instant::now();
+        if let Some(path) = &self.path {
+            let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+            if modified != state.modified {
+                match load_policy_file(path) {
+                    Ok((policy, modified)) => {
+                        tracing::info!(target: "mcp_policy", path = %path.display(), "Reloaded deployment policy");
+                        state.policy = Arc::new(policy);
+                        state.modified = modified;
+                    }
+                    Err(e) => {
+                        tracing::error!(target: "mcp_policy", path = %path.display(), error = %e, "Failed to reload deployment policy, keeping previous policy");
+                        state.modified = modified;
+                    }
+                }
+            }
+        }
+        state.policy.clone()
+    }
+}
+
+fn load_policy_file(path: &Path) -> Result<(DeploymentPolicy, Option<SystemTime>), SubgraphError> {
+    let contents = fs::read_to_string(path).map_err(|e| {
+        SubgraphError::InternalProcessingError(format!(
+            "Failed to read policy file {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    let policy = serde_json::from_str::<DeploymentPolicy>(&contents)?;
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+    Ok((policy, modified))
+}
+
+/// Policy configured through `DEPLOYMENT_POLICY_FILE`, shared by every session.
+pub static POLICY_STORE: Lazy<Option<Arc<PolicyStore>>> = Lazy::new(|| {
+    let path = env::var("DEPLOYMENT_POLICY_FILE").ok()?;
+    match PolicyStore::from_file(&path) {
+        Ok(store) => {
+            tracing::info!(target: "mcp_policy", path = %path, "Loaded deployment policy");
+            Some(Arc::new(store))
+        }
+        Err(e) => {
+            // Failing open would silently lift the restrictions, so deny everything instead.
+            tracing::error!(target: "mcp_policy", path = %path, error = %e, "Failed to load deployment policy, denying all deployments");
+            Some(Arc::new(PolicyStore::from_policy(DeploymentPolicy {
+                load_error: Some(e.to_string()),
+                ..Default::default()
+            })))
+        }
+    }
+});
+
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Converts a `0x`-prefixed deployment ID into its `Qm...` IPFS hash.
+pub fn deployment_id_to_ipfs_hash(deployment_id: &str) -> Option<String> {
+    let hex = deployment_id.strip_prefix("0x")?;
+    if hex.len() != 64 {
+        return None;
+    }
+    let mut bytes = vec![0x12, 0x20];
+    for i in (0..hex.len()).step_by(2) {
+        bytes.push(u8::from_str_radix(&hex[i..i + 2], 16).ok()?);
+    }
+    Some(base58_encode(&bytes))
+}
+
+/// Converts a `Qm...` IPFS hash into its `0x`-prefixed deployment ID.
+pub fn ipfs_hash_to_deployment_id(ipfs_hash: &str) -> Option<String> {
+    let bytes = base58_decode(ipfs_hash)?;
+    if bytes.len() != 34 || bytes[0] != 0x12 || bytes[1] != 0x20 {
+        return None;
+    }
+    Some(format!(
+        "0x{}",
+        bytes[2..]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>()
+    ))
+}
+
+fn base58_encode(bytes: &[u8]) -> String {
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in bytes {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let leading_zeros = bytes.iter().take_while(|&&b| b == 0).count();
+    std::iter::repeat_n(BASE58_ALPHABET[0], leading_zeros)
+        .chain(digits.iter().rev().map(|&d| BASE58_ALPHABET[d as usize]))
+        .map(char::from)
+        .collect()
+}
+
+fn base58_decode(input: &str) -> Option<Vec<u8>> {
+    let mut bytes: Vec<u8> = Vec::new();
+    for c in input.bytes() {
+        let mut carry = BASE58_ALPHABET.iter().position(|&a| a == c)? as u32;
+        for byte in bytes.iter_mut() {
+            carry += (*byte as u32) * 58;
+            *byte = (carry & 0xff) as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push((carry & 0xff) as u8);
+            carry >>= 8;
+        }
+    }
+    let leading_zeros = input
+        .bytes()
+        .take_while(|&c| c == BASE58_ALPHABET[0])
+        .count();
+    let mut result = vec![0u8; leading_zeros];
+    result.extend(bytes.iter().rev());
+    Some(result)
+}
+
+/// How a tool call identifies the deployment it targets.
+#[derive(Clone, Copy, Debug)]
+pub enum DeploymentRef<'a> {
+    DeploymentId(&'a str),
+    IpfsHash(&'a str),
+    SubgraphId(&'a str),
+}
+
+impl<'a> DeploymentRef<'a> {
+    /// Picks the kind of identifier from its prefix: `0x` for deployment IDs, `Qm`
+    /// for IPFS hashes and anything else for subgraph IDs.
+    pub fn parse(id: &'a str) -> Self {
+        if id.starts_with("0x") {
+            DeploymentRef::DeploymentId(id)
+        } else if id.starts_with("Qm") {
+            DeploymentRef::IpfsHash(id)
+        } else {
+            DeploymentRef::SubgraphId(id)
+        }
+    }
+
+    /// The gateway path the identifier is queried under.
+    pub fn endpoint_type(&self) -> &'static str {
+        match self {
+            DeploymentRef::DeploymentId(_) | DeploymentRef::IpfsHash(_) => "deployments/id",
+            DeploymentRef::SubgraphId(_) => "subgraphs/id",
+        }
+    }
+
+    /// The identifier as given by the caller.
+    pub fn as_str(&self) -> &str {
+        match self {
+            DeploymentRef::DeploymentId(id)
+            | DeploymentRef::IpfsHash(id)
+            | DeploymentRef::SubgraphId(id) => id,
+        }
+    }
+
+    /// The IPFS hash, when it can be derived without asking the network subgraph.
+    pub fn local_ipfs_hash(&self) -> Option<String> {
+        match self {
+            DeploymentRef::IpfsHash(hash) => Some(hash.to_string()),
+            DeploymentRef::DeploymentId(id) if id.starts_with("Qm") => Some(id.to_string()),
+            DeploymentRef::DeploymentId(id) => deployment_id_to_ipfs_hash(id),
+            DeploymentRef::SubgraphId(_) => None,
+        }
+    }
+
+    /// What is known about the deployment without asking the network subgraph.
+    pub fn local_target(&self) -> DeploymentTarget {
+        let subgraph_id = match self {
+            DeploymentRef::SubgraphId(id) => Some(id.to_string()),
+            _ => None,
+        };
+        DeploymentTarget {
+            ipfs_hash: self.local_ipfs_hash(),
+            subgraph_ids: subgraph_id.clone().map(|id| vec![id]),
+            subgraph_id,
+            network: None,
+        }
+    }
+}
@@ -0,0 +1,340 @@
+// SPDX-License-Identifier: Apache-2.0
+use crate::error::SubgraphError;
+use crate::schema_summary::{EntitySummary, SchemaSummary};
+use graphql_parser::query::{
+    parse_query, Definition, Field, OperationDefinition, Selection, SelectionSet, Value,
+};
+use serde::Serialize;
+use std::{collections::HashMap, env};
+
+// Number of entities graph-node returns for a collection field without `first`
+const DEFAULT_FIRST: u64 = 100;
+
+/// Thresholds a query must stay within before it is sent to the gateway.
+/// `None` disables the corresponding check.
+#[derive(Clone, Copy, Debug)]
+pub struct QueryLimits {
+    pub max_depth: Option<usize>,
+    pub max_fields: Option<usize>,
+    pub max_estimated_rows: Option<u64>,
+}
+
+impl Default for QueryLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: Some(8),
+            max_fields: Some(500),
+            max_estimated_rows: Some(100_000),
+        }
+    }
+}
+
+impl QueryLimits {
+    /// Reads the thresholds from the environment. Setting a variable to `0`
+    /// disables that check.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            max_depth: env_limit("QUERY_MAX_DEPTH", defaults.max_depth),
+            max_fields: env_limit("QUERY_MAX_FIELDS", defaults.max_fields),
+            max_estimated_rows: env_limit("QUERY_MAX_ESTIMATED_ROWS", defaults.max_estimated_rows),
+        }
+    }
+
+    pub fn check(&self, analysis: &QueryAnalysis) -> Result<(), SubgraphError> {
+        if let Some(max_depth) = self.max_depth {
+            if analysis.depth > max_depth {
+                return Err(SubgraphError::QueryRejected(format!(
+                    "Query depth {} exceeds the limit of {}. The deepest selection is `{}`; query the nested entities separately or remove levels of nesting.",
+                    analysis.depth, max_depth, analysis.deepest_path
+                )));
+            }
+        }
+
+        if let Some(max_fields) = self.max_fields {
+            if analysis.field_count > max_fields {
+                return Err(SubgraphError::QueryRejected(format!(
+                    "Query selects {} fields, exceeding the limit of {}. Select only the fields you need instead of listing every field of each entity.",
+                    analysis.field_count, max_fields
+                )));
+            }
+        }
+
+        if let Some(max_rows) = self.max_estimated_rows {
+            if analysis.estimated_rows > max_rows {
+                let narrow = match &analysis.heaviest_selection {
+                    Some(selection) => format!(
+                        " Most rows come from `{}` (up to {} rows); lower `first` on one of its levels or add a more selective `where` filter.",
+                        selection.path, selection.rows
+                    ),
+                    None => String::new(),
+                };
+                return Err(SubgraphError::QueryRejected(format!(
+                    "Query may return up to {} rows in the worst case, exceeding the limit of {}.{}",
+                    analysis.estimated_rows, max_rows, narrow
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn env_limit<T: std::str::FromStr + PartialEq + Default>(
+    name: &str,
+    default: Option<T>,
+) -> Option<T> {
+    match env::var(name).ok().and_then(|s| s.parse::<T>().ok()) {
+        Some(value) if value == T::default() => None,
+        Some(value) => Some(value),
+        None => default,
+    }
+}
+
+/// The collection selection contributing the most rows, rendered as a path of
+/// field names with the page size used at each level.
+#[derive(Clone, Debug, Serialize)]
+pub struct HeavySelection {
+    pub path: String,
+    pub rows: u64,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct QueryAnalysis {
+    pub depth: usize,
+    pub field_count: usize,
+    /// Worst-case number of entities returned, multiplying the `first` arguments
+    /// of nested collections.
+    pub estimated_rows: u64,
+    pub deepest_path: String,
+    pub heaviest_selection: Option<HeavySelection>,
+}
+
+/// Parses `query` and measures its depth, field count and worst-case row count.
+/// Without a schema, every field with a selection set is taken for a collection
+/// unless it is looked up by `id` or is `_meta` or introspection, so the row
+/// count is an upper bound. Collections without `first` count as graph-node's
+/// default page of 100.
+pub fn analyze_query(
+    query: &str,
+    variables: Option<&serde_json::Value>,
+) -> Result<QueryAnalysis, SubgraphError> {
+    analyze_query_with_schema(query, variables, None)
+}
+
+/// Like `analyze_query`, but tells list fields from single entities by the
+/// deployment's schema where the selection can be followed through it.
+pub fn analyze_query_with_schema(
+    query: &str,
+    variables: Option<&serde_json::Value>,
+    schema: Option<&SchemaSummary>,
+) -> Result<QueryAnalysis, SubgraphError> {
+    let document = parse_query::<String>(query)
+        .map_err(|e| SubgraphError::QueryRejected(format!("Query is not valid GraphQL: {}", e)))?;
+
+    let mut fragments = HashMap::new();
+    for definition in &document.definitions {
+        if let Definition::Fragment(fragment) = definition {
+            fragments.insert(fragment.name.as_str(), &fragment.selection_set);
+        }
+    }
+
+    let mut walker = Walker {
+        schema,
+        fragments,
+        variables,
+        variable_defaults: HashMap::new(),
+        fragment_stack: Vec::new(),
+        path: Vec::new(),
+        analysis: QueryAnalysis::default(),
+    };
+
+    for definition in &document.definitions {
+        let Definition::Operation(operation) = definition else {
+            continue;
+        };
+        let (variable_definitions, selection_set) = match operation {
+            OperationDefinition::SelectionSet(set) => (&[][..], set),
+            OperationDefinition::Query(q) => (&q.variable_definitions[..], &q.selection_set),
+            OperationDefinition::Mutation(m) => (&m.variable_definitions[..], &m.selection_set),
+            OperationDefinition::Subscription(s) => (&s.variable_definitions[..], &s.selection_set),
+        };
+        walker.variable_defaults = variable_definitions
+            .iter()
+            .filter_map(|def| {
+                let value = walker.int_value(def.default_value.as_ref()?)?;
+                Some((def.name.clone(), value))
+            })
+            .collect();
+        walker.walk(selection_set, 0, 1, Scope::Root);
+    }
+
+    Ok(walker.analysis)
+}
+
+/// What the fields of a selection set belong to.
+#[derive(Clone, Copy)]
+enum Scope<'s> {
+    Root,
+    Entity(&'s EntitySummary),
+    /// Not followed through the schema; nested objects count as collections.
+    Unknown,
+    /// `_meta` and introspection, which hold no entity collections.
+    Metadata,
+}
+
+struct Walker<'a> {
+    schema: Option<&'a SchemaSummary>,
+    fragments: HashMap<&'a str, &'a SelectionSet<'a, String>>,
+    variables: Option<&'a serde_json::Value>,
+    variable_defaults: HashMap<String, u64>,
+    fragment_stack: Vec<&'a str>,
+    path: Vec<String>,
+    analysis: QueryAnalysis,
+}
+
+impl<'a> Walker<'a> {
+    fn walk(
+        &mut self,
+        selection_set: &'a SelectionSet<'a, String>,
+        depth: usize,
+        rows: u64,
+        scope: Scope<'a>,
+    ) {
+        for selection in &selection_set.items {
+            match selection {
+                Selection::Field(field) => self.visit_field(field, depth + 1, rows, scope),
+                Selection::InlineFragment(fragment) => {
+                    self.walk(&fragment.selection_set, depth, rows, scope)
+                }
+                Selection::FragmentSpread(spread) => {
+                    let name = spread.fragment_name.as_str();
+                    // Cyclic fragments are invalid GraphQL; the gateway reports them.
+                    if self.fragment_stack.contains(&name) {
+                        continue;
+                    }
+                    if let Some(fragment) = self.fragments.get(name).copied() {
+                        self.fragment_stack.push(name);
+                        self.walk(fragment, depth, rows, scope);
+                        self.fragment_stack.pop();
+                    }
+                }
+            }
+        }
+    }
+
+    fn visit_field(
+        &mut self,
+        field: &'a Field<'a, String>,
+        depth: usize,
+        rows: u64,
+        scope: Scope<'a>,
+    ) {
+        self.analysis.field_count += 1;
+
+        let (page_size, inner) = self.page_size(field, scope);
+        let rows = rows.saturating_mul(page_size.unwrap_or(1));
+        self.path.push(match page_size {
+            Some(first) => format!("{}(first: {})", field.name, first),
+            None => field.name.clone(),
+        });
+
+        if depth > self.analysis.depth {
+            self.analysis.depth = depth;
+            self.analysis.deepest_path = self.path.join(".");
+        }
+
+        if page_size.is_some() {
+            self.analysis.estimated_rows = self.analysis.estimated_rows.saturating_add(rows);
+            let heavier = self
+                .analysis
+                .heaviest_selection
+                .as_ref()
+                .is_none_or(|heaviest| rows > heaviest.rows);
+            if heavier {
+                self.analysis.heaviest_selection = Some(HeavySelection {
+                    path: self.path.join("."),
+                    rows,
+                });
+            }
+        }
+
+        self.walk(&field.selection_set, depth, rows, inner);
+        self.path.pop();
+    }
+
+    /// The number of entities a collection field returns, or `None` for single
+    /// entities and scalars, along with the scope of its selection set.
+    fn page_size(&self, field: &Field<'a, String>, scope: Scope<'a>) -> (Option<u64>, Scope<'a>) {
+        let name = field.name.as_str();
+        if name == "_meta" || name.starts_with("__") || matches!(scope, Scope::Metadata) {
+            return (None, Scope::Metadata);
+        }
+        let first = field
+            .arguments
+            .iter()
+            .find(|(argument, _)| argument == "first")
+            .map(|(_, value)| self.int_value(value).unwrap_or(DEFAULT_FIRST));
+        let entity = |name: &str| {
+            self.schema
+                .and_then(|schema| schema.entities.iter().find(|e| e.name == name))
+        };
+
+        // Follow the selection through the schema where it can be
+        let known = match scope {
+            Scope::Root => self.schema.and_then(|schema| {
+                schema.entities.iter().find_map(|e| {
+                    if e.query_fields.collection == name {
+                        Some((true, e))
+                    } else if e.query_fields.single.as_deref() == Some(name) {
+                        Some((false, e))
+                    } else {
+                        None
+                    }
+                })
+            }),
+            Scope::Entity(parent) => {
+                if parent.fields.iter().any(|f| f.name == name) {
+                    return (None, Scope::Unknown);
+                }
+                parent
+                    .relationships
+                    .iter()
+                    .find(|r| r.field == name)
+                    .and_then(|r| Some((r.list, entity(&r.target)?)))
+            }
+            Scope::Unknown | Scope::Metadata => None,
+        };
+        if let Some((list, target)) = known {
+            let page_size = list.then(|| first.unwrap_or(DEFAULT_FIRST));
+            return (page_size, Scope::Entity(target));
+        }
+
+        let collection_arguments = field.arguments.iter().any(|(argument, _)| {
+            matches!(
+                argument.as_str(),
+                "skip" | "where" | "orderBy" | "orderDirection"
+            )
+        });
+        let by_id = field.arguments.iter().any(|(argument, _)| argument == "id");
+        let nested_object = !field.selection_set.items.is_empty() && !by_id;
+        let page_size = match first {
+            Some(first) => Some(first),
+            None if collection_arguments || nested_object => Some(DEFAULT_FIRST),
+            None => None,
+        };
+        (page_size, Scope::Unknown)
+    }
+
+    fn int_value(&self, value: &Value<'a, String>) -> Option<u64> {
+        match value {
+            Value::Int(number) => number.as_i64().map(|n| n.max(0) as u64),
+            Value::Variable(name) => self
+                .variables
+                .and_then(|vars| vars.get(name))
+                .and_then(|v| v.as_u64())
+                .or_else(|| self.variable_defaults.get(name).copied()),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,308 @@
+// SPDX-License-Identifier: Apache-2.0
+use std::{
+    collections::HashMap,
+    env,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Tools are throttled in two independent budgets: the `execute_query_*` tools,
+/// `execute_queries_batch`, `query_across_chains`, `export_query` and
+/// `get_schema_by_introspection`, which spend query fees on the caller's key, and
+/// everything else.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ToolClass {
+    Query,
+    Metadata,
+}
+
+impl ToolClass {
+    pub fn for_tool(tool_name: &str) -> Self {
+        if tool_name.starts_with("execute_query")
+            || matches!(
+                tool_name,
+                "execute_queries_batch"
+                    | "query_across_chains"
+                    | "export_query"
+                    | "get_schema_by_introspection"
+            )
+        {
+            ToolClass::Query
+        } else {
+            ToolClass::Metadata
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ToolClass::Query => "query",
+            ToolClass::Metadata => "metadata",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BudgetConfig {
+    /// Sustained number of calls allowed per minute.
+    pub per_minute: f64,
+    /// Number of calls that may be made back to back before throttling kicks in.
+    pub burst: f64,
+    /// Maximum number of calls that may be executing at the same time.
+    pub max_in_flight: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimitConfig {
+    pub query: BudgetConfig,
+    pub metadata: BudgetConfig,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            query: BudgetConfig {
+                per_minute: 60.0,
+                burst: 10.0,
+                max_in_flight: 4,
+            },
+            metadata: BudgetConfig {
+                per_minute: 120.0,
+                burst: 20.0,
+                max_in_flight: 8,
+            },
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// Reads the limits from the environment. Rate limiting is off unless it is
+    /// turned on with `RATE_LIMIT_ENABLED=true` or by setting any of the limits,
+    /// so upgrading never starts throttling an existing deployment.
+    pub fn from_env() -> Option<Self> {
+        let enabled = match env::var("RATE_LIMIT_ENABLED") {
+            Ok(v) => !matches!(v.to_lowercase().as_str(), "false" | "0" | "no" | "off"),
+            Err(_) => env::vars().any(|(name, _)| name.starts_with("RATE_LIMIT_")),
+        };
+        if !enabled {
+            return None;
+        }
+
+        let defaults = Self::default();
+        Some(Self {
+            query: BudgetConfig::from_env("QUERY", defaults.query),
+            metadata: BudgetConfig::from_env("METADATA", defaults.metadata),
+        })
+    }
+
+    fn budget(&self, class: ToolClass) -> &BudgetConfig {
+        match class {
+            ToolClass::Query => &self.query,
+            ToolClass::Metadata => &self.metadata,
+        }
+    }
+}
+
+impl BudgetConfig {
+    /// Reads `RATE_LIMIT_<CLASS>_*`. Values a budget can't work with, such as a
+    /// burst below one call, fall back to the defaults rather than throttling
+    /// every call.
+    fn from_env(class: &str, defaults: BudgetConfig) -> Self {
+        Self {
+            per_minute: env_or(
+                &format!("RATE_LIMIT_{}_PER_MINUTE", class),
+                defaults.per_minute,
+                |per_minute: &f64| per_minute.is_finite() && *per_minute > 0.0,
+            ),
+            burst: env_or(
+                &format!("RATE_LIMIT_{}_BURST", class),
+                defaults.burst,
+                |burst: &f64| burst.is_finite() && *burst >= 1.0,
+            ),
+            max_in_flight: env_or(
+                &format!("RATE_LIMIT_{}_MAX_IN_FLIGHT", class),
+                defaults.max_in_flight,
+                |max_in_flight: &usize| *max_in_flight >= 1,
+            ),
+        }
+    }
+}
+
+fn env_or<T: std::str::FromStr + std::fmt::Display>(
+    name: &str,
+    default: T,
+    valid: impl Fn(&T) -> bool,
+) -> T {
+    let Ok(raw) = env::var(name) else {
+        return default;
+    };
+    match raw.trim().parse::<T>() {
+        Ok(value) if valid(&value) => value,
+        _ => {
+            tracing::warn!(
+                target: "mcp_rate_limit",
+                name,
+                value = %raw,
+                default = %default,
+                "Invalid rate limit setting, using the default"
+            );
+            default
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThrottleReason {
+    RateLimit,
+    Concurrency,
+}
+
+impl ThrottleReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ThrottleReason::RateLimit => "rate_limit",
+            ThrottleReason::Concurrency => "concurrency",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Throttled {
+    pub class: ToolClass,
+    pub reason: ThrottleReason,
+    pub retry_after: Duration,
+}
+
+impl std::fmt::Display for Throttled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.reason {
+            ThrottleReason::RateLimit => write!(
+                f,
+                "Rate limit exceeded for {} tools on this API key. Retry after {} seconds.",
+                self.class.as_str(),
+                self.retry_after.as_secs().max(1)
+            ),
+            ThrottleReason::Concurrency => write!(
+                f,
+                "Too many concurrent {} tool calls on this API key. Retry after {} seconds.",
+                self.class.as_str(),
+                self.retry_after.as_secs().max(1)
+            ),
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+    in_flight: usize,
+}
+
+type BucketMap = HashMap<(String, ToolClass), Bucket>;
+
+// Idle, fully refilled buckets are dropped once the map grows past this size.
+const MAX_TRACKED_BUCKETS: usize = 10_000;
+
+/// Token-bucket rate limiter with a per-key concurrency cap, shared by every
+/// session of the SSE server.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Arc<Mutex<BucketMap>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Takes a token and an in-flight slot for `api_key`. The slot is held until
+    /// the returned permit is dropped.
+    pub fn acquire(&self, api_key: &str, class: ToolClass) -> Result<RateLimitPermit, Throttled> {
+        self.acquire_weighted(api_key, class, 1)
+    }
+
+    /// Like `acquire`, for a call that counts as `weight` calls against the rate,
+    /// such as a batch of queries. The weight is capped at the burst size so that
+    /// every call can eventually pass.
+    pub fn acquire_weighted(
+        &self,
+        api_key: &str,
+        class: ToolClass,
+        weight: usize,
+    ) -> Result<RateLimitPermit, Throttled> {
+        let budget = self.config.budget(class);
+        let cost = (weight.max(1) as f64).min(budget.burst.max(1.0));
+        let now = Instant::now();
+        let refill_per_sec = budget.per_minute / 60.0;
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() >= MAX_TRACKED_BUCKETS {
+            let config = self.config;
+            buckets.retain(|(_, class), bucket| {
+                let budget = config.budget(*class);
+                let refilled = bucket.tokens
+                    + now.duration_since(bucket.last_refill).as_secs_f64() * budget.per_minute
+                        / 60.0;
+                bucket.in_flight > 0 || refilled < budget.burst
+            });
+        }
+
+        let key = (api_key.to_string(), class);
+        let bucket = buckets.entry(key.clone()).or_insert_with(|| Bucket {
+            tokens: budget.burst,
+            last_refill: now,
+            in_flight: 0,
+        });
+
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * refill_per_sec).min(budget.burst);
+        bucket.last_refill = now;
+
+        if bucket.in_flight >= budget.max_in_flight {
+            return Err(Throttled {
+                class,
+                reason: ThrottleReason::Concurrency,
+                retry_after: Duration::from_secs(1),
+            });
+        }
+
+        if bucket.tokens < cost {
+            let retry_after = if refill_per_sec > 0.0 {
+                Duration::try_from_secs_f64((cost - bucket.tokens) / refill_per_sec)
+                    .unwrap_or(Duration::MAX)
+            } else {
+                Duration::from_secs(60)
+            };
+            return Err(Throttled {
+                class,
+                reason: ThrottleReason::RateLimit,
+                retry_after,
+            });
+        }
+
+        bucket.tokens -= cost;
+        bucket.in_flight += 1;
+
+        Ok(RateLimitPermit {
+            buckets: self.buckets.clone(),
+            key,
+        })
+    }
+}
+
+/// Releases the in-flight slot taken by [`RateLimiter::acquire`] when dropped.
+pub struct RateLimitPermit {
+    buckets: Arc<Mutex<BucketMap>>,
+    key: (String, ToolClass),
+}
+
+impl Drop for RateLimitPermit {
+    fn drop(&mut self) {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(bucket) = buckets.get_mut(&self.key) {
+            bucket.in_flight = bucket.in_flight.saturating_sub(1);
+        }
+    }
+}
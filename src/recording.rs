@@ -0,0 +1,348 @@
+// SPDX-License-Identifier: Apache-2.0
+use crate::backend::{GatewayBackend, GatewayResponse, GatewayTarget};
+use crate::error::SubgraphError;
+use crate::types::GraphQLRequest;
+use crate::usage::fee_header_name;
+use async_trait::async_trait;
+use http::{header::CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Replaces the API key wherever it appears in a recorded exchange.
+pub const SCRUBBED_API_KEY: &str = "<api-key>";
+
+/// One gateway request and the response it got, as stored in a fixture file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedExchange {
+    pub target: GatewayTarget,
+    pub request: GraphQLRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    /// Only the content type and the query fee header are kept.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    /// The JSON body, or the raw body as a string when `text` is set.
+    pub body: serde_json::Value,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub text: bool,
+}
+
+impl RecordedResponse {
+    fn from_response(response: &GatewayResponse) -> Self {
+        let mut kept_headers = vec![CONTENT_TYPE.as_str().to_string()];
+        kept_headers.extend(fee_header_name());
+        let headers = kept_headers
+            .into_iter()
+            .filter_map(|name| {
+                let value = response.header(&name)?.to_string();
+                Some((name.to_lowercase(), value))
+            })
+            .collect();
+
+        match serde_json::from_slice(&response.body) {
+            Ok(body) => Self {
+                status: response.status.as_u16(),
+                headers,
+                body,
+                text: false,
+            },
+            Err(_) => Self {
+                status: response.status.as_u16(),
+                headers,
+                body: String::from_utf8_lossy(&response.body).into_owned().into(),
+                text: true,
+            },
+        }
+    }
+
+    fn to_response(&self) -> GatewayResponse {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                headers.insert(name, value);
+            }
+        }
+        let body = match (&self.body, self.text) {
+            (serde_json::Value::String(text), true) => text.clone().into_bytes(),
+            (body, _) => serde_json::to_vec(body).unwrap_or_default(),
+        };
+        GatewayResponse {
+            status: StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK),
+            headers,
+            body,
+        }
+    }
+}
+
+/// Passes requests to another backend and appends every exchange to an NDJSON
+/// fixture file, with the API key scrubbed. Failing to write is logged rather
+/// than failing the request.
+pub struct RecordingBackend {
+    inner: Arc<dyn GatewayBackend>,
+    path: PathBuf,
+    write_lock: Mutex<()>,
+}
+
+impl RecordingBackend {
+    pub fn new(inner: Arc<dyn GatewayBackend>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            inner,
+            path: path.into(),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    fn record(
+        &self,
+        api_key: &str,
+        target: GatewayTarget,
+        request: &GraphQLRequest,
+        response: &GatewayResponse,
+    ) {
+        let exchange = RecordedExchange {
+            target,
+            request: request.clone(),
+            response: RecordedResponse::from_response(response),
+        };
+        let result = serde_json::to_string(&exchange)
+            .map_err(SubgraphError::from)
+            .and_then(|line| {
+                let line = if api_key.is_empty() {
+                    line
+                } else {
+                    line.replace(api_key, SCRUBBED_API_KEY)
+                };
+                let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)
+                    .and_then(|mut file| writeln!(file, "{}", line))
+                    .map_err(|e| {
+                        SubgraphError::InternalProcessingError(format!(
+                            "Failed to write {}: {}",
+                            self.path.display(),
+                            e
+                        ))
+                    })
+            });
+        if let Err(e) = result {
+            tracing::error!(target: "mcp_recording", error = %e, "Failed to record gateway exchange");
+        }
+    }
+}
+
+#[async_trait]
+impl GatewayBackend for RecordingBackend {
+    async fn query_network_subgraph(
+        &self,
+        api_key: &str,
+        gateway_url: &str,
+        request: &GraphQLRequest,
+    ) -> Result<GatewayResponse, SubgraphError> {
+        let response = self
+            .inner
+            .query_network_subgraph(api_key, gateway_url, request)
+            .await?;
+        self.record(api_key, GatewayTarget::NetworkSubgraph, request, &response);
+        Ok(response)
+    }
+
+    async fn query_deployment(
+        &self,
+        api_key: &str,
+        gateway_url: &str,
+        endpoint_type: &str,
+        id: &str,
+        request: &GraphQLRequest,
+    ) -> Result<GatewayResponse, SubgraphError> {
+        let response = self
+            .inner
+            .query_deployment(api_key, gateway_url, endpoint_type, id, request)
+            .await?;
+        let target = GatewayTarget::Deployment {
+            endpoint_type: endpoint_type.to_string(),
+            id: id.to_string(),
+        };
+        self.record(api_key, target, request, &response);
+        Ok(response)
+    }
+
+    async fn query_qos_oracle(
+        &self,
+        api_key: &str,
+        gateway_url: &str,
+        request: &GraphQLRequest,
+    ) -> Result<GatewayResponse, SubgraphError> {
+        let response = self
+            .inner
+            .query_qos_oracle(api_key, gateway_url, request)
+            .await?;
+        self.record(api_key, GatewayTarget::QosOracle, request, &response);
+        Ok(response)
+    }
+}
+
+/// Collapses whitespace so that reformatted queries still match their recording.
+fn normalize_query(query: &str) -> String {
+    query.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Variables the server derives from the current time, which never match their
+/// recording: the start of the 30-day window of query counts.
+pub const TIME_DEPENDENT_VARIABLES: [&str; 1] = ["thirtyDaysAgoTimestamp"];
+
+/// Answers requests from recorded exchanges. A request is matched by target,
+/// query and variables, falling back to a match that ignores the time-dependent
+/// variables so that they still replay. Whitespace in queries is ignored.
+pub struct ReplayBackend {
+    exchanges: Vec<RecordedExchange>,
+    ignored_variables: Vec<String>,
+}
+
+impl Default for ReplayBackend {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl ReplayBackend {
+    pub fn new(exchanges: Vec<RecordedExchange>) -> Self {
+        Self {
+            exchanges,
+            ignored_variables: TIME_DEPENDENT_VARIABLES.map(String::from).to_vec(),
+        }
+    }
+
+    /// Also ignores `names` when no recording matches a request's variables
+    /// exactly, for variables that change between runs.
+    pub fn ignoring_variables<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.ignored_variables
+            .extend(names.into_iter().map(Into::into));
+        self
+    }
+
+    /// Loads an NDJSON fixture file written by `RecordingBackend`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, SubgraphError> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|e| {
+            SubgraphError::InternalProcessingError(format!(
+                "Failed to read gateway fixtures {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        let exchanges = contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str::<RecordedExchange>)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::new(exchanges))
+    }
+
+    pub fn len(&self) -> usize {
+        self.exchanges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exchanges.is_empty()
+    }
+
+    fn replay(
+        &self,
+        target: GatewayTarget,
+        request: &GraphQLRequest,
+    ) -> Result<GatewayResponse, SubgraphError> {
+        let query = normalize_query(&request.query);
+        let same_query = |exchange: &&RecordedExchange| {
+            exchange.target == target && normalize_query(&exchange.request.query) == query
+        };
+        let exact = self
+            .exchanges
+            .iter()
+            .filter(same_query)
+            .find(|exchange| exchange.request.variables == request.variables);
+        let exchange = exact.or_else(|| {
+            let variables = self.stable_variables(request.variables.as_ref());
+            let exchange = self.exchanges.iter().filter(same_query).find(|exchange| {
+                self.stable_variables(exchange.request.variables.as_ref()) == variables
+            })?;
+            tracing::info!(
+                target: "mcp_recording",
+                ignored = ?self.ignored_variables,
+                "Replaying a recording whose time-dependent variables differ"
+            );
+            Some(exchange)
+        });
+        exchange
+            .map(|exchange| exchange.response.to_response())
+            .ok_or_else(|| {
+                SubgraphError::InternalProcessingError(format!(
+                    "No recorded exchange for {:?} matching query {} and its variables",
+                    target, query
+                ))
+            })
+    }
+
+    /// The variables of a request without the ignored ones.
+    fn stable_variables(&self, variables: Option<&serde_json::Value>) -> Option<serde_json::Value> {
+        let mut variables = variables?.clone();
+        if let Some(fields) = variables.as_object_mut() {
+            fields.retain(|name, _| !self.ignored_variables.contains(name));
+        }
+        Some(variables)
+    }
+}
+
+#[async_trait]
+impl GatewayBackend for ReplayBackend {
+    async fn query_network_subgraph(
+        &self,
+        _api_key: &str,
+        _gateway_url: &str,
+        request: &GraphQLRequest,
+    ) -> Result<GatewayResponse, SubgraphError> {
+        self.replay(GatewayTarget::NetworkSubgraph, request)
+    }
+
+    async fn query_deployment(
+        &self,
+        _api_key: &str,
+        _gateway_url: &str,
+        endpoint_type: &str,
+        id: &str,
+        request: &GraphQLRequest,
+    ) -> Result<GatewayResponse, SubgraphError> {
+        let target = GatewayTarget::Deployment {
+            endpoint_type: endpoint_type.to_string(),
+            id: id.to_string(),
+        };
+        self.replay(target, request)
+    }
+
+    async fn query_qos_oracle(
+        &self,
+        _api_key: &str,
+        _gateway_url: &str,
+        request: &GraphQLRequest,
+    ) -> Result<GatewayResponse, SubgraphError> {
+        self.replay(GatewayTarget::QosOracle, request)
+    }
+}
@@ -0,0 +1,99 @@
+// SPDX-License-Identifier: Apache-2.0
+use crate::error::SubgraphError;
+use once_cell::sync::Lazy;
+use std::{
+    collections::HashMap,
+    env,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Schemas returned by the schema tools, keyed by the identifier they were
+/// requested with. Deployment schemas never change, but a subgraph ID follows
+/// its current version, so entries expire after `ttl`.
+pub struct SchemaCache {
+    ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<HashMap<String, (Instant, String)>>,
+}
+
+impl SchemaCache {
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            ttl,
+            max_entries,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reads `SCHEMA_CACHE_TTL_SECONDS` (default 300) and `SCHEMA_CACHE_MAX_ENTRIES`
+    /// (default 256). Either set to `0` disables the cache.
+    pub fn from_env() -> Option<Self> {
+        let ttl = env::var("SCHEMA_CACHE_TTL_SECONDS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(300);
+        let max_entries = env::var("SCHEMA_CACHE_MAX_ENTRIES")
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .unwrap_or(256);
+        (ttl > 0 && max_entries > 0).then(|| Self::new(Duration::from_secs(ttl), max_entries))
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(key)
+            .filter(|(inserted, _)| inserted.elapsed() < self.ttl)
+            .map(|(_, schema)| schema.clone())
+    }
+
+    /// Stores `schema`, evicting expired entries and then the oldest ones when full.
+    pub fn insert(&self, key: impl Into<String>, schema: String) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let key = key.into();
+        if !entries.contains_key(&key) && entries.len() >= self.max_entries {
+            entries.retain(|_, (inserted, _)| inserted.elapsed() < self.ttl);
+            while entries.len() >= self.max_entries {
+                let Some(oldest) = entries
+                    .iter()
+                    .min_by_key(|(_, (inserted, _))| *inserted)
+                    .map(|(key, _)| key.clone())
+                else {
+                    break;
+                };
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(key, (Instant::now(), schema));
+    }
+
+    /// Returns the cached schema for `key`, or fetches and caches it. Failures are
+    /// not cached.
+    pub async fn get_or_fetch<F, Fut>(&self, key: &str, fetch: F) -> Result<String, SubgraphError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<String, SubgraphError>>,
+    {
+        if let Some(schema) = self.get(key) {
+            tracing::debug!(target: "mcp_schema_cache", key = %key, "Schema cache hit");
+            return Ok(schema);
+        }
+        let schema = fetch().await?;
+        self.insert(key, schema.clone());
+        Ok(schema)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Schema cache configured from the environment, shared by every session.
+pub static SCHEMA_CACHE: Lazy<Option<Arc<SchemaCache>>> =
+    Lazy::new(|| SchemaCache::from_env().map(Arc::new));
@@ -0,0 +1,340 @@
+// SPDX-License-Identifier: Apache-2.0
+use crate::error::SubgraphError;
+use graphql_parser::schema::{
+    parse_schema, Definition, Directive, Field, Type, TypeDefinition, Value,
+};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+// Number of collections reported as entry points
+const MAX_ENTRY_POINTS: usize = 5;
+
+/// A compact overview of a subgraph schema: what can be queried and how the
+/// entities connect, without descriptions or directive noise.
+#[derive(Debug, Serialize)]
+pub struct SchemaSummary {
+    pub entities: Vec<EntitySummary>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub enums: Vec<EnumSummary>,
+    /// Collection fields of the entities most others point to, usually the best
+    /// place to start exploring.
+    pub entry_points: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityKind {
+    Entity,
+    Timeseries,
+    Aggregation,
+    Interface,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EntitySummary {
+    pub name: String,
+    pub kind: EntityKind,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub immutable: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub aggregation_intervals: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub implements: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub implemented_by: Vec<String>,
+    /// Scalar and enum fields.
+    pub fields: Vec<ScalarField>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub relationships: Vec<Relationship>,
+    pub query_fields: QueryFields,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScalarField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Relationship {
+    pub field: String,
+    pub target: String,
+    pub list: bool,
+    /// The field on `target` this one is derived from, when it is not stored.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub derived_from: Option<String>,
+}
+
+/// The fields graph-node generates on `Query` for an entity.
+#[derive(Debug, Serialize)]
+pub struct QueryFields {
+    /// Lookup by `id`. Aggregations have none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub single: Option<String>,
+    pub collection: String,
+    /// Input type of the collection's `where` argument.
+    #[serde(rename = "where")]
+    pub filter: String,
+    /// Enum of the collection's `orderBy` argument.
+    pub order_by: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub required_arguments: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EnumSummary {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+/// The query field of a single entity, e.g. `tokenDayData` for `TokenDayData`.
+/// A leading acronym is lowercased as a whole, like graph-node does.
+pub fn single_field_name(entity: &str) -> String {
+    let chars: Vec<char> = entity.chars().collect();
+    let upper_run = chars.iter().take_while(|c| c.is_uppercase()).count();
+    // In `NFTSale` the `S` starts the next word and stays uppercase
+    let lowered = if upper_run > 1 && upper_run < chars.len() && chars[upper_run].is_lowercase() {
+        upper_run - 1
+    } else {
+        upper_run.max(1)
+    };
+    chars
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            if i < lowered {
+                c.to_ascii_lowercase()
+            } else {
+                *c
+            }
+        })
+        .collect()
+}
+
+/// The query field of an entity collection, e.g. `pools` for `Pool`. Regular
+/// English plurals only; graph-node may differ for irregular ones.
+pub fn collection_field_name(entity: &str) -> String {
+    let single = single_field_name(entity);
+    let ends_with_consonant_y = single.ends_with('y')
+        && !single
+            .chars()
+            .rev()
+            .nth(1)
+            .is_some_and(|c| "aeiou".contains(c));
+    if ends_with_consonant_y {
+        format!("{}ies", &single[..single.len() - 1])
+    } else if ["s", "x", "z", "ch", "sh"]
+        .iter()
+        .any(|suffix| single.ends_with(suffix))
+    {
+        format!("{}es", single)
+    } else {
+        format!("{}s", single)
+    }
+}
+
+fn directive<'d>(
+    directives: &'d [Directive<'static, String>],
+    name: &str,
+) -> Option<&'d Directive<'static, String>> {
+    directives.iter().find(|d| d.name == name)
+}
+
+fn argument<'d>(
+    directive: &'d Directive<'static, String>,
+    name: &str,
+) -> Option<&'d Value<'static, String>> {
+    directive
+        .arguments
+        .iter()
+        .find(|(arg, _)| arg == name)
+        .map(|(_, value)| value)
+}
+
+fn base_type<'d>(field_type: &'d Type<'static, String>) -> (&'d str, bool) {
+    match field_type {
+        Type::NamedType(name) => (name, false),
+        Type::NonNullType(inner) => base_type(inner),
+        Type::ListType(inner) => (base_type(inner).0, true),
+    }
+}
+
+struct ParsedType<'d> {
+    name: String,
+    kind: EntityKind,
+    directives: &'d [Directive<'static, String>],
+    implements: Vec<String>,
+    fields: &'d [Field<'static, String>],
+}
+
+/// Parses `sdl` and summarizes its entities. Object types marked `@entity` or
+/// `@aggregation` count as entities; schemas without such directives, e.g.
+/// printed from introspection, use every object type except the root types.
+pub fn summarize_schema(sdl: &str) -> Result<SchemaSummary, SubgraphError> {
+    let document = parse_schema::<String>(sdl)
+        .map_err(|e| SubgraphError::InternalProcessingError(format!("Invalid schema: {}", e)))?
+        .into_static();
+
+    let mut types = Vec::new();
+    let mut enums = Vec::new();
+    for definition in &document.definitions {
+        match definition {
+            Definition::TypeDefinition(TypeDefinition::Object(object)) => {
+                let kind = match (
+                    directive(&object.directives, "entity"),
+                    directive(&object.directives, "aggregation"),
+                ) {
+                    (_, Some(_)) => EntityKind::Aggregation,
+                    (Some(entity), None)
+                        if matches!(argument(entity, "timeseries"), Some(Value::Boolean(true))) =>
+                    {
+                        EntityKind::Timeseries
+                    }
+                    _ => EntityKind::Entity,
+                };
+                types.push(ParsedType {
+                    name: object.name.clone(),
+                    kind,
+                    directives: &object.directives,
+                    implements: object.implements_interfaces.clone(),
+                    fields: &object.fields,
+                });
+            }
+            Definition::TypeDefinition(TypeDefinition::Interface(interface)) => {
+                types.push(ParsedType {
+                    name: interface.name.clone(),
+                    kind: EntityKind::Interface,
+                    directives: &interface.directives,
+                    implements: interface.implements_interfaces.clone(),
+                    fields: &interface.fields,
+                });
+            }
+            Definition::TypeDefinition(TypeDefinition::Enum(enum_type)) => {
+                enums.push(EnumSummary {
+                    name: enum_type.name.clone(),
+                    values: enum_type.values.iter().map(|v| v.name.clone()).collect(),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    let annotated = types.iter().any(|t| {
+        directive(t.directives, "entity").is_some()
+            || directive(t.directives, "aggregation").is_some()
+    });
+    types.retain(|t| {
+        if annotated {
+            t.kind == EntityKind::Interface
+                || directive(t.directives, "entity").is_some()
+                || directive(t.directives, "aggregation").is_some()
+        } else {
+            !matches!(t.name.as_str(), "Query" | "Mutation" | "Subscription")
+                && !t.name.starts_with("__")
+        }
+    });
+
+    let entity_names: HashSet<&str> = types.iter().map(|t| t.name.as_str()).collect();
+    let mut incoming: HashMap<&str, usize> = HashMap::new();
+    let mut entities: Vec<EntitySummary> = types
+        .iter()
+        .map(|t| {
+            let mut fields = Vec::new();
+            let mut relationships = Vec::new();
+            for field in t.fields {
+                let (target, list) = base_type(&field.field_type);
+                if entity_names.contains(target) {
+                    if target != t.name {
+                        *incoming.entry(target).or_default() += 1;
+                    }
+                    relationships.push(Relationship {
+                        field: field.name.clone(),
+                        target: target.to_string(),
+                        list,
+                        derived_from: directive(&field.directives, "derivedFrom")
+                            .and_then(|d| argument(d, "field"))
+                            .and_then(|value| match value {
+                                Value::String(s) | Value::Enum(s) => Some(s.clone()),
+                                _ => None,
+                            }),
+                    });
+                } else {
+                    fields.push(ScalarField {
+                        name: field.name.clone(),
+                        field_type: field.field_type.to_string(),
+                    });
+                }
+            }
+
+            let aggregation_intervals = directive(t.directives, "aggregation")
+                .and_then(|d| argument(d, "intervals"))
+                .map(|value| match value {
+                    Value::List(values) => values
+                        .iter()
+                        .filter_map(|v| match v {
+                            Value::String(s) | Value::Enum(s) => Some(s.clone()),
+                            _ => None,
+                        })
+                        .collect(),
+                    _ => Vec::new(),
+                })
+                .unwrap_or_default();
+            let immutable = t.kind == EntityKind::Timeseries
+                || directive(t.directives, "entity")
+                    .and_then(|d| argument(d, "immutable"))
+                    .is_some_and(|value| matches!(value, Value::Boolean(true)));
+            let is_aggregation = t.kind == EntityKind::Aggregation;
+
+            EntitySummary {
+                name: t.name.clone(),
+                kind: t.kind,
+                immutable,
+                aggregation_intervals,
+                implements: t.implements.clone(),
+                implemented_by: types
+                    .iter()
+                    .filter(|other| other.implements.contains(&t.name))
+                    .map(|other| other.name.clone())
+                    .collect(),
+                fields,
+                relationships,
+                query_fields: QueryFields {
+                    single: (!is_aggregation).then(|| single_field_name(&t.name)),
+                    collection: collection_field_name(&t.name),
+                    filter: format!("{}_filter", t.name),
+                    order_by: format!("{}_orderBy", t.name),
+                    required_arguments: if is_aggregation {
+                        vec!["interval: Aggregation_interval!".to_string()]
+                    } else {
+                        Vec::new()
+                    },
+                },
+            }
+        })
+        .collect();
+
+    // Rank by how many other entities point to each one, then by how many it points to
+    let mut ranked: Vec<&EntitySummary> = entities
+        .iter()
+        .filter(|e| e.kind != EntityKind::Aggregation)
+        .collect();
+    ranked.sort_by_key(|e| {
+        (
+            std::cmp::Reverse(incoming.get(e.name.as_str()).copied().unwrap_or(0)),
+            std::cmp::Reverse(e.relationships.len()),
+        )
+    });
+    let entry_points = ranked
+        .iter()
+        .take(MAX_ENTRY_POINTS)
+        .map(|e| e.query_fields.collection.clone())
+        .collect();
+
+    entities.sort_by_key(|e| e.kind == EntityKind::Interface);
+    Ok(SchemaSummary {
+        entities,
+        enums,
+        entry_points,
+    })
+}
@@ -1,16 +1,53 @@
 // SPDX-License-Identifier: Apache-2.0
-use crate::metrics::METRICS;
-use crate::{constants::SUBGRAPH_SERVER_INSTRUCTIONS, error::SubgraphError, types::*};
-use reqwest::Client;
-use rmcp::{model::*, service::RequestContext, tool, Error as McpError, RoleServer, ServerHandler};
-use serde_json::json;
-use std::time::Duration;
+use crate::audit::{collect_audit_details, is_audited_tool, AuditLogger, AuditedCall};
+use crate::backend::GatewayBackend;
+use crate::batch::BatchLimits;
+use crate::example_queries::{generate_example_queries, DEFAULT_EXAMPLE_FIRST};
+use crate::export::{ExportSettings, MAX_PAGE_SIZE};
+use crate::freshness::FreshnessSettings;
+use crate::introspection::{print_introspection_sdl, IntrospectionFormat};
+use crate::metrics::{note_tool_error, METRICS};
+use crate::policy::{DeploymentRef, PolicyStore};
+use crate::query_analysis::QueryLimits;
+use crate::rate_limit::{RateLimiter, ToolClass};
+use crate::schema_cache::SchemaCache;
+use crate::schema_summary::summarize_schema;
+use crate::server_builder::SubgraphServerBuilder;
+use crate::tabular::result_contents;
+use crate::telemetry::with_request_id;
+use crate::transform::Transform;
+use crate::usage::{SessionUsage, KEY_USAGE};
+use crate::{
+    constants::{RATE_LIMITED_ERROR_CODE, SUBGRAPH_SERVER_INSTRUCTIONS},
+    error::SubgraphError,
+    types::*,
+};
+use rmcp::{
+    handler::server::tool::ToolCallContext, model::*, service::RequestContext, tool,
+    Error as McpError, RoleServer, ServerHandler,
+};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tracing::Instrument;
 #[derive(Clone)]
 pub struct SubgraphServer {
-    #[cfg(test)]
-    pub http_client: Client,
-    #[cfg(not(test))]
-    pub(crate) http_client: Client,
+    pub(crate) backend: Arc<dyn GatewayBackend>,
+    pub(crate) gateways: Arc<HashMap<String, String>>,
+    pub(crate) default_gateway: String,
+    pub(crate) api_key: Option<String>,
+    pub(crate) rate_limiter: Option<Arc<RateLimiter>>,
+    pub(crate) session_usage: Arc<SessionUsage>,
+    pub(crate) policy: Option<Arc<PolicyStore>>,
+    pub(crate) query_limits: QueryLimits,
+    pub(crate) freshness: FreshnessSettings,
+    pub(crate) batch_limits: BatchLimits,
+    pub(crate) export: ExportSettings,
+    pub(crate) audit_logger: Option<Arc<AuditLogger>>,
+    pub(crate) schema_cache: Option<Arc<SchemaCache>>,
 }
 
 impl Default for SubgraphServer {
@@ -20,29 +57,48 @@ impl Default for SubgraphServer {
 }
 
 impl SubgraphServer {
+    /// A server configured from the environment, like the binary's.
     pub fn new() -> Self {
-        let timeout_seconds = std::env::var("SUBGRAPH_REQUEST_TIMEOUT_SECONDS")
-            .ok()
-            .and_then(|s| s.parse::<u64>().ok())
-            .unwrap_or(120); // Default to 120 seconds instead of 30
-
-        Self::with_timeout(Duration::from_secs(timeout_seconds))
+        SubgraphServerBuilder::from_env().build()
     }
 
     pub fn with_timeout(timeout: Duration) -> Self {
-        let client = Client::builder()
-            .timeout(timeout)
-            .build()
-            .expect("Failed to build HTTP client");
+        SubgraphServerBuilder::from_env().timeout(timeout).build()
+    }
 
-        SubgraphServer {
-            http_client: client,
-        }
+    pub fn builder() -> SubgraphServerBuilder {
+        SubgraphServerBuilder::new()
+    }
+
+    /// Sends gateway requests through `backend` instead of HTTP.
+    pub fn with_backend(mut self, backend: Arc<dyn GatewayBackend>) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Restricts the schema and query tools to the deployments allowed by `policy`.
+    pub fn with_deployment_policy(mut self, policy: Arc<PolicyStore>) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Records schema and query tool calls in `audit_logger`.
+    pub fn with_audit_logger(mut self, audit_logger: Arc<AuditLogger>) -> Self {
+        self.audit_logger = Some(audit_logger);
+        self
     }
 
-    #[cfg(test)]
-    pub fn get_http_client(&self) -> &Client {
-        &self.http_client
+    /// Overrides the depth, field count and row estimate limits applied to queries.
+    pub fn with_query_limits(mut self, query_limits: QueryLimits) -> Self {
+        self.query_limits = query_limits;
+        self
+    }
+
+    /// Throttles tool calls per API key. The limiter is meant to be shared by
+    /// every session so that limits apply across connections.
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
     }
 }
 
@@ -83,21 +139,32 @@ impl SubgraphServer {
 
         METRICS
             .observe_tool_call("get_schema_by_deployment_id", &api_key, || async {
+                self.enforce_deployment_policy(
+                    "get_schema_by_deployment_id",
+                    &api_key,
+                    &gateway_url,
+                    DeploymentRef::DeploymentId(&deployment_id),
+                )
+                .await?;
+
                 match self
                     .get_schema_by_deployment_id_internal(&api_key, &gateway_url, &deployment_id)
                     .await
                 {
                     Ok(schema) => Ok(CallToolResult::success(vec![Content::text(schema)])),
-                    Err(e) => match e {
-                        SubgraphError::GraphQlError(_) => Err(McpError::internal_error(
-                            e.to_string(),
-                            Some(json!({ "details": e.to_string() })),
-                        )),
-                        _ => Err(McpError::internal_error(
-                            format!("Unexpected error during schema retrieval: {}", e),
-                            Some(json!({ "details": e.to_string()})),
-                        )),
-                    },
+                    Err(e) => {
+                        note_tool_error(&e);
+                        match e {
+                            SubgraphError::GraphQlError(_) => Err(McpError::internal_error(
+                                e.to_string(),
+                                Some(json!({ "details": e.to_string() })),
+                            )),
+                            _ => Err(McpError::internal_error(
+                                format!("Unexpected error during schema retrieval: {}", e),
+                                Some(json!({ "details": e.to_string()})),
+                            )),
+                        }
+                    }
                 }
             })
             .await
@@ -136,6 +203,14 @@ impl SubgraphServer {
         };
 
         METRICS.observe_tool_call("get_schema_by_subgraph_id", &api_key, || async {
+            self.enforce_deployment_policy(
+                "get_schema_by_subgraph_id",
+                &api_key,
+                &gateway_url,
+                DeploymentRef::SubgraphId(&subgraph_id),
+            )
+            .await?;
+
             match self
                 .get_schema_by_subgraph_id_internal(&api_key, &gateway_url, &subgraph_id)
                 .await
@@ -151,6 +226,7 @@ impl SubgraphServer {
                         error = %e,
                         "Internal function call failed."
                     );
+                    note_tool_error(&e);
                     match e {
                         SubgraphError::GraphQlError(_) => Err(McpError::internal_error(e.to_string(), Some(json!({ "details": e.to_string() })),)),
                         _ => Err(McpError::internal_error(format!("Unexpected error during schema retrieval by subgraph ID: {}",e), Some(json!({ "details": e.to_string()})),
@@ -196,30 +272,43 @@ impl SubgraphServer {
 
         METRICS
             .observe_tool_call("get_schema_by_ipfs_hash", &api_key, || async {
+                self.enforce_deployment_policy(
+                    "get_schema_by_ipfs_hash",
+                    &api_key,
+                    &gateway_url,
+                    DeploymentRef::IpfsHash(&ipfs_hash),
+                )
+                .await?;
+
                 match self
                     .get_schema_by_ipfs_hash_internal(&api_key, &gateway_url, &ipfs_hash)
                     .await
                 {
                     Ok(schema) => Ok(CallToolResult::success(vec![Content::text(schema)])),
-                    Err(e) => match e {
-                        SubgraphError::GraphQlError(_) => Err(McpError::internal_error(
-                            e.to_string(),
-                            Some(json!({ "details": e.to_string() })),
-                        )),
-                        _ => Err(McpError::internal_error(
-                            format!(
-                                "Unexpected error during schema retrieval by IPFS hash: {}",
-                                e
-                            ),
-                            Some(json!({ "details": e.to_string()})),
-                        )),
-                    },
+                    Err(e) => {
+                        note_tool_error(&e);
+                        match e {
+                            SubgraphError::GraphQlError(_) => Err(McpError::internal_error(
+                                e.to_string(),
+                                Some(json!({ "details": e.to_string() })),
+                            )),
+                            _ => Err(McpError::internal_error(
+                                format!(
+                                    "Unexpected error during schema retrieval by IPFS hash: {}",
+                                    e
+                                ),
+                                Some(json!({ "details": e.to_string()})),
+                            )),
+                        }
+                    }
                 }
             })
             .await
     }
 
-    #[tool(description = "Execute a GraphQL query against a specific deployment ID.")]
+    #[tool(
+        description = "Execute a GraphQL query against a specific deployment ID. Billable: each call spends query fees from the caller's gateway API key. Unless `freshness` is false, the result's `freshness` section reports the deployment's latest indexed block and warns when it is behind."
+    )]
     pub async fn execute_query_by_deployment_id(
         &self,
         extensions: Extensions,
@@ -227,6 +316,10 @@ impl SubgraphServer {
             deployment_id,
             query,
             variables,
+            freshness,
+            decode,
+            transform,
+            output_format,
         }: ExecuteQueryByDeploymentIdRequest,
     ) -> Result<CallToolResult, McpError> {
         let api_key = match self.get_api_key(&extensions) {
@@ -255,40 +348,89 @@ impl SubgraphServer {
 
         METRICS
             .observe_tool_call("execute_query_by_deployment_id", &api_key, || async {
-                match self
-                    .execute_query_on_endpoint(
+                // Checked before the query is sent, so a bad spec costs no fees
+                let transform = transform.map(Transform::new).transpose().map_err(|e| {
+                    McpError::invalid_params(
+                        e.to_string(),
+                        Some(json!({ "details": e.to_string() })),
+                    )
+                })?;
+                self.enforce_deployment_policy(
+                    "execute_query_by_deployment_id",
+                    &api_key,
+                    &gateway_url,
+                    DeploymentRef::DeploymentId(&deployment_id),
+                )
+                .await?;
+
+                let result = self
+                    .execute_query_with_freshness(
                         &api_key,
                         &gateway_url,
                         "deployments/id",
                         &deployment_id,
                         &query,
                         variables,
+                        freshness,
                     )
-                    .await
-                {
-                    Ok(result) => Ok(CallToolResult::success(vec![Content::text(format!(
-                        "{:#}",
-                        result
-                    ))])),
-                    Err(e) => match e {
-                        SubgraphError::GraphQlError(_) => Err(McpError::internal_error(
-                            e.to_string(),
-                            Some(json!({ "details": e.to_string() })),
-                        )),
-                        _ => Err(McpError::internal_error(
-                            format!(
-                                "Unexpected error during query execution by deployment ID: {}",
-                                e
-                            ),
-                            Some(json!({ "details": e.to_string()})),
-                        )),
-                    },
+                    .await;
+                let result = match (result, &decode) {
+                    (Ok(result), Some(decode)) => Ok(self
+                        .decode_result(
+                            &api_key,
+                            &gateway_url,
+                            DeploymentRef::DeploymentId(&deployment_id),
+                            &query,
+                            decode,
+                            result,
+                        )
+                        .await),
+                    (result, _) => result,
+                };
+                match result.and_then(|result| match &transform {
+                    Some(transform) => transform.apply(result),
+                    None => Ok(result),
+                }) {
+                    Ok(result) => Ok(CallToolResult::success(result_contents(
+                        &result,
+                        output_format.unwrap_or_default(),
+                    ))),
+                    Err(e) => {
+                        note_tool_error(&e);
+                        match e {
+                            SubgraphError::GraphQlError(_) => Err(McpError::internal_error(
+                                e.to_string(),
+                                Some(json!({ "details": e.to_string() })),
+                            )),
+                            SubgraphError::QueryRejected(_)
+                            | SubgraphError::InvalidTransform(_) => Err(McpError::invalid_params(
+                                e.to_string(),
+                                Some(json!({ "details": e.to_string() })),
+                            )),
+                            SubgraphError::BudgetExceeded(_) => Err(McpError::invalid_request(
+                                e.to_string(),
+                                Some(json!({
+                                    "details": e.to_string(),
+                                    "session_id": self.session_usage.session_id(),
+                                })),
+                            )),
+                            _ => Err(McpError::internal_error(
+                                format!(
+                                    "Unexpected error during query execution by deployment ID: {}",
+                                    e
+                                ),
+                                Some(json!({ "details": e.to_string()})),
+                            )),
+                        }
+                    }
                 }
             })
             .await
     }
 
-    #[tool(description = "Execute a GraphQL query against a specific IPFS hash.")]
+    #[tool(
+        description = "Execute a GraphQL query against a specific IPFS hash. Billable: each call spends query fees from the caller's gateway API key. Unless `freshness` is false, the result's `freshness` section reports the deployment's latest indexed block and warns when it is behind."
+    )]
     pub async fn execute_query_by_ipfs_hash(
         &self,
         extensions: Extensions,
@@ -296,6 +438,10 @@ impl SubgraphServer {
             ipfs_hash,
             query,
             variables,
+            freshness,
+            decode,
+            transform,
+            output_format,
         }: ExecuteQueryByIpfsHashRequest,
     ) -> Result<CallToolResult, McpError> {
         let api_key = match self.get_api_key(&extensions) {
@@ -324,40 +470,89 @@ impl SubgraphServer {
 
         METRICS
             .observe_tool_call("execute_query_by_ipfs_hash", &api_key, || async {
-                match self
-                    .execute_query_on_endpoint(
+                // Checked before the query is sent, so a bad spec costs no fees
+                let transform = transform.map(Transform::new).transpose().map_err(|e| {
+                    McpError::invalid_params(
+                        e.to_string(),
+                        Some(json!({ "details": e.to_string() })),
+                    )
+                })?;
+                self.enforce_deployment_policy(
+                    "execute_query_by_ipfs_hash",
+                    &api_key,
+                    &gateway_url,
+                    DeploymentRef::IpfsHash(&ipfs_hash),
+                )
+                .await?;
+
+                let result = self
+                    .execute_query_with_freshness(
                         &api_key,
                         &gateway_url,
                         "deployments/id",
                         &ipfs_hash,
                         &query,
                         variables,
+                        freshness,
                     )
-                    .await
-                {
-                    Ok(result) => Ok(CallToolResult::success(vec![Content::text(format!(
-                        "{:#}",
-                        result
-                    ))])),
-                    Err(e) => match e {
-                        SubgraphError::GraphQlError(_) => Err(McpError::internal_error(
-                            e.to_string(),
-                            Some(json!({ "details": e.to_string() })),
-                        )),
-                        _ => Err(McpError::internal_error(
-                            format!(
-                                "Unexpected error during query execution by IPFS hash: {}",
-                                e
-                            ),
-                            Some(json!({ "details": e.to_string()})),
-                        )),
-                    },
+                    .await;
+                let result = match (result, &decode) {
+                    (Ok(result), Some(decode)) => Ok(self
+                        .decode_result(
+                            &api_key,
+                            &gateway_url,
+                            DeploymentRef::IpfsHash(&ipfs_hash),
+                            &query,
+                            decode,
+                            result,
+                        )
+                        .await),
+                    (result, _) => result,
+                };
+                match result.and_then(|result| match &transform {
+                    Some(transform) => transform.apply(result),
+                    None => Ok(result),
+                }) {
+                    Ok(result) => Ok(CallToolResult::success(result_contents(
+                        &result,
+                        output_format.unwrap_or_default(),
+                    ))),
+                    Err(e) => {
+                        note_tool_error(&e);
+                        match e {
+                            SubgraphError::GraphQlError(_) => Err(McpError::internal_error(
+                                e.to_string(),
+                                Some(json!({ "details": e.to_string() })),
+                            )),
+                            SubgraphError::QueryRejected(_)
+                            | SubgraphError::InvalidTransform(_) => Err(McpError::invalid_params(
+                                e.to_string(),
+                                Some(json!({ "details": e.to_string() })),
+                            )),
+                            SubgraphError::BudgetExceeded(_) => Err(McpError::invalid_request(
+                                e.to_string(),
+                                Some(json!({
+                                    "details": e.to_string(),
+                                    "session_id": self.session_usage.session_id(),
+                                })),
+                            )),
+                            _ => Err(McpError::internal_error(
+                                format!(
+                                    "Unexpected error during query execution by IPFS hash: {}",
+                                    e
+                                ),
+                                Some(json!({ "details": e.to_string()})),
+                            )),
+                        }
+                    }
                 }
             })
             .await
     }
 
-    #[tool(description = "Execute a GraphQL query against the latest deployment of a subgraph ID.")]
+    #[tool(
+        description = "Execute a GraphQL query against the latest deployment of a subgraph ID. Billable: each call spends query fees from the caller's gateway API key. Unless `freshness` is false, the result's `freshness` section reports the deployment's latest indexed block and warns when it is behind."
+    )]
     pub async fn execute_query_by_subgraph_id(
         &self,
         extensions: Extensions,
@@ -365,6 +560,10 @@ impl SubgraphServer {
             subgraph_id,
             query,
             variables,
+            freshness,
+            decode,
+            transform,
+            output_format,
         }: ExecuteQueryBySubgraphIdRequest,
     ) -> Result<CallToolResult, McpError> {
         let api_key = match self.get_api_key(&extensions) {
@@ -393,34 +592,323 @@ impl SubgraphServer {
 
         METRICS
             .observe_tool_call("execute_query_by_subgraph_id", &api_key, || async {
-                match self
-                    .execute_query_on_endpoint(
+                // Checked before the query is sent, so a bad spec costs no fees
+                let transform = transform.map(Transform::new).transpose().map_err(|e| {
+                    McpError::invalid_params(
+                        e.to_string(),
+                        Some(json!({ "details": e.to_string() })),
+                    )
+                })?;
+                self.enforce_deployment_policy(
+                    "execute_query_by_subgraph_id",
+                    &api_key,
+                    &gateway_url,
+                    DeploymentRef::SubgraphId(&subgraph_id),
+                )
+                .await?;
+
+                let result = self
+                    .execute_query_with_freshness(
                         &api_key,
                         &gateway_url,
                         "subgraphs/id",
                         &subgraph_id,
                         &query,
                         variables,
+                        freshness,
                     )
+                    .await;
+                let result = match (result, &decode) {
+                    (Ok(result), Some(decode)) => Ok(self
+                        .decode_result(
+                            &api_key,
+                            &gateway_url,
+                            DeploymentRef::SubgraphId(&subgraph_id),
+                            &query,
+                            decode,
+                            result,
+                        )
+                        .await),
+                    (result, _) => result,
+                };
+                match result.and_then(|result| match &transform {
+                    Some(transform) => transform.apply(result),
+                    None => Ok(result),
+                }) {
+                    Ok(result) => Ok(CallToolResult::success(result_contents(
+                        &result,
+                        output_format.unwrap_or_default(),
+                    ))),
+                    Err(e) => {
+                        note_tool_error(&e);
+                        match e {
+                            SubgraphError::GraphQlError(_) => Err(McpError::internal_error(
+                                e.to_string(),
+                                Some(json!({ "details": e.to_string() })),
+                            )),
+                            SubgraphError::QueryRejected(_)
+                            | SubgraphError::InvalidTransform(_) => Err(McpError::invalid_params(
+                                e.to_string(),
+                                Some(json!({ "details": e.to_string() })),
+                            )),
+                            SubgraphError::BudgetExceeded(_) => Err(McpError::invalid_request(
+                                e.to_string(),
+                                Some(json!({
+                                    "details": e.to_string(),
+                                    "session_id": self.session_usage.session_id(),
+                                })),
+                            )),
+                            _ => Err(McpError::internal_error(
+                                format!(
+                                    "Unexpected error during query execution by subgraph ID: {}",
+                                    e
+                                ),
+                                Some(json!({ "details": e.to_string()})),
+                            )),
+                        }
+                    }
+                }
+            })
+            .await
+    }
+
+    #[tool(
+        description = "Execute several GraphQL queries in one call, each against its own deployment ID (0x...), IPFS hash (Qm...) or subgraph ID, e.g. to compare protocols. Queries run concurrently and each result holds its own `data` and `freshness`, or an `error` that does not fail the rest of the batch. Billable: every query spends query fees from the caller's gateway API key."
+    )]
+    pub async fn execute_queries_batch(
+        &self,
+        extensions: Extensions,
+        #[tool(aggr)] ExecuteQueriesBatchRequest {
+            queries,
+            max_concurrency,
+            freshness,
+        }: ExecuteQueriesBatchRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let api_key = match self.get_api_key(&extensions) {
+            Ok(key) => key,
+            Err(SubgraphError::ApiKeyNotSet) => return Err(McpError::invalid_params(
+                "Configuration error: API key not found. Please set the GATEWAY_API_KEY environment variable or provide a Bearer token in the Authorization header.",
+                None,
+            )),
+            Err(e) => return Err(McpError::internal_error(format!("Error retrieving API key: {}", e), Some(json!({ "details": e.to_string() }))))
+        };
+        let gateway_url = match self.get_gateway_url(&extensions) {
+            Ok(url) => url,
+            Err(SubgraphError::InvalidGatewayId(msg)) => {
+                return Err(McpError::internal_error(
+                    msg.clone(),
+                    Some(json!({ "details": msg.clone() })),
+                ))
+            }
+            Err(e) => {
+                return Err(McpError::internal_error(
+                    format!("Error retrieving gateway URL: {}", e),
+                    Some(json!({ "details": e.to_string() })),
+                ))
+            }
+        };
+
+        METRICS
+            .observe_tool_call("execute_queries_batch", &api_key, || async {
+                if queries.is_empty() {
+                    return Err(McpError::invalid_params(
+                        "The batch holds no queries.",
+                        None,
+                    ));
+                }
+                let max_queries = self.batch_limits.max_queries;
+                if queries.len() > max_queries {
+                    return Err(McpError::invalid_params(
+                        format!(
+                            "The batch holds {} queries, more than the limit of {}. Split it into smaller batches.",
+                            queries.len(),
+                            max_queries
+                        ),
+                        Some(json!({ "max_queries": max_queries })),
+                    ));
+                }
+
+                let concurrency = max_concurrency
+                    .unwrap_or(usize::MAX)
+                    .min(self.batch_limits.max_concurrency);
+                let result = self
+                    .execute_batch_internal(
+                        "execute_queries_batch",
+                        &api_key,
+                        &gateway_url,
+                        queries.into_iter().map(|query| (query, None)).collect(),
+                        concurrency,
+                        freshness,
+                    )
+                    .await;
+                Ok(CallToolResult::success(vec![Content::text(format!(
+                    "{:#}",
+                    result
+                ))]))
+            })
+            .await
+    }
+
+    #[tool(
+        description = "Run one GraphQL query on every chain a protocol is deployed to. Give a keyword matched against subgraph names (e.g., 'Uniswap V3') or an explicit list of subgraph IDs. Only the current deployments whose schema has every entity and field the query selects are queried; the others are listed under `skipped` with the reason. Each result is tagged with its subgraph, `network` and `schema_hash`. With `merge`, the top-level collections of all chains are also merged into one list per field, tagged with `_network` and optionally sorted by `order_by`. Billable: every deployment queried spends query fees from the caller's gateway API key."
+    )]
+    pub async fn query_across_chains(
+        &self,
+        extensions: Extensions,
+        #[tool(aggr)] request: QueryAcrossChainsRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let api_key = match self.get_api_key(&extensions) {
+            Ok(key) => key,
+            Err(SubgraphError::ApiKeyNotSet) => return Err(McpError::invalid_params(
+                "Configuration error: API key not found. Please set the GATEWAY_API_KEY environment variable or provide a Bearer token in the Authorization header.",
+                None,
+            )),
+            Err(e) => return Err(McpError::internal_error(format!("Error retrieving API key: {}", e), Some(json!({ "details": e.to_string() }))))
+        };
+        let gateway_url = match self.get_gateway_url(&extensions) {
+            Ok(url) => url,
+            Err(SubgraphError::InvalidGatewayId(msg)) => {
+                return Err(McpError::internal_error(
+                    msg.clone(),
+                    Some(json!({ "details": msg.clone() })),
+                ))
+            }
+            Err(e) => {
+                return Err(McpError::internal_error(
+                    format!("Error retrieving gateway URL: {}", e),
+                    Some(json!({ "details": e.to_string() })),
+                ))
+            }
+        };
+
+        METRICS
+            .observe_tool_call("query_across_chains", &api_key, || async {
+                match (&request.keyword, &request.subgraph_ids) {
+                    (Some(_), None) => {}
+                    (None, Some(subgraph_ids)) => {
+                        let max_queries = self.batch_limits.max_queries;
+                        if subgraph_ids.is_empty() || subgraph_ids.len() > max_queries {
+                            return Err(McpError::invalid_params(
+                                format!(
+                                    "Give between 1 and {} subgraph IDs, not {}.",
+                                    max_queries,
+                                    subgraph_ids.len()
+                                ),
+                                Some(json!({ "max_queries": max_queries })),
+                            ));
+                        }
+                    }
+                    _ => {
+                        return Err(McpError::invalid_params(
+                            "Give either a keyword or a list of subgraph_ids, but not both.",
+                            None,
+                        ))
+                    }
+                }
+
+                match self
+                    .query_across_chains_internal(&api_key, &gateway_url, request)
                     .await
                 {
                     Ok(result) => Ok(CallToolResult::success(vec![Content::text(format!(
                         "{:#}",
                         result
                     ))])),
-                    Err(e) => match e {
-                        SubgraphError::GraphQlError(_) => Err(McpError::internal_error(
-                            e.to_string(),
-                            Some(json!({ "details": e.to_string() })),
-                        )),
-                        _ => Err(McpError::internal_error(
-                            format!(
-                                "Unexpected error during query execution by subgraph ID: {}",
-                                e
-                            ),
-                            Some(json!({ "details": e.to_string()})),
-                        )),
-                    },
+                    Err(e) => {
+                        note_tool_error(&e);
+                        match e {
+                            SubgraphError::QueryRejected(_) => Err(McpError::invalid_params(
+                                e.to_string(),
+                                Some(json!({ "details": e.to_string() })),
+                            )),
+                            _ => Err(McpError::internal_error(
+                                format!("Failed to query across chains: {}", e),
+                                Some(json!({ "details": e.to_string() })),
+                            )),
+                        }
+                    }
+                }
+            })
+            .await
+    }
+
+    #[tool(
+        description = "Export every row of one collection to a file in the server's export directory, as CSV, NDJSON or Parquet. The query must select a single collection field (e.g., `{ swaps(where: { pool: \"0x...\" }) { id amountUSD } }`) without `first`, `skip` or `orderBy`; it is paged by `id` until the collection is exhausted or `max_rows` is reached. CSV and Parquet columns follow the selection, typed from the subgraph schema. Returns the file's path, row count, byte size and the first rows as a preview. `deployment` is a subgraph ID, deployment ID (0x...) or IPFS hash (Qm...). Billable: every page spends query fees from the caller's gateway API key."
+    )]
+    pub async fn export_query(
+        &self,
+        extensions: Extensions,
+        #[tool(aggr)] request: ExportQueryRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let api_key = match self.get_api_key(&extensions) {
+            Ok(key) => key,
+            Err(SubgraphError::ApiKeyNotSet) => return Err(McpError::invalid_params(
+                "Configuration error: API key not found. Please set the GATEWAY_API_KEY environment variable or provide a Bearer token in the Authorization header.",
+                None,
+            )),
+            Err(e) => return Err(McpError::internal_error(format!("Error retrieving API key: {}", e), Some(json!({ "details": e.to_string() }))))
+        };
+        let gateway_url = match self.get_gateway_url(&extensions) {
+            Ok(url) => url,
+            Err(SubgraphError::InvalidGatewayId(msg)) => {
+                return Err(McpError::internal_error(
+                    msg.clone(),
+                    Some(json!({ "details": msg.clone() })),
+                ))
+            }
+            Err(e) => {
+                return Err(McpError::internal_error(
+                    format!("Error retrieving gateway URL: {}", e),
+                    Some(json!({ "details": e.to_string() })),
+                ))
+            }
+        };
+
+        METRICS
+            .observe_tool_call("export_query", &api_key, || async {
+                let deployment = request.deployment.clone();
+                self.enforce_deployment_policy(
+                    "export_query",
+                    &api_key,
+                    &gateway_url,
+                    DeploymentRef::parse(&deployment),
+                )
+                .await?;
+
+                match self
+                    .export_query_internal(&api_key, &gateway_url, request)
+                    .await
+                {
+                    Ok(result) => Ok(CallToolResult::success(vec![Content::text(format!(
+                        "{:#}",
+                        result
+                    ))])),
+                    Err(e) => {
+                        note_tool_error(&e);
+                        match e {
+                            SubgraphError::GraphQlError(_) => Err(McpError::internal_error(
+                                e.to_string(),
+                                Some(json!({ "details": e.to_string() })),
+                            )),
+                            SubgraphError::QueryRejected(_) | SubgraphError::ExportRefused(_) => {
+                                Err(McpError::invalid_params(
+                                    e.to_string(),
+                                    Some(json!({ "details": e.to_string() })),
+                                ))
+                            }
+                            SubgraphError::BudgetExceeded(_) => Err(McpError::invalid_request(
+                                e.to_string(),
+                                Some(json!({
+                                    "details": e.to_string(),
+                                    "session_id": self.session_usage.session_id(),
+                                })),
+                            )),
+                            _ => Err(McpError::internal_error(
+                                format!("Failed to export query results: {}", e),
+                                Some(json!({ "details": e.to_string() })),
+                            )),
+                        }
+                    }
                 }
             })
             .await
@@ -476,19 +964,22 @@ impl SubgraphServer {
                         "{:#}",
                         result
                     ))])),
-                    Err(e) => match e {
-                        SubgraphError::GraphQlError(_) => Err(McpError::internal_error(
-                            e.to_string(),
-                            Some(json!({ "details": e.to_string() })),
-                        )),
-                        _ => Err(McpError::internal_error(
-                            format!(
-                                "Unexpected error during top subgraph deployment retrieval: {}",
-                                e
-                            ),
-                            Some(json!({ "details": e.to_string()})),
-                        )),
-                    },
+                    Err(e) => {
+                        note_tool_error(&e);
+                        match e {
+                            SubgraphError::GraphQlError(_) => Err(McpError::internal_error(
+                                e.to_string(),
+                                Some(json!({ "details": e.to_string() })),
+                            )),
+                            _ => Err(McpError::internal_error(
+                                format!(
+                                    "Unexpected error during top subgraph deployment retrieval: {}",
+                                    e
+                                ),
+                                Some(json!({ "details": e.to_string()})),
+                            )),
+                        }
+                    }
                 }
             })
             .await
@@ -536,16 +1027,19 @@ impl SubgraphServer {
                         "{:#}",
                         result
                     ))])),
-                    Err(e) => match e {
-                        SubgraphError::GraphQlError(_) => Err(McpError::internal_error(
-                            e.to_string(),
-                            Some(json!({ "details": e.to_string() })),
-                        )),
-                        _ => Err(McpError::internal_error(
-                            format!("Unexpected error during subgraph search: {}", e),
-                            Some(json!({ "details": e.to_string()})),
-                        )),
-                    },
+                    Err(e) => {
+                        note_tool_error(&e);
+                        match e {
+                            SubgraphError::GraphQlError(_) => Err(McpError::internal_error(
+                                e.to_string(),
+                                Some(json!({ "details": e.to_string() })),
+                            )),
+                            _ => Err(McpError::internal_error(
+                                format!("Unexpected error during subgraph search: {}", e),
+                                Some(json!({ "details": e.to_string()})),
+                            )),
+                        }
+                    }
                 }
             })
             .await
@@ -598,26 +1092,409 @@ impl SubgraphServer {
                         "{:#}",
                         result
                     ))])),
-                    Err(e) => match e {
-                        SubgraphError::GraphQlError(_) => Err(McpError::internal_error(
-                            e.to_string(),
-                            Some(json!({ "details": e.to_string() })),
-                        )),
-                        _ => Err(McpError::internal_error(
-                            format!(
-                                "Unexpected error during 30-day query count retrieval: {}",
-                                e
-                            ),
-                            Some(json!({ "details": e.to_string()})),
-                        )),
-                    },
+                    Err(e) => {
+                        note_tool_error(&e);
+                        match e {
+                            SubgraphError::GraphQlError(_) => Err(McpError::internal_error(
+                                e.to_string(),
+                                Some(json!({ "details": e.to_string() })),
+                            )),
+                            _ => Err(McpError::internal_error(
+                                format!(
+                                    "Unexpected error during 30-day query count retrieval: {}",
+                                    e
+                                ),
+                                Some(json!({ "details": e.to_string()})),
+                            )),
+                        }
+                    }
+                }
+            })
+            .await
+    }
+
+    #[tool(
+        description = "Summarize the schema of a deployment (0x...), IPFS hash (Qm...) or subgraph ID as compact JSON: entities with their scalar fields, relationships (including @derivedFrom), immutable, timeseries and aggregation markers, enums, likely entry point collections, and the generated query fields (single, collection, where filter and orderBy enum) of each entity. Much smaller than the raw schema; use it before writing a first query."
+    )]
+    pub async fn summarize_schema(
+        &self,
+        extensions: Extensions,
+        #[tool(aggr)] SummarizeSchemaRequest { deployment }: SummarizeSchemaRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let api_key = match self.get_api_key(&extensions) {
+            Ok(key) => key,
+            Err(SubgraphError::ApiKeyNotSet) => return Err(McpError::invalid_params(
+                "Configuration error: API key not found. Please set the GATEWAY_API_KEY environment variable or provide a Bearer token in the Authorization header.",
+                None,
+            )),
+            Err(e) => return Err(McpError::internal_error(format!("Error retrieving API key: {}", e), Some(json!({ "details": e.to_string() }))))
+        };
+        let gateway_url = match self.get_gateway_url(&extensions) {
+            Ok(url) => url,
+            Err(SubgraphError::InvalidGatewayId(msg)) => {
+                return Err(McpError::internal_error(
+                    msg.clone(),
+                    Some(json!({ "details": msg.clone() })),
+                ))
+            }
+            Err(e) => {
+                return Err(McpError::internal_error(
+                    format!("Error retrieving gateway URL: {}", e),
+                    Some(json!({ "details": e.to_string() })),
+                ))
+            }
+        };
+
+        METRICS
+            .observe_tool_call("summarize_schema", &api_key, || async {
+                let deployment = DeploymentRef::parse(&deployment);
+                self.enforce_deployment_policy(
+                    "summarize_schema",
+                    &api_key,
+                    &gateway_url,
+                    deployment,
+                )
+                .await?;
+
+                match self
+                    .get_schema_internal(&api_key, &gateway_url, deployment)
+                    .await
+                    .and_then(|schema| summarize_schema(&schema))
+                {
+                    Ok(summary) => Ok(CallToolResult::success(vec![Content::text(format!(
+                        "{:#}",
+                        json!(summary)
+                    ))])),
+                    Err(e) => {
+                        note_tool_error(&e);
+                        match e {
+                            SubgraphError::GraphQlError(_) => Err(McpError::internal_error(
+                                e.to_string(),
+                                Some(json!({ "details": e.to_string() })),
+                            )),
+                            _ => Err(McpError::internal_error(
+                                format!("Unexpected error during schema summarization: {}", e),
+                                Some(json!({ "details": e.to_string()})),
+                            )),
+                        }
+                    }
                 }
             })
             .await
     }
+
+    #[tool(
+        description = "Generate ready-to-run example queries for an entity of a deployment (0x...), IPFS hash (Qm...) or subgraph ID: the latest entities by timestamp or block, a lookup by id, a list filtered with `where`, and a traversal of a relationship. Each query comes with a description, example variables and their types. Without an entity, the schema's most connected entity is used."
+    )]
+    pub async fn generate_example_queries(
+        &self,
+        extensions: Extensions,
+        #[tool(aggr)] GenerateExampleQueriesRequest {
+            deployment,
+            entity,
+            first,
+        }: GenerateExampleQueriesRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let api_key = match self.get_api_key(&extensions) {
+            Ok(key) => key,
+            Err(SubgraphError::ApiKeyNotSet) => return Err(McpError::invalid_params(
+                "Configuration error: API key not found. Please set the GATEWAY_API_KEY environment variable or provide a Bearer token in the Authorization header.",
+                None,
+            )),
+            Err(e) => return Err(McpError::internal_error(format!("Error retrieving API key: {}", e), Some(json!({ "details": e.to_string() }))))
+        };
+        let gateway_url = match self.get_gateway_url(&extensions) {
+            Ok(url) => url,
+            Err(SubgraphError::InvalidGatewayId(msg)) => {
+                return Err(McpError::internal_error(
+                    msg.clone(),
+                    Some(json!({ "details": msg.clone() })),
+                ))
+            }
+            Err(e) => {
+                return Err(McpError::internal_error(
+                    format!("Error retrieving gateway URL: {}", e),
+                    Some(json!({ "details": e.to_string() })),
+                ))
+            }
+        };
+
+        METRICS
+            .observe_tool_call("generate_example_queries", &api_key, || async {
+                let deployment = DeploymentRef::parse(&deployment);
+                self.enforce_deployment_policy(
+                    "generate_example_queries",
+                    &api_key,
+                    &gateway_url,
+                    deployment,
+                )
+                .await?;
+
+                match self
+                    .get_schema_internal(&api_key, &gateway_url, deployment)
+                    .await
+                    .and_then(|schema| summarize_schema(&schema))
+                    .and_then(|summary| {
+                        generate_example_queries(
+                            &summary,
+                            entity.as_deref(),
+                            first.unwrap_or(DEFAULT_EXAMPLE_FIRST),
+                        )
+                    }) {
+                    Ok(examples) => Ok(CallToolResult::success(vec![Content::text(format!(
+                        "{:#}",
+                        json!(examples)
+                    ))])),
+                    Err(e) => {
+                        note_tool_error(&e);
+                        match e {
+                            SubgraphError::GraphQlError(_) => Err(McpError::internal_error(
+                                e.to_string(),
+                                Some(json!({ "details": e.to_string() })),
+                            )),
+                            _ => Err(McpError::internal_error(
+                                format!("Unexpected error during example query generation: {}", e),
+                                Some(json!({ "details": e.to_string()})),
+                            )),
+                        }
+                    }
+                }
+            })
+            .await
+    }
+
+    #[tool(
+        description = "Get the schema of a deployment (0x...), IPFS hash (Qm...) or subgraph ID by running a GraphQL introspection query against the deployment itself. Unlike the other schema tools, this works for deployments missing from the network subgraph and returns the generated query API: collection and single fields, where filter input types, orderBy enums and _meta. Format 'sdl' (default) prints that API as GraphQL SDL; 'json' returns the full introspection result. Runs a billable query unless the result is cached."
+    )]
+    pub async fn get_schema_by_introspection(
+        &self,
+        extensions: Extensions,
+        #[tool(aggr)]
+        GetSchemaByIntrospectionRequest { deployment, format }: GetSchemaByIntrospectionRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let api_key = match self.get_api_key(&extensions) {
+            Ok(key) => key,
+            Err(SubgraphError::ApiKeyNotSet) => return Err(McpError::invalid_params(
+                "Configuration error: API key not found. Please set the GATEWAY_API_KEY environment variable or provide a Bearer token in the Authorization header.",
+                None,
+            )),
+            Err(e) => return Err(McpError::internal_error(format!("Error retrieving API key: {}", e), Some(json!({ "details": e.to_string() }))))
+        };
+        let gateway_url = match self.get_gateway_url(&extensions) {
+            Ok(url) => url,
+            Err(SubgraphError::InvalidGatewayId(msg)) => {
+                return Err(McpError::internal_error(
+                    msg.clone(),
+                    Some(json!({ "details": msg.clone() })),
+                ))
+            }
+            Err(e) => {
+                return Err(McpError::internal_error(
+                    format!("Error retrieving gateway URL: {}", e),
+                    Some(json!({ "details": e.to_string() })),
+                ))
+            }
+        };
+
+        METRICS
+            .observe_tool_call("get_schema_by_introspection", &api_key, || async {
+                let deployment = DeploymentRef::parse(&deployment);
+                self.enforce_deployment_policy(
+                    "get_schema_by_introspection",
+                    &api_key,
+                    &gateway_url,
+                    deployment,
+                )
+                .await?;
+
+                let result = self
+                    .get_introspection_internal(&api_key, &gateway_url, deployment)
+                    .await
+                    .and_then(|data| match format.unwrap_or_default() {
+                        IntrospectionFormat::Json => Ok(data),
+                        IntrospectionFormat::Sdl => {
+                            print_introspection_sdl(&serde_json::from_str(&data)?)
+                        }
+                    });
+                match result {
+                    Ok(schema) => Ok(CallToolResult::success(vec![Content::text(schema)])),
+                    Err(e) => {
+                        note_tool_error(&e);
+                        match e {
+                            SubgraphError::GraphQlError(_) => Err(McpError::internal_error(
+                                e.to_string(),
+                                Some(json!({ "details": e.to_string() })),
+                            )),
+                            SubgraphError::BudgetExceeded(_) => Err(McpError::invalid_request(
+                                e.to_string(),
+                                Some(json!({ "details": e.to_string() })),
+                            )),
+                            _ => Err(McpError::internal_error(
+                                format!("Unexpected error during schema introspection: {}", e),
+                                Some(json!({ "details": e.to_string()})),
+                            )),
+                        }
+                    }
+                }
+            })
+            .await
+    }
+
+    #[tool(
+        description = "Get the billable query usage of the current session and API key: number of executed queries, estimated fees (when the gateway reports them) and the configured session limits."
+    )]
+    pub async fn get_session_usage(
+        &self,
+        extensions: Extensions,
+        #[tool(aggr)] GetSessionUsageRequest {}: GetSessionUsageRequest,
+    ) -> Result<CallToolResult, McpError> {
+        // Usage is reported even without an API key; only the per-key section needs one.
+        let api_key = self.get_api_key(&extensions).ok();
+        let metrics_key = api_key.clone().unwrap_or_default();
+
+        METRICS
+            .observe_tool_call("get_session_usage", &metrics_key, || async {
+                let mut result = json!({
+                    "session_id": self.session_usage.session_id(),
+                    "session": self.session_usage.snapshot(),
+                    "limits": self.session_usage.limits(),
+                });
+                if let Some(api_key) = &api_key {
+                    result["api_key"] = json!(KEY_USAGE.snapshot(api_key));
+                }
+                Ok(CallToolResult::success(vec![Content::text(format!(
+                    "{:#}",
+                    result
+                ))]))
+            })
+            .await
+    }
+}
+
+impl SubgraphServer {
+    /// Returns the request ID with the tool result: in the error data on failure
+    /// and, as the `CallToolResult` of rmcp 0.1 has no `_meta`, as a trailing
+    /// content item for the user rather than the model on success. Tool output
+    /// stays in the leading items.
+    fn attach_request_id(
+        result: Result<CallToolResult, McpError>,
+        request_id: &str,
+    ) -> Result<CallToolResult, McpError> {
+        match result {
+            Ok(mut result) => {
+                result.content.push(
+                    Content::text(json!({ "request_id": request_id }).to_string())
+                        .with_audience(vec![Role::User])
+                        .with_priority(0.0),
+                );
+                Ok(result)
+            }
+            Err(mut error) => {
+                error.data = Some(match error.data.take() {
+                    Some(Value::Object(mut data)) => {
+                        data.insert("request_id".to_string(), json!(request_id));
+                        Value::Object(data)
+                    }
+                    Some(details) => json!({ "details": details, "request_id": request_id }),
+                    None => json!({ "request_id": request_id }),
+                });
+                Err(error)
+            }
+        }
+    }
+
+    /// Total length of the text content returned by a tool.
+    fn response_bytes(result: &CallToolResult) -> usize {
+        result
+            .content
+            .iter()
+            .filter_map(|content| content.raw.as_text())
+            .map(|text| text.text.len())
+            .sum()
+    }
+
+    /// Applies the per-key rate limit, then dispatches to the tool.
+    async fn call_tool_inner(
+        &self,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        // Calls without an API key are left to fail inside the tool itself.
+        let _permit = match (&self.rate_limiter, self.get_api_key(&context.extensions)) {
+            (Some(rate_limiter), Ok(api_key)) => {
+                let tool_class = ToolClass::for_tool(&request.name);
+                // Each query of a batch counts against the rate like a separate call
+                let list_len = |name: &str| {
+                    request
+                        .arguments
+                        .as_ref()
+                        .and_then(|args| args.get(name))
+                        .and_then(|list| list.as_array())
+                        .map(|list| list.len())
+                };
+                let weight = match request.name.as_ref() {
+                    "execute_queries_batch" => list_len("queries").unwrap_or(1),
+                    // A keyword may match up to a full batch of deployments
+                    "query_across_chains" => {
+                        list_len("subgraph_ids").unwrap_or(self.batch_limits.max_queries)
+                    }
+                    // Every page of an export is a billable query
+                    "export_query" => {
+                        let number = |name: &str| {
+                            request
+                                .arguments
+                                .as_ref()
+                                .and_then(|args| args.get(name))
+                                .and_then(|value| value.as_u64())
+                                .map(|n| usize::try_from(n).unwrap_or(usize::MAX))
+                        };
+                        let max_rows = number("max_rows")
+                            .unwrap_or(usize::MAX)
+                            .min(self.export.max_rows);
+                        let page_size = number("page_size")
+                            .unwrap_or(MAX_PAGE_SIZE)
+                            .clamp(1, MAX_PAGE_SIZE);
+                        max_rows.div_ceil(page_size).max(1)
+                    }
+                    _ => 1,
+                };
+                match rate_limiter.acquire_weighted(&api_key, tool_class, weight) {
+                    Ok(permit) => Some(permit),
+                    Err(throttled) => {
+                        tracing::warn!(
+                            target: "mcp_rate_limit",
+                            tool_name = %request.name,
+                            tool_class = tool_class.as_str(),
+                            reason = throttled.reason.as_str(),
+                            "Tool call throttled"
+                        );
+                        METRICS.record_rate_limited(
+                            &request.name,
+                            tool_class.as_str(),
+                            throttled.reason.as_str(),
+                            &api_key,
+                        );
+                        let retry_after_seconds = throttled.retry_after.as_secs().max(1);
+                        return Err(McpError::new(
+                            ErrorCode(RATE_LIMITED_ERROR_CODE),
+                            throttled.to_string(),
+                            Some(json!({
+                                "retry_after_seconds": retry_after_seconds,
+                                "tool_class": tool_class.as_str(),
+                                "reason": throttled.reason.as_str(),
+                            })),
+                        ));
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        let context = ToolCallContext::new(self, request, context);
+        Self::tool_box().call(context).await
+    }
 }
 
-#[tool(tool_box)]
 impl ServerHandler for SubgraphServer {
     fn get_info(&self) -> ServerInfo {
         ServerInfo {
@@ -635,6 +1512,78 @@ impl ServerHandler for SubgraphServer {
         }
     }
 
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        Ok(ListToolsResult {
+            tools: Self::tool_box()
+                .list()
+                .into_iter()
+                .map(|tool| self.annotate_tool(tool))
+                .collect(),
+            next_cursor: None,
+        })
+    }
+
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        // Ties every log line, span and gateway request of this call together
+        let request_id = uuid::Uuid::new_v4().to_string();
+        let span = tracing::info_span!(
+            "mcp.request",
+            otel.name = %format!("tools/call {}", request.name),
+            otel.kind = "server",
+            mcp.method = "tools/call",
+            rpc.jsonrpc.request_id = %context.id,
+            request_id = %request_id,
+            tool.name = %request.name,
+        );
+
+        let tool_name = request.name.to_string();
+        let audit = self
+            .audit_logger
+            .clone()
+            .filter(|_| is_audited_tool(&tool_name))
+            .map(|audit_logger| {
+                (
+                    audit_logger,
+                    request.arguments.clone(),
+                    self.get_api_key(&context.extensions).ok(),
+                )
+            });
+
+        let call = with_request_id(request_id.clone(), self.call_tool_inner(request, context))
+            .instrument(span);
+        let result = match audit {
+            Some((audit_logger, arguments, api_key)) => {
+                let started = Instant::now();
+                let (result, details) = collect_audit_details(call).await;
+                audit_logger.record(AuditedCall {
+                    request_id: &request_id,
+                    session_id: self.session_usage.session_id(),
+                    api_key: api_key.as_deref(),
+                    tool: &tool_name,
+                    arguments: arguments.as_ref(),
+                    details,
+                    response_bytes: result.as_ref().map_or(0, Self::response_bytes),
+                    error: result.as_ref().err().map(|e| e.message.to_string()),
+                    latency: started.elapsed(),
+                });
+                result
+            }
+            None => call.await,
+        };
+        if let Ok(result) = &result {
+            METRICS.record_tool_response_size(&tool_name, Self::response_bytes(result));
+        }
+        Self::attach_request_id(result, &request_id)
+    }
+
     async fn list_resources(
         &self,
         _request: Option<PaginatedRequestParam>,
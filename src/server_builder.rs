@@ -0,0 +1,275 @@
+// SPDX-License-Identifier: Apache-2.0
+use crate::audit::{AuditLogger, AUDIT_LOGGER};
+use crate::backend::{GatewayBackend, ReqwestBackend};
+use crate::batch::BatchLimits;
+use crate::constants::{DEFAULT_GATEWAY_ID, GATEWAY_REGISTRY, GRAPH_NETWORK_SUBGRAPH_ARBITRUM};
+use crate::export::ExportSettings;
+use crate::freshness::FreshnessSettings;
+use crate::metrics::METRICS;
+use crate::policy::{PolicyStore, POLICY_STORE};
+use crate::query_analysis::QueryLimits;
+use crate::rate_limit::RateLimiter;
+use crate::recording::{RecordingBackend, ReplayBackend};
+use crate::schema_cache::{SchemaCache, SCHEMA_CACHE};
+use crate::server::SubgraphServer;
+use crate::usage::{BudgetLimits, SessionUsage};
+use prometheus_client::registry::Registry;
+use reqwest::Client;
+use std::{collections::HashMap, env, path::PathBuf, sync::Arc, time::Duration};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Configures a `SubgraphServer` explicitly, for applications embedding it as a
+/// library. `new()` starts from built-in defaults and reads nothing from the
+/// environment; `from_env()` starts from the same settings the binary uses.
+pub struct SubgraphServerBuilder {
+    http_client: Option<Client>,
+    timeout: Duration,
+    backend: Option<Arc<dyn GatewayBackend>>,
+    record_file: Option<PathBuf>,
+    network_subgraph: String,
+    gateways: HashMap<String, String>,
+    default_gateway: String,
+    api_key: Option<String>,
+    budget_limits: BudgetLimits,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    policy: Option<Arc<PolicyStore>>,
+    query_limits: QueryLimits,
+    freshness: FreshnessSettings,
+    batch_limits: BatchLimits,
+    export: ExportSettings,
+    audit_logger: Option<Arc<AuditLogger>>,
+    schema_cache: Option<Arc<SchemaCache>>,
+}
+
+impl Default for SubgraphServerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SubgraphServerBuilder {
+    /// The built-in gateway registry and network subgraph, a 120 second timeout,
+    /// default query, batch and freshness settings, no API key fallback and no policy, budget, rate
+    /// limit, audit log or schema cache.
+    pub fn new() -> Self {
+        Self {
+            http_client: None,
+            timeout: DEFAULT_TIMEOUT,
+            backend: None,
+            record_file: None,
+            network_subgraph: GRAPH_NETWORK_SUBGRAPH_ARBITRUM.to_string(),
+            gateways: GATEWAY_REGISTRY
+                .iter()
+                .map(|(id, url)| (id.to_string(), url.to_string()))
+                .collect(),
+            default_gateway: DEFAULT_GATEWAY_ID.to_string(),
+            api_key: None,
+            budget_limits: BudgetLimits::default(),
+            rate_limiter: None,
+            policy: None,
+            query_limits: QueryLimits::default(),
+            freshness: FreshnessSettings::default(),
+            batch_limits: BatchLimits::default(),
+            export: ExportSettings::default(),
+            audit_logger: None,
+            schema_cache: None,
+        }
+    }
+
+    /// Settings read from `SUBGRAPH_REQUEST_TIMEOUT_SECONDS`, `GRAPH_NETWORK_SUBGRAPH`,
+    /// `GATEWAY_API_KEY`, `GATEWAY_RECORD_FILE`, `GATEWAY_REPLAY_FILE`, the session
+    /// budget, query limit, batch, export, freshness and policy variables, and the shared audit log and schema cache.
+    pub fn from_env() -> Self {
+        let mut builder = Self::new();
+        if let Some(timeout_seconds) = env::var("SUBGRAPH_REQUEST_TIMEOUT_SECONDS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+        {
+            builder.timeout = Duration::from_secs(timeout_seconds);
+        }
+        if let Ok(network_subgraph) = env::var("GRAPH_NETWORK_SUBGRAPH") {
+            builder.network_subgraph = network_subgraph;
+        }
+        builder.api_key = env::var("GATEWAY_API_KEY").ok();
+        builder.record_file = env::var("GATEWAY_RECORD_FILE")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .map(PathBuf::from);
+        if let Some(path) = env::var("GATEWAY_REPLAY_FILE")
+            .ok()
+            .filter(|s| !s.is_empty())
+        {
+            let replay = ReplayBackend::from_file(&path).unwrap_or_else(|e| {
+                // Falling back to the live gateway would bill queries a replay run expects
+                // to be free, so every request fails instead.
+                tracing::error!(target: "mcp_recording", path = %path, error = %e, "Failed to load gateway fixtures, no request will be answered");
+                ReplayBackend::default()
+            });
+            let ignored = env::var("GATEWAY_REPLAY_IGNORE_VARIABLES").unwrap_or_default();
+            let replay = replay.ignoring_variables(
+                ignored
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty()),
+            );
+            builder.backend = Some(Arc::new(replay));
+        }
+        builder.budget_limits = BudgetLimits::from_env();
+        builder.policy = POLICY_STORE.clone();
+        builder.query_limits = QueryLimits::from_env();
+        builder.freshness = FreshnessSettings::from_env();
+        builder.batch_limits = BatchLimits::from_env();
+        builder.export = ExportSettings::from_env();
+        builder.audit_logger = AUDIT_LOGGER.clone();
+        builder.schema_cache = SCHEMA_CACHE.clone();
+        builder
+    }
+
+    /// HTTP client for the default backend. Its own timeout applies instead of `timeout`.
+    pub fn http_client(mut self, http_client: Client) -> Self {
+        self.http_client = Some(http_client);
+        self
+    }
+
+    /// Timeout of gateway requests made by the default backend.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sends gateway requests through `backend`. The HTTP client, timeout and network
+    /// subgraph settings only apply to the default backend and are then ignored.
+    pub fn backend(mut self, backend: Arc<dyn GatewayBackend>) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// Appends every gateway exchange to the NDJSON fixture file at `path`, with the
+    /// API key scrubbed, for later use with `ReplayBackend`.
+    pub fn record_gateway_traffic(mut self, path: impl Into<PathBuf>) -> Self {
+        self.record_file = Some(path.into());
+        self
+    }
+
+    /// IPFS hash of the network subgraph deployment queried for subgraph metadata.
+    pub fn network_subgraph(mut self, network_subgraph: impl Into<String>) -> Self {
+        self.network_subgraph = network_subgraph.into();
+        self
+    }
+
+    /// Adds or replaces a gateway clients can select with the `x-gateway-id` header.
+    pub fn gateway(mut self, id: impl Into<String>, url: impl Into<String>) -> Self {
+        self.gateways.insert(id.into(), url.into());
+        self
+    }
+
+    /// Replaces the whole gateway registry.
+    pub fn gateways(mut self, gateways: HashMap<String, String>) -> Self {
+        self.gateways = gateways;
+        self
+    }
+
+    /// ID of the gateway used when a request does not pick one.
+    pub fn default_gateway(mut self, id: impl Into<String>) -> Self {
+        self.default_gateway = id.into();
+        self
+    }
+
+    /// API key used when a request carries no Bearer token.
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Billable query limits of each session created by this builder.
+    pub fn session_budget(mut self, budget_limits: BudgetLimits) -> Self {
+        self.budget_limits = budget_limits;
+        self
+    }
+
+    pub fn rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    pub fn deployment_policy(mut self, policy: Arc<PolicyStore>) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    pub fn query_limits(mut self, query_limits: QueryLimits) -> Self {
+        self.query_limits = query_limits;
+        self
+    }
+
+    /// Whether query results report deployment freshness by default, and the
+    /// staleness threshold of its warning.
+    pub fn freshness(mut self, freshness: FreshnessSettings) -> Self {
+        self.freshness = freshness;
+        self
+    }
+
+    /// Size and concurrency limits of `execute_queries_batch` calls.
+    pub fn batch_limits(mut self, batch_limits: BatchLimits) -> Self {
+        self.batch_limits = batch_limits;
+        self
+    }
+
+    /// Export directory and row limit of `export_query`. Exports are refused
+    /// without a directory.
+    pub fn export(mut self, export: ExportSettings) -> Self {
+        self.export = export;
+        self
+    }
+
+    pub fn audit_logger(mut self, audit_logger: Arc<AuditLogger>) -> Self {
+        self.audit_logger = Some(audit_logger);
+        self
+    }
+
+    /// Caches schema tool results in `schema_cache`, which can be shared between servers.
+    pub fn schema_cache(mut self, schema_cache: Arc<SchemaCache>) -> Self {
+        self.schema_cache = Some(schema_cache);
+        self
+    }
+
+    /// Registers the server's metrics in the embedding application's registry.
+    /// Metrics are process-wide, so registering them once is enough.
+    pub fn metrics_registry(self, registry: &mut Registry) -> Self {
+        METRICS.register(registry);
+        self
+    }
+
+    pub fn build(self) -> SubgraphServer {
+        let backend = self.backend.unwrap_or_else(|| {
+            let http_client = self.http_client.unwrap_or_else(|| {
+                Client::builder()
+                    .timeout(self.timeout)
+                    .build()
+                    .expect("Failed to build HTTP client")
+            });
+            Arc::new(ReqwestBackend::new(http_client).with_network_subgraph(self.network_subgraph))
+        });
+        let backend: Arc<dyn GatewayBackend> = match self.record_file {
+            Some(path) => Arc::new(RecordingBackend::new(backend, path)),
+            None => backend,
+        };
+
+        SubgraphServer {
+            backend,
+            gateways: Arc::new(self.gateways),
+            default_gateway: self.default_gateway,
+            api_key: self.api_key,
+            rate_limiter: self.rate_limiter,
+            session_usage: Arc::new(SessionUsage::new(self.budget_limits)),
+            policy: self.policy,
+            query_limits: self.query_limits,
+            freshness: self.freshness,
+            batch_limits: self.batch_limits,
+            export: self.export,
+            audit_logger: self.audit_logger,
+            schema_cache: self.schema_cache,
+        }
+    }
+}
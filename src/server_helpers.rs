@@ -1,18 +1,39 @@
 // SPDX-License-Identifier: Apache-2.0
+use crate::audit::{collect_audit_details, note_deployment, note_fee, AuditedCall};
+use crate::batch::{batch_item_result, BatchItemError};
 use crate::constants::{
-    DEFAULT_GATEWAY_ID, GATEWAY_QOS_ORACLE, GATEWAY_REGISTRY, GRAPH_NETWORK_SUBGRAPH_ARBITRUM,
+    POLICY_VIOLATION_ERROR_CODE, TOOL_COST_BILLABLE, TOOL_COST_FREE, TOOL_COST_HINT_KEY,
 };
+use crate::cross_chain::{check_compatibility, merge_collections, required_fields, schema_hash};
+use crate::decode::{DecodeSpec, ResultTypes};
 use crate::error::SubgraphError;
+use crate::export::{
+    resolve_export_path, ExportFormat, ExportWriter, PagedQuery, MAX_PAGE_SIZE, PREVIEW_ROWS,
+};
+use crate::freshness::{add_meta_selection, take_freshness};
+use crate::introspection::INTROSPECTION_QUERY;
 use crate::metrics::METRICS;
+use crate::network_subgraph::NetworkSubgraphClient;
+use crate::policy::{DeploymentRef, DeploymentTarget};
+use crate::query_analysis::{analyze_query, analyze_query_with_schema};
+use crate::schema_summary::summarize_schema;
 use crate::server::SubgraphServer;
-use crate::types::*;
+use crate::telemetry::current_request_id;
+use crate::types::{BatchQuery, ExportQueryRequest, GraphQLRequest, QueryAcrossChainsRequest};
+use crate::usage::{fee_header_name, KEY_USAGE};
 use axum::http::request::Parts;
+use futures::{stream, StreamExt};
 use http;
-use rmcp::model::{AnnotateAble, Extensions, RawResource, Resource};
+use rmcp::{
+    model::{AnnotateAble, ErrorCode, Extensions, RawResource, Resource, Tool, ToolAnnotations},
+    Error as McpError,
+};
 use serde_json::json;
 use std::{
-    env,
-    time::{SystemTime, UNIX_EPOCH},
+    collections::HashSet,
+    fs,
+    sync::Arc,
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
 
 impl SubgraphServer {
@@ -31,7 +52,19 @@ impl SubgraphServer {
             }
         }
 
-        env::var("GATEWAY_API_KEY").map_err(|_| SubgraphError::ApiKeyNotSet)
+        self.api_key.clone().ok_or(SubgraphError::ApiKeyNotSet)
+    }
+
+    /// The ID `gateway_url` is registered under, used as the `gateway` metrics
+    /// label, or `custom` for a URL the server doesn't know.
+    pub(crate) fn gateway_label(&self, gateway_url: &str) -> String {
+        self.gateways
+            .iter()
+            .filter(|(_, url)| *url == gateway_url)
+            .map(|(id, _)| id.as_str())
+            .min()
+            .unwrap_or("custom")
+            .to_string()
     }
 
     pub(crate) fn get_gateway_url(&self, extensions: &Extensions) -> Result<String, SubgraphError> {
@@ -41,12 +74,14 @@ impl SubgraphServer {
                 if let Ok(gateway_id) = gateway_id_header.to_str() {
                     if !gateway_id.is_empty() {
                         // Look up the gateway URL by ID
-                        if let Some(gateway_url) = GATEWAY_REGISTRY.get(gateway_id) {
+                        if let Some(gateway_url) = self.gateways.get(gateway_id) {
                             tracing::info!(target: "mcp_gateway", gateway_id = %gateway_id, gateway_url = %gateway_url, "Using gateway from 'x-gateway-id' header");
                             return Ok(gateway_url.to_string());
                         } else {
                             // Invalid gateway ID - return error with available options
-                            let valid_ids: Vec<&str> = GATEWAY_REGISTRY.keys().copied().collect();
+                            let mut valid_ids: Vec<&str> =
+                                self.gateways.keys().map(String::as_str).collect();
+                            valid_ids.sort_unstable();
                             let error_msg = format!(
                                 "Invalid gateway ID '{}' from header. Valid gateway IDs are: {}",
                                 gateway_id,
@@ -60,8 +95,8 @@ impl SubgraphServer {
             }
         }
         // Use default gateway
-        if let Some(gateway_url) = GATEWAY_REGISTRY.get(DEFAULT_GATEWAY_ID) {
-            tracing::info!(target: "mcp_gateway", gateway_id = %DEFAULT_GATEWAY_ID, gateway_url = %gateway_url, "Using default gateway");
+        if let Some(gateway_url) = self.gateways.get(&self.default_gateway) {
+            tracing::info!(target: "mcp_gateway", gateway_id = %self.default_gateway, gateway_url = %gateway_url, "Using default gateway");
             Ok(gateway_url.to_string())
         } else {
             Err(SubgraphError::InvalidGatewayId(
@@ -70,22 +105,28 @@ impl SubgraphServer {
         }
     }
 
-    pub(crate) fn get_graph_network_subgraph(&self) -> String {
-        env::var("GRAPH_NETWORK_SUBGRAPH")
-            .unwrap_or_else(|_| GRAPH_NETWORK_SUBGRAPH_ARBITRUM.to_string())
+    /// Returns the schema cached under `key`, fetching it on a miss.
+    async fn cached_schema<F, Fut>(&self, key: String, fetch: F) -> Result<String, SubgraphError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<String, SubgraphError>>,
+    {
+        match &self.schema_cache {
+            Some(cache) => cache.get_or_fetch(&key, fetch).await,
+            None => fetch().await,
+        }
     }
 
-    pub(crate) fn get_network_subgraph_query_url(
+    /// Runs a minimal query against the network subgraph to check that the
+    /// gateway is answering.
+    pub async fn probe_gateway(
         &self,
         api_key: &str,
         gateway_url: &str,
-    ) -> String {
-        format!(
-            "{}/{}/deployments/id/{}",
-            gateway_url,
-            api_key,
-            self.get_graph_network_subgraph()
-        )
+    ) -> Result<(), SubgraphError> {
+        NetworkSubgraphClient::new(self, api_key, gateway_url)
+            .probe()
+            .await
     }
 
     pub(crate) async fn get_schema_by_deployment_id_internal(
@@ -94,192 +135,583 @@ impl SubgraphServer {
         gateway_url: &str,
         deployment_id: &str,
     ) -> Result<String, SubgraphError> {
-        METRICS
-            .observe_gateway_request("network_subgraph_query", api_key, || async {
-                let url = self.get_network_subgraph_query_url(api_key, gateway_url);
-
-                let query = r#"
-            query SubgraphDeploymentSchema($id: String!) {
-                subgraphDeployment(id: $id) {
-                    manifest {
-                        schema {
-                            schema
-                        }
-                    }
-                }
-            }
-            "#;
+        self.cached_schema(format!("deployment_id:{}", deployment_id), || async move {
+            NetworkSubgraphClient::new(self, api_key, gateway_url)
+                .deployment_schema(deployment_id)
+                .await
+        })
+        .await
+    }
 
-                let variables = serde_json::json!({
-                    "id": deployment_id
-                });
+    pub(crate) async fn get_schema_by_subgraph_id_internal(
+        &self,
+        api_key: &str,
+        gateway_url: &str,
+        subgraph_id: &str,
+    ) -> Result<String, SubgraphError> {
+        self.cached_schema(format!("subgraph_id:{}", subgraph_id), || async move {
+            NetworkSubgraphClient::new(self, api_key, gateway_url)
+                .subgraph_schema(subgraph_id)
+                .await
+        })
+        .await
+    }
 
-                let request_body = serde_json::json!({
-                    "query": query,
-                    "variables": variables
-                });
+    pub(crate) async fn get_schema_by_ipfs_hash_internal(
+        &self,
+        api_key: &str,
+        gateway_url: &str,
+        ipfs_hash: &str,
+    ) -> Result<String, SubgraphError> {
+        self.cached_schema(format!("ipfs_hash:{}", ipfs_hash), || async move {
+            NetworkSubgraphClient::new(self, api_key, gateway_url)
+                .schema_by_ipfs_hash(ipfs_hash)
+                .await
+        })
+        .await
+    }
 
+    /// Fetches the schema of `deployment` with whichever helper matches its kind.
+    pub(crate) async fn get_schema_internal(
+        &self,
+        api_key: &str,
+        gateway_url: &str,
+        deployment: DeploymentRef<'_>,
+    ) -> Result<String, SubgraphError> {
+        match deployment {
+            DeploymentRef::DeploymentId(id) => {
+                self.get_schema_by_deployment_id_internal(api_key, gateway_url, id)
+                    .await
+            }
+            DeploymentRef::IpfsHash(hash) => {
+                self.get_schema_by_ipfs_hash_internal(api_key, gateway_url, hash)
+                    .await
+            }
+            DeploymentRef::SubgraphId(id) => {
+                self.get_schema_by_subgraph_id_internal(api_key, gateway_url, id)
+                    .await
+            }
+        }
+    }
+
+    /// Runs the introspection query against `deployment` and returns the response
+    /// `data` as JSON text. Cached like the schemas; a cache miss is a billable query.
+    /// The query is the server's own, so it is sent without checking the query limits
+    /// its nesting would exceed.
+    pub(crate) async fn get_introspection_internal(
+        &self,
+        api_key: &str,
+        gateway_url: &str,
+        deployment: DeploymentRef<'_>,
+    ) -> Result<String, SubgraphError> {
+        self.cached_schema(
+            format!("introspection:{}", deployment.as_str()),
+            || async move {
                 let response = self
-                    .http_client
-                    .post(&url)
-                    .json(&request_body)
-                    .send()
-                    .await?
-                    .json::<GraphQLResponse>()
+                    .execute_query_on_endpoint(
+                        api_key,
+                        gateway_url,
+                        deployment.endpoint_type(),
+                        deployment.as_str(),
+                        INTROSPECTION_QUERY,
+                        None,
+                    )
                     .await?;
+                Ok(response["data"].to_string())
+            },
+        )
+        .await
+    }
 
-                if let Some(errors) = response.errors {
-                    if !errors.is_empty() {
-                        return Err(SubgraphError::GraphQlError(errors[0].message.clone()));
-                    }
-                }
-
-                let data = response.data.ok_or_else(|| {
-                    SubgraphError::GraphQlError("No data returned from the GraphQL API".to_string())
-                })?;
+    /// Runs a query of the `execute_query_*` tools. Unless `freshness`, or the server
+    /// default when it is `None`, turns it off, the deployment's `_meta` is selected
+    /// along with the query and reported under `freshness` instead of `data`.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn execute_query_with_freshness(
+        &self,
+        api_key: &str,
+        gateway_url: &str,
+        endpoint_type: &str,
+        id: &str,
+        query: &str,
+        variables: Option<serde_json::Value>,
+        freshness: Option<bool>,
+    ) -> Result<serde_json::Value, SubgraphError> {
+        let with_meta = if freshness.unwrap_or(self.freshness.enabled) {
+            add_meta_selection(query)
+        } else {
+            None
+        };
+        // The limits apply to the user's query, not to the `_meta` selection added to it
+        let deployment = match endpoint_type {
+            "subgraphs/id" => DeploymentRef::SubgraphId(id),
+            _ => DeploymentRef::parse(id),
+        };
+        self.check_query_limits(api_key, gateway_url, deployment, query, variables.as_ref())
+            .await?;
+        let mut response = self
+            .execute_query_on_endpoint(
+                api_key,
+                gateway_url,
+                endpoint_type,
+                id,
+                with_meta.as_deref().unwrap_or(query),
+                variables,
+            )
+            .await?;
+        if with_meta.is_some() {
+            if let Some(freshness) = take_freshness(
+                &mut response,
+                self.freshness.max_staleness,
+                SystemTime::now(),
+            ) {
+                response["freshness"] = json!(freshness);
+            }
+        }
+        Ok(response)
+    }
 
-                let schema = data
-                    .get("subgraphDeployment")
-                    .and_then(|dep| dep.get("manifest"))
-                    .and_then(|manifest| manifest.get("schema"))
-                    .and_then(|schema| schema.get("schema"))
-                    .and_then(|schema| schema.as_str())
-                    .ok_or_else(|| {
-                        SubgraphError::GraphQlError("Schema not found in the response".to_string())
-                    })?;
-
-                Ok(schema.to_string())
+    /// Runs the queries of a batch, at most `concurrency` at a time. Each query is
+    /// checked against the deployment policy, metered and audited on its own, and
+    /// a failing query only fails its own entry of the result.
+    pub(crate) async fn execute_batch_internal(
+        &self,
+        tool_name: &str,
+        api_key: &str,
+        gateway_url: &str,
+        queries: Vec<(BatchQuery, Option<DeploymentTarget>)>,
+        concurrency: usize,
+        freshness: Option<bool>,
+    ) -> serde_json::Value {
+        let results: Vec<serde_json::Value> = stream::iter(queries.into_iter().enumerate())
+            .map(|(index, (item, target))| async move {
+                let deployment = DeploymentRef::parse(&item.deployment);
+                let run =
+                    METRICS.observe_batch_item(index, deployment.as_str(), api_key, || async {
+                        match target {
+                            Some(target) => {
+                                self.check_deployment_target(tool_name, api_key, target)
+                            }
+                            None => {
+                                self.enforce_deployment_policy(
+                                    tool_name,
+                                    api_key,
+                                    gateway_url,
+                                    deployment,
+                                )
+                                .await
+                            }
+                        }?;
+                        let response = self
+                            .execute_query_with_freshness(
+                                api_key,
+                                gateway_url,
+                                deployment.endpoint_type(),
+                                deployment.as_str(),
+                                &item.query,
+                                item.variables.clone(),
+                                freshness,
+                            )
+                            .await?;
+                        Ok::<_, BatchItemError>(response)
+                    });
+
+                let Some(audit_logger) = &self.audit_logger else {
+                    return batch_item_result(index, &item.deployment, run.await);
+                };
+                let started = Instant::now();
+                let (outcome, details) = collect_audit_details(run).await;
+                let error = outcome.as_ref().err().map(|e| e.message.clone());
+                let entry = batch_item_result(index, &item.deployment, outcome);
+                let mut arguments = serde_json::Map::new();
+                arguments.insert("deployment".to_string(), json!(item.deployment));
+                arguments.insert("query".to_string(), json!(item.query));
+                if let Some(variables) = item.variables {
+                    arguments.insert("variables".to_string(), variables);
+                }
+                audit_logger.record(AuditedCall {
+                    request_id: &current_request_id().unwrap_or_default(),
+                    session_id: self.session_usage.session_id(),
+                    api_key: Some(api_key),
+                    tool: tool_name,
+                    arguments: Some(&arguments),
+                    details,
+                    response_bytes: entry.to_string().len(),
+                    error,
+                    latency: started.elapsed(),
+                });
+                entry
             })
-            .await
+            .buffered(concurrency.max(1))
+            .collect()
+            .await;
+
+        let failed = results.iter().filter(|r| r.get("error").is_some()).count();
+        json!({
+            "results": results,
+            "succeeded": results.len() - failed,
+            "failed": failed,
+        })
     }
 
-    pub(crate) async fn get_schema_by_subgraph_id_internal(
+    /// Runs one query on the current deployments of the requested subgraphs whose
+    /// schema has every entity and field the query selects. Subgraphs left out are
+    /// listed under `skipped` with the reason.
+    pub(crate) async fn query_across_chains_internal(
         &self,
         api_key: &str,
         gateway_url: &str,
-        subgraph_id: &str,
-    ) -> Result<String, SubgraphError> {
-        METRICS
-            .observe_gateway_request("network_subgraph_query", api_key, || async {
-                let url = self.get_network_subgraph_query_url(api_key, gateway_url);
-
-                let query = r#"
-            query SubgraphSchema($id: String!) {
-              subgraph(id: $id) {
-                currentVersion {
-                  subgraphDeployment {
-                    manifest {
-                      schema {
-                        schema
-                      }
+        request: QueryAcrossChainsRequest,
+    ) -> Result<serde_json::Value, SubgraphError> {
+        let required = required_fields(&request.query)?;
+        let max_queries = self.batch_limits.max_queries;
+        let client = NetworkSubgraphClient::new(self, api_key, gateway_url);
+        let mut skipped = Vec::new();
+
+        let candidates = match (&request.subgraph_ids, &request.keyword) {
+            (Some(subgraph_ids), _) => {
+                let mut found = client.subgraphs_with_schemas(subgraph_ids).await?;
+                // Keep the order the caller listed the subgraphs in
+                found.sort_by_key(|subgraph| {
+                    subgraph_ids
+                        .iter()
+                        .position(|id| subgraph.id.as_ref() == Some(id))
+                });
+                for id in subgraph_ids {
+                    if !found
+                        .iter()
+                        .any(|subgraph| subgraph.id.as_ref() == Some(id))
+                    {
+                        skipped.push(json!({ "subgraph_id": id, "reason": "Subgraph not found" }));
                     }
-                  }
                 }
-              }
+                found
             }
-            "#;
-
-                let variables = serde_json::json!({ "id": subgraph_id });
-                let request_body = serde_json::json!({ "query": query, "variables": variables });
-
-                let response = self
-                    .http_client
-                    .post(&url)
-                    .json(&request_body)
-                    .send()
+            (None, Some(keyword)) => {
+                client
+                    .search_subgraphs_with_schemas(keyword, max_queries)
                     .await?
-                    .json::<GraphQLResponse>()
-                    .await?;
+            }
+            (None, None) => {
+                return Err(SubgraphError::InternalProcessingError(
+                    "Either keyword or subgraph_ids is required".to_string(),
+                ))
+            }
+        };
+
+        let mut targets = Vec::new();
+        let mut seen = HashSet::new();
+        for subgraph in candidates {
+            let mut target = json!({
+                "subgraph_id": subgraph.id,
+                "display_name": subgraph.metadata.and_then(|m| m.display_name),
+            });
+            let deployment = subgraph
+                .current_version
+                .map(|version| version.subgraph_deployment)
+                .unwrap_or_default();
+            let mut owners = deployment.subgraph_ids();
+            owners.extend(subgraph.id.clone().filter(|id| !owners.contains(id)));
+            let manifest = deployment.manifest.unwrap_or_default();
+            target["network"] = json!(manifest.network);
+
+            let (Some(ipfs_hash), Some(schema)) = (deployment.ipfs_hash, manifest.schema) else {
+                target["reason"] = json!("No current deployment with a schema");
+                skipped.push(target);
+                continue;
+            };
+            target["deployment"] = json!(ipfs_hash);
+            target["schema_hash"] = json!(schema_hash(&schema.schema));
+
+            let compatible = summarize_schema(&schema.schema)
+                .map_err(|e| e.to_string())
+                .and_then(|summary| check_compatibility(&summary, &required));
+            let reason = match compatible {
+                Err(reason) => Some(reason),
+                Ok(()) if !seen.insert(ipfs_hash.clone()) => {
+                    Some("Same deployment as another subgraph of the list".to_string())
+                }
+                Ok(()) if targets.len() >= max_queries => Some(format!(
+                    "More than {} compatible deployments; query this one separately",
+                    max_queries
+                )),
+                Ok(()) => None,
+            };
+            match reason {
+                Some(reason) => {
+                    target["reason"] = json!(reason);
+                    skipped.push(target);
+                }
+                None => {
+                    // Everything the policy checks is known already, so the
+                    // deployment is not looked up again
+                    let policy_target = DeploymentTarget {
+                        ipfs_hash: Some(ipfs_hash.clone()),
+                        subgraph_id: subgraph.id.clone(),
+                        subgraph_ids: Some(owners),
+                        network: manifest.network.clone(),
+                    };
+                    targets.push((ipfs_hash, target, policy_target));
+                }
+            }
+        }
 
-                if let Some(errors) = response.errors {
-                    if !errors.is_empty() {
-                        return Err(SubgraphError::GraphQlError(errors[0].message.clone()));
+        let queries = targets
+            .iter()
+            .map(|(ipfs_hash, _, policy_target)| {
+                let query = BatchQuery {
+                    deployment: ipfs_hash.clone(),
+                    query: request.query.clone(),
+                    variables: request.variables.clone(),
+                };
+                (query, Some(policy_target.clone()))
+            })
+            .collect();
+        let batch = self
+            .execute_batch_internal(
+                "query_across_chains",
+                api_key,
+                gateway_url,
+                queries,
+                self.batch_limits.max_concurrency,
+                request.freshness,
+            )
+            .await;
+
+        let results = batch["results"].as_array().cloned().unwrap_or_default();
+        let deployments: Vec<serde_json::Value> = targets
+            .into_iter()
+            .zip(results)
+            .map(|((_, mut target, _), result)| {
+                for field in ["data", "freshness", "error"] {
+                    if let Some(value) = result.get(field) {
+                        target[field] = value.clone();
                     }
                 }
-
-                let data = response.data.ok_or_else(|| {
-                    SubgraphError::GraphQlError("No data returned from the GraphQL API".to_string())
-                })?;
-
-                let schema = data
-                    .get("subgraph")
-                    .and_then(|sg| sg.get("currentVersion"))
-                    .and_then(|cv| cv.get("subgraphDeployment"))
-                    .and_then(|dep| dep.get("manifest"))
-                    .and_then(|manifest| manifest.get("schema"))
-                    .and_then(|schema| schema.get("schema"))
-                    .and_then(|schema| schema.as_str())
-                    .ok_or_else(|| {
-                        SubgraphError::GraphQlError(
-                            "Schema not found for current version in the response".to_string(),
-                        )
-                    })?;
-
-                Ok(schema.to_string())
+                target
             })
-            .await
+            .collect();
+
+        let mut response = json!({
+            "deployments": deployments,
+            "skipped": skipped,
+            "succeeded": batch["succeeded"],
+            "failed": batch["failed"],
+        });
+        if request.merge.unwrap_or(false) {
+            let chains: Vec<(String, &serde_json::Value)> = deployments
+                .iter()
+                .filter_map(|d| {
+                    let chain = d["network"].as_str().or(d["subgraph_id"].as_str())?;
+                    Some((chain.to_string(), d.get("data")?))
+                })
+                .collect();
+            response["merged"] = json!(merge_collections(
+                &chains,
+                request.order_by.as_deref(),
+                request.order_direction.unwrap_or_default(),
+            ));
+        }
+        Ok(response)
     }
 
-    pub(crate) async fn get_schema_by_ipfs_hash_internal(
+    /// Pages through the collection `request.query` selects and streams the rows
+    /// to a new file in the export directory. The file is removed again when a
+    /// page fails.
+    pub(crate) async fn export_query_internal(
         &self,
         api_key: &str,
         gateway_url: &str,
-        ipfs_hash: &str,
-    ) -> Result<String, SubgraphError> {
-        METRICS
-            .observe_gateway_request("network_subgraph_query", api_key, || async {
-                let url = self.get_network_subgraph_query_url(api_key, gateway_url);
-
-                let query = r#"
-            query DeploymentSchemaByIpfsHash($hash: String!) {
-              subgraphDeployments(where: {ipfsHash: $hash}, first: 1) {
-                manifest {
-                  schema {
-                    schema
-                  }
-                }
-              }
+        request: ExportQueryRequest,
+    ) -> Result<serde_json::Value, SubgraphError> {
+        let root = self.export.dir.as_ref().ok_or_else(|| {
+            SubgraphError::ExportRefused(
+                "Exports are disabled; set EXPORT_DIR to the directory files may be written to"
+                    .to_string(),
+            )
+        })?;
+        let mut paged = PagedQuery::parse(&request.query)?;
+        let deployment = DeploymentRef::parse(&request.deployment);
+
+        let columns = match request.format {
+            ExportFormat::Ndjson => Vec::new(),
+            ExportFormat::Csv | ExportFormat::Parquet => {
+                let schema = self
+                    .get_schema_internal(api_key, gateway_url, deployment)
+                    .await?;
+                paged.columns(&summarize_schema(&schema)?)?
             }
-            "#;
-
-                let variables = serde_json::json!({ "hash": ipfs_hash });
-                let request_body = serde_json::json!({ "query": query, "variables": variables });
-
-                let response = self
-                    .http_client
-                    .post(&url)
-                    .json(&request_body)
-                    .send()
-                    .await?
-                    .json::<GraphQLResponse>()
+        };
+        let file_name = request.file_name.clone().unwrap_or_else(|| {
+            format!(
+                "{}-{}.{}",
+                paged.field_name,
+                chrono::Utc::now().format("%Y%m%dT%H%M%S"),
+                request.format.extension()
+            )
+        });
+        let path = resolve_export_path(root, &file_name)?;
+        let mut writer = ExportWriter::create(&path, request.format, columns.clone())?;
+
+        let max_rows = request
+            .max_rows
+            .unwrap_or(usize::MAX)
+            .min(self.export.max_rows);
+        let page_size = request
+            .page_size
+            .unwrap_or(MAX_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+        let mut rows = 0;
+        let mut pages = 0;
+        let mut complete = false;
+        let mut preview = Vec::new();
+        let mut cursor: Option<String> = None;
+        let outcome = async {
+            while rows < max_rows {
+                let first = page_size.min(max_rows - rows);
+                let query = paged.page(first, cursor.as_deref());
+                self.check_query_limits(
+                    api_key,
+                    gateway_url,
+                    deployment,
+                    &query,
+                    request.variables.as_ref(),
+                )
+                .await?;
+                // The first page also reads the latest block, which the others are pinned to
+                let with_meta = if paged.needs_block() {
+                    add_meta_selection(&query)
+                } else {
+                    None
+                };
+                let mut response = self
+                    .execute_query_on_endpoint(
+                        api_key,
+                        gateway_url,
+                        deployment.endpoint_type(),
+                        deployment.as_str(),
+                        with_meta.as_deref().unwrap_or(&query),
+                        request.variables.clone(),
+                    )
                     .await?;
-
-                if let Some(errors) = response.errors {
-                    if !errors.is_empty() {
-                        return Err(SubgraphError::GraphQlError(errors[0].message.clone()));
+                if with_meta.is_some() {
+                    if let Some(number) = take_freshness(&mut response, None, SystemTime::now())
+                        .and_then(|freshness| freshness.block_number)
+                    {
+                        paged.pin_block(number);
                     }
                 }
+                let page = response["data"][&paged.response_key]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default();
+                writer.write_rows(&page)?;
+                pages += 1;
+                rows += page.len();
+                let room = PREVIEW_ROWS.saturating_sub(preview.len());
+                preview.extend(page.iter().take(room).cloned());
+
+                if page.len() < first {
+                    complete = true;
+                    break;
+                }
+                cursor = page
+                    .last()
+                    .and_then(|row| row["id"].as_str())
+                    .map(str::to_string);
+                if cursor.is_none() {
+                    return Err(SubgraphError::InternalProcessingError(
+                        "The last row of a page has no `id` to continue from".to_string(),
+                    ));
+                }
+            }
+            Ok(())
+        }
+        .await;
 
-                let data = response.data.ok_or_else(|| {
-                    SubgraphError::GraphQlError("No data returned from the GraphQL API".to_string())
-                })?;
+        let bytes = match outcome.and_then(|()| writer.finish()) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                let _ = fs::remove_file(&path);
+                return Err(e);
+            }
+        };
+        Ok(json!({
+            "path": path.display().to_string(),
+            "format": request.format,
+            "rows": rows,
+            "bytes": bytes,
+            "pages": pages,
+            "complete": complete,
+            "columns": columns,
+            "preview": preview,
+        }))
+    }
 
-                let schema = data
-                    .get("subgraphDeployments")
-                    .and_then(|deployments| deployments.get(0))
-                    .and_then(|dep| dep.get("manifest"))
-                    .and_then(|manifest| manifest.get("schema"))
-                    .and_then(|schema| schema.get("schema"))
-                    .and_then(|schema| schema.as_str())
-                    .ok_or_else(|| {
-                        SubgraphError::GraphQlError(
-                            "Schema not found for the given IPFS hash in the response".to_string(),
-                        )
-                    })?;
-
-                Ok(schema.to_string())
-            })
+    /// Decodes a query result, typing its fields by the deployment's schema,
+    /// which is fetched from the gateway unless the schema cache holds it.
+    /// Without the schema, only hinted amounts and timestamps are decoded.
+    pub(crate) async fn decode_result(
+        &self,
+        api_key: &str,
+        gateway_url: &str,
+        deployment: DeploymentRef<'_>,
+        query: &str,
+        decode: &DecodeSpec,
+        result: serde_json::Value,
+    ) -> serde_json::Value {
+        let types = match self
+            .get_schema_internal(api_key, gateway_url, deployment)
             .await
+            .and_then(|sdl| summarize_schema(&sdl))
+        {
+            Ok(schema) => ResultTypes::new(query, &schema),
+            Err(e) => {
+                tracing::debug!(error = %e, "No schema to type the decoded fields");
+                ResultTypes::default()
+            }
+        };
+        decode.apply(result, &types)
+    }
+
+    /// Checks `query` against the query limits, rejecting queries likely to time
+    /// out before they are billed. The schema-free estimate never
+    /// undercounts rows, so the deployment's schema is only fetched, usually from
+    /// the cache, to take a closer look at queries rejected for their rows.
+    async fn check_query_limits(
+        &self,
+        api_key: &str,
+        gateway_url: &str,
+        deployment: DeploymentRef<'_>,
+        query: &str,
+        variables: Option<&serde_json::Value>,
+    ) -> Result<(), SubgraphError> {
+        let analysis = analyze_query(query, variables)?;
+        let rejected = match self.query_limits.check(&analysis) {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+        let too_many_rows = self
+            .query_limits
+            .max_estimated_rows
+            .is_some_and(|max_rows| analysis.estimated_rows > max_rows);
+        if !too_many_rows {
+            return Err(rejected);
+        }
+
+        let schema = match self
+            .get_schema_internal(api_key, gateway_url, deployment)
+            .await
+            .and_then(|sdl| summarize_schema(&sdl))
+        {
+            Ok(schema) => schema,
+            Err(e) => {
+                tracing::debug!(error = %e, "No schema to refine the row estimate");
+                return Err(rejected);
+            }
+        };
+        let analysis = analyze_query_with_schema(query, variables, Some(&schema))?;
+        self.query_limits.check(&analysis)
     }
 
     pub(crate) async fn execute_query_on_endpoint(
@@ -291,44 +723,66 @@ impl SubgraphServer {
         query: &str,
         variables: Option<serde_json::Value>,
     ) -> Result<serde_json::Value, SubgraphError> {
-        METRICS
-            .observe_gateway_request(endpoint_type, api_key, || async {
-                let url = format!("{}/{}/{}/{}", gateway_url, api_key, endpoint_type, id);
-
-                let mut request_body = serde_json::json!({
-                    "query": query,
-                });
+        let reservation = self.session_usage.reserve_query()?;
+        tracing::Span::current().record("subgraph.deployment", id);
 
-                if let Some(vars) = variables {
-                    request_body["variables"] = vars;
-                }
-
-                let response_val = self
-                    .http_client
-                    .post(&url)
-                    .json(&request_body)
-                    .send()
-                    .await?
-                    .json::<serde_json::Value>()
-                    .await?;
+        METRICS
+            .observe_gateway_request(
+                endpoint_type,
+                &self.gateway_label(gateway_url),
+                api_key,
+                || async {
+                    tracing::Span::current().record("subgraph.deployment", id);
+                    let request = GraphQLRequest {
+                        query: query.to_string(),
+                        variables,
+                    };
+                    let response = self
+                        .backend
+                        .query_deployment(api_key, gateway_url, endpoint_type, id, &request)
+                        .await?;
+                    // The gateway answered, so the query counts even if it failed
+                    reservation.confirm();
+                    KEY_USAGE.record_query(api_key);
+
+                    if let Some(fee_grt) = fee_header_name()
+                        .and_then(|name| response.header(&name))
+                        .and_then(|value| value.trim().parse::<f64>().ok())
+                    {
+                        self.session_usage.record_fee(fee_grt);
+                        KEY_USAGE.record_fee(api_key, fee_grt);
+                        note_fee(fee_grt);
+                    }
 
-                if let Some(errors_val) = response_val.get("errors") {
-                    if let Some(errors_arr) = errors_val.as_array() {
-                        if !errors_arr.is_empty() {
-                            if let Some(first_error) =
-                                errors_arr[0].get("message").and_then(|m| m.as_str())
-                            {
-                                return Err(SubgraphError::GraphQlError(first_error.to_string()));
-                            } else {
-                                return Err(SubgraphError::GraphQlError(
-                                    "Received GraphQL errors without a message.".to_string(),
-                                ));
+                    let response_val =
+                        match serde_json::from_slice::<serde_json::Value>(&response.body) {
+                            Ok(value) => value,
+                            Err(_) if !response.status.is_success() => {
+                                return Err(SubgraphError::HttpStatus(response.status.as_u16()))
+                            }
+                            Err(e) => return Err(e.into()),
+                        };
+
+                    if let Some(errors_val) = response_val.get("errors") {
+                        if let Some(errors_arr) = errors_val.as_array() {
+                            if !errors_arr.is_empty() {
+                                if let Some(first_error) =
+                                    errors_arr[0].get("message").and_then(|m| m.as_str())
+                                {
+                                    return Err(SubgraphError::GraphQlError(
+                                        first_error.to_string(),
+                                    ));
+                                } else {
+                                    return Err(SubgraphError::GraphQlError(
+                                        "Received GraphQL errors without a message.".to_string(),
+                                    ));
+                                }
                             }
                         }
                     }
-                }
-                Ok(response_val)
-            })
+                    Ok(response_val)
+                },
+            )
             .await
     }
 
@@ -339,59 +793,10 @@ impl SubgraphServer {
         contract_address: &str,
         chain: &str,
     ) -> Result<serde_json::Value, SubgraphError> {
-        METRICS
-            .observe_gateway_request("network_subgraph_query", api_key, || async {
-                let url = self.get_network_subgraph_query_url(api_key, gateway_url);
-
-                let query = r#"
-            query TopSubgraphDeploymentsForContract($network: String!, $contractAddress: String!) {
-              subgraphDeployments(
-                where: {manifest_: {network: $network, manifest_contains: $contractAddress}}
-                orderBy: queryFeesAmount
-                orderDirection: desc
-                first: 3
-              ) {
-                ipfsHash
-                manifest {
-                  network
-                }
-                queryFeesAmount
-              }
-            }
-            "#;
-
-                let variables = serde_json::json!({
-                    "network": chain,
-                    "contractAddress": contract_address
-                });
-
-                let request_body = serde_json::json!({
-                    "query": query,
-                    "variables": variables
-                });
-
-                let response = self
-                    .http_client
-                    .post(&url)
-                    .json(&request_body)
-                    .send()
-                    .await?
-                    .json::<GraphQLResponse>()
-                    .await?;
-
-                if let Some(errors) = response.errors {
-                    if !errors.is_empty() {
-                        return Err(SubgraphError::GraphQlError(errors[0].message.clone()));
-                    }
-                }
-
-                let data = response.data.ok_or_else(|| {
-                    SubgraphError::GraphQlError("No data returned from the GraphQL API".to_string())
-                })?;
-
-                Ok(data)
-            })
-            .await
+        let deployments = NetworkSubgraphClient::new(self, api_key, gateway_url)
+            .top_deployments_for_contract(contract_address, chain)
+            .await?;
+        Ok(json!({ "subgraphDeployments": deployments }))
     }
 
     pub(crate) async fn search_subgraphs_by_keyword_internal(
@@ -400,77 +805,22 @@ impl SubgraphServer {
         gateway_url: &str,
         keyword: &str,
     ) -> Result<serde_json::Value, SubgraphError> {
-        METRICS
-            .observe_gateway_request("network_subgraph_query", api_key, || async {
-                let url = self.get_network_subgraph_query_url(api_key, gateway_url);
-
-                let query = r#"
-            query SearchSubgraphsByKeyword($keyword: String!) {
-              subgraphs(
-                where: {metadata_: {displayName_contains_nocase: $keyword}}
-                orderBy: currentSignalledTokens
-                orderDirection: desc
-                first: 1000
-              ) {
-                id
-                metadata {
-                  displayName
-                }
-                currentVersion {
-                  subgraphDeployment {
-                    ipfsHash
-                  }
-                }
-              }
-            }
-            "#;
-
-                let variables = serde_json::json!({
-                    "keyword": keyword
-                });
+        let subgraphs = NetworkSubgraphClient::new(self, api_key, gateway_url)
+            .search_subgraphs(keyword)
+            .await?;
 
-                let request_body = serde_json::json!({
-                    "query": query,
-                    "variables": variables
-                });
-
-                let response = self
-                    .http_client
-                    .post(&url)
-                    .json(&request_body)
-                    .send()
-                    .await?
-                    .json::<GraphQLResponse>()
-                    .await?;
-
-                if let Some(errors) = response.errors {
-                    if !errors.is_empty() {
-                        return Err(SubgraphError::GraphQlError(errors[0].message.clone()));
-                    }
-                }
-
-                let data = response.data.ok_or_else(|| {
-                    SubgraphError::GraphQlError("No data returned from the GraphQL API".to_string())
-                })?;
-
-                if let Some(subgraphs_arr) = data.get("subgraphs").and_then(|s| s.as_array()) {
-                    let total_count = subgraphs_arr.len();
-                    let limit = if total_count <= 100 {
-                        10
-                    } else {
-                        (total_count as f64).sqrt().ceil() as usize
-                    };
-                    let limited_subgraphs: Vec<serde_json::Value> =
-                        subgraphs_arr.iter().take(limit).cloned().collect();
-                    return Ok(json!({
-                        "subgraphs": limited_subgraphs,
-                        "total": total_count,
-                        "returned": limited_subgraphs.len()
-                    }));
-                }
-                Ok(data)
-            })
-            .await
+        let total_count = subgraphs.len();
+        let limit = if total_count <= 100 {
+            10
+        } else {
+            (total_count as f64).sqrt().ceil() as usize
+        };
+        let limited_subgraphs: Vec<_> = subgraphs.into_iter().take(limit).collect();
+        Ok(json!({
+            "subgraphs": limited_subgraphs,
+            "total": total_count,
+            "returned": limited_subgraphs.len()
+        }))
     }
 
     pub(crate) async fn get_deployment_30day_query_counts_internal(
@@ -479,129 +829,213 @@ impl SubgraphServer {
         gateway_url: &str,
         ipfs_hashes: &[String],
     ) -> Result<serde_json::Value, SubgraphError> {
-        METRICS
-            .observe_gateway_request("qos_oracle_query", api_key, || async {
-                let url = format!(
-                    "{}/{}/deployments/id/{}",
-                    gateway_url, api_key, GATEWAY_QOS_ORACLE
-                );
-
-                let now = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .map_err(|e| {
-                        SubgraphError::InternalProcessingError(format!(
-                            "Error calculating timestamp: {}",
-                            e
-                        ))
-                    })?
-                    .as_secs();
-
-                let thirty_days_ago = now - (30 * 24 * 60 * 60);
-
-                let query = r#"
-            query GetSubgraphDeployment30DayQueryCounts(
-              $deploymentIDs: [ID!]!,
-              $thirtyDaysAgoTimestamp: BigInt!
-            ) {
-              subgraphDeployments(where: { id_in: $deploymentIDs }) {
-                id
-                queryDailyDataPoints(
-                  where: { dayStart_gte: $thirtyDaysAgoTimestamp }
-                  orderBy: dayStart
-                  orderDirection: asc
-                  first: 31
-                ) {
-                  query_count
-                  dayStart
-                }
-              }
-            }
-            "#;
-
-                let variables = serde_json::json!({
-                    "deploymentIDs": ipfs_hashes,
-                    "thirtyDaysAgoTimestamp": thirty_days_ago.to_string()
-                });
-
-                let request_body = serde_json::json!({ "query": query, "variables": variables });
-
-                let response = self
-                    .http_client
-                    .post(&url)
-                    .json(&request_body)
-                    .send()
-                    .await?
-                    .json::<GraphQLResponse>()
-                    .await?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| {
+                SubgraphError::InternalProcessingError(format!(
+                    "Error calculating timestamp: {}",
+                    e
+                ))
+            })?
+            .as_secs();
+        let thirty_days_ago = now - (30 * 24 * 60 * 60);
+
+        let deployments = NetworkSubgraphClient::qos_oracle(self, api_key, gateway_url)
+            .daily_query_counts(ipfs_hashes, thirty_days_ago)
+            .await?;
+
+        let mut query_counts_results: Vec<serde_json::Value> = deployments
+            .iter()
+            .map(|deployment| {
+                let total_query_count: i64 = deployment
+                    .query_daily_data_points
+                    .iter()
+                    .filter_map(|point| point.query_count.parse::<i64>().ok())
+                    .sum();
+                json!({
+                    "ipfs_hash": deployment.id,
+                    "total_query_count": total_query_count,
+                    "data_points_count": deployment.query_daily_data_points.len()
+                })
+            })
+            .collect();
+
+        query_counts_results.sort_by(|a, b| {
+            let count_a = a["total_query_count"].as_i64().unwrap_or(0);
+            let count_b = b["total_query_count"].as_i64().unwrap_or(0);
+            count_b.cmp(&count_a) // Sort descending
+        });
+
+        Ok(json!({
+            "deployments": query_counts_results,
+            "total_deployments_processed": query_counts_results.len()
+        }))
+    }
 
-                if let Some(errors) = response.errors {
-                    if !errors.is_empty() {
-                        return Err(SubgraphError::GraphQlError(errors[0].message.clone()));
+    /// Looks up the deployment behind `deployment` in the network subgraph. Subgraph IDs
+    /// resolve to their current version's deployment.
+    pub(crate) async fn resolve_deployment_internal(
+        &self,
+        api_key: &str,
+        gateway_url: &str,
+        deployment: DeploymentRef<'_>,
+    ) -> Result<DeploymentTarget, SubgraphError> {
+        let client = NetworkSubgraphClient::new(self, api_key, gateway_url);
+        let (found, subgraph_id) = match deployment {
+            DeploymentRef::SubgraphId(subgraph_id) => (
+                client.current_deployment(subgraph_id).await?,
+                Some(subgraph_id.to_string()),
+            ),
+            _ => {
+                let ipfs_hash = deployment.local_ipfs_hash().ok_or_else(|| {
+                    SubgraphError::InternalProcessingError(format!(
+                        "Invalid deployment identifier: {:?}",
+                        deployment
+                    ))
+                })?;
+                (client.deployment_by_ipfs_hash(&ipfs_hash).await?, None)
+            }
+        };
+
+        match found {
+            Some(found) => {
+                let mut subgraph_ids = found.subgraph_ids();
+                if let Some(subgraph_id) = &subgraph_id {
+                    if !subgraph_ids.contains(subgraph_id) {
+                        subgraph_ids.push(subgraph_id.clone());
                     }
                 }
+                Ok(DeploymentTarget {
+                    ipfs_hash: found.ipfs_hash,
+                    subgraph_ids: Some(subgraph_ids),
+                    subgraph_id,
+                    network: found.manifest.and_then(|manifest| manifest.network),
+                })
+            }
+            // Deployments missing from the network subgraph are only known by what
+            // the call gave; rules needing the rest deny them
+            None => Ok(DeploymentTarget {
+                ipfs_hash: deployment.local_ipfs_hash(),
+                subgraph_ids: subgraph_id.clone().map(|id| vec![id]),
+                subgraph_id,
+                network: None,
+            }),
+        }
+    }
 
-                let data = response.data.ok_or_else(|| {
-                    SubgraphError::GraphQlError("No data returned from the GraphQL API".to_string())
-                })?;
-
-                let deployments = data
-                    .get("subgraphDeployments")
-                    .and_then(|d| d.as_array())
-                    .ok_or_else(|| {
-                        SubgraphError::GraphQlError(
-                            "Unexpected response format for deployments".to_string(),
-                        )
-                    })?;
-
-                let mut query_counts_results: Vec<serde_json::Value> = Vec::new();
-                for deployment_data in deployments {
-                    let id = deployment_data
-                        .get("id")
-                        .and_then(|id_val| id_val.as_str())
-                        .ok_or_else(|| {
-                            SubgraphError::GraphQlError(
-                                "Missing deployment ID in response".to_string(),
-                            )
-                        })?;
-                    let data_points = deployment_data
-                        .get("queryDailyDataPoints")
-                        .and_then(|dp| dp.as_array())
-                        .ok_or_else(|| {
-                            SubgraphError::GraphQlError(
-                                "Missing data points in response".to_string(),
-                            )
-                        })?;
-
-                    let total_query_count: i64 = data_points
-                        .iter()
-                        .filter_map(|point| {
-                            point
-                                .get("query_count")
-                                .and_then(|qc| qc.as_str())
-                                .and_then(|s| s.parse::<i64>().ok())
-                        })
-                        .sum();
-
-                    query_counts_results.push(json!({
-                        "ipfs_hash": id,
-                        "total_query_count": total_query_count,
-                        "data_points_count": data_points.len()
-                    }));
-                }
+    /// Checks `deployment` against the configured deployment policy, if any.
+    pub(crate) async fn enforce_deployment_policy(
+        &self,
+        tool_name: &str,
+        api_key: &str,
+        gateway_url: &str,
+        deployment: DeploymentRef<'_>,
+    ) -> Result<(), McpError> {
+        tracing::Span::current().record("subgraph.deployment", deployment.as_str());
+
+        let Some(store) = &self.policy else {
+            return self.check_deployment_target(tool_name, api_key, deployment.local_target());
+        };
+        let policy = store.current();
+
+        let needs_lookup = matches!(deployment, DeploymentRef::SubgraphId(_))
+            || policy.needs_network()
+            || policy.needs_subgraphs();
+        let target = if needs_lookup {
+            self.resolve_deployment_internal(api_key, gateway_url, deployment)
+                .await
+                .map_err(|e| {
+                    McpError::internal_error(
+                        format!("Failed to resolve deployment for policy check: {}", e),
+                        Some(json!({ "details": e.to_string() })),
+                    )
+                })?
+        } else {
+            deployment.local_target()
+        };
+        self.check_deployment_target(tool_name, api_key, target)
+    }
 
-                query_counts_results.sort_by(|a, b| {
-                    let count_a = a["total_query_count"].as_i64().unwrap_or(0);
-                    let count_b = b["total_query_count"].as_i64().unwrap_or(0);
-                    count_b.cmp(&count_a) // Sort descending
-                });
+    /// Checks a deployment whose target is already known against the configured
+    /// deployment policy, if any.
+    pub(crate) fn check_deployment_target(
+        &self,
+        tool_name: &str,
+        api_key: &str,
+        target: DeploymentTarget,
+    ) -> Result<(), McpError> {
+        note_deployment(&target);
+        let Some(store) = &self.policy else {
+            return Ok(());
+        };
+
+        store.current().check(&target).map_err(|e| {
+            tracing::warn!(target: "mcp_policy", tool_name = %tool_name, error = %e, "Deployment rejected by policy");
+            METRICS.record_policy_violation(tool_name, api_key);
+            McpError::new(
+                ErrorCode(POLICY_VIOLATION_ERROR_CODE),
+                e.to_string(),
+                Some(json!({
+                    "details": e.to_string(),
+                    "ipfs_hash": target.ipfs_hash,
+                    "subgraph_id": target.subgraph_id,
+                    "subgraph_ids": target.subgraph_ids,
+                    "network": target.network,
+                })),
+            )
+        })
+    }
 
-                Ok(json!({
-                    "deployments": query_counts_results,
-                    "total_deployments_processed": query_counts_results.len()
-                }))
-            })
-            .await
+    pub(crate) fn annotate_tool(&self, mut tool: Tool) -> Tool {
+        // (title, billable, open_world)
+        let (title, billable, open_world) = match tool.name.as_ref() {
+            "get_schema_by_deployment_id" => ("Get Schema by Deployment ID", false, true),
+            "get_schema_by_subgraph_id" => ("Get Schema by Subgraph ID", false, true),
+            "get_schema_by_ipfs_hash" => ("Get Schema by IPFS Hash", false, true),
+            "execute_query_by_deployment_id" => ("Execute Query by Deployment ID", true, true),
+            "execute_query_by_ipfs_hash" => ("Execute Query by IPFS Hash", true, true),
+            "execute_query_by_subgraph_id" => ("Execute Query by Subgraph ID", true, true),
+            "get_top_subgraph_deployments" => ("Get Top Subgraph Deployments", false, true),
+            "search_subgraphs_by_keyword" => ("Search Subgraphs by Keyword", false, true),
+            "get_deployment_30day_query_counts" => ("Get 30-Day Query Counts", false, true),
+            "get_session_usage" => ("Get Session Usage", false, false),
+            "summarize_schema" => ("Summarize Schema", false, true),
+            "generate_example_queries" => ("Generate Example Queries", false, true),
+            "get_schema_by_introspection" => ("Get Schema by Introspection", true, true),
+            "execute_queries_batch" => ("Execute Queries in Batch", true, true),
+            "query_across_chains" => ("Query Across Chains", true, true),
+            "export_query" => ("Export Query Results", true, true),
+            _ => ("", false, true),
+        };
+
+        // Exports write a new file on every call, without ever overwriting one
+        let writes_files = tool.name == "export_query";
+        let mut annotations = ToolAnnotations::new()
+            .read_only(!writes_files)
+            .destructive(false)
+            .idempotent(!writes_files)
+            .open_world(open_world);
+        if !title.is_empty() {
+            annotations.title = Some(title.to_string());
+        }
+        tool.annotations = Some(annotations);
+
+        // ToolAnnotations has no slot for custom hints, so the cost hint travels as a
+        // vendor extension keyword on the input schema, which JSON Schema consumers ignore.
+        let mut schema = tool.input_schema.as_ref().clone();
+        schema.insert(
+            TOOL_COST_HINT_KEY.to_string(),
+            json!(if billable {
+                TOOL_COST_BILLABLE
+            } else {
+                TOOL_COST_FREE
+            }),
+        );
+        tool.input_schema = Arc::new(schema);
+
+        tool
     }
+
     pub(crate) fn _create_resource_text(&self, uri: &str, name: &str) -> Resource {
         RawResource::new(uri, name.to_string()).no_annotation()
     }
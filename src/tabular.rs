@@ -0,0 +1,230 @@
+// SPDX-License-Identifier: Apache-2.0
+use rmcp::{model::Content, schemars};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeSet;
+
+/// How the query tools render a result.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ResultFormat {
+    /// The whole response as pretty-printed JSON
+    #[default]
+    Json,
+    /// A Markdown table of the rows
+    Markdown,
+    /// Comma-separated values with a header line
+    Csv,
+    /// One flattened JSON object per line
+    Ndjson,
+}
+
+impl ResultFormat {
+    fn name(&self) -> &'static str {
+        match self {
+            ResultFormat::Json => "json",
+            ResultFormat::Markdown => "markdown",
+            ResultFormat::Csv => "csv",
+            ResultFormat::Ndjson => "ndjson",
+        }
+    }
+}
+
+/// Turns nested objects into dotted column names; arrays stay as they are.
+pub fn flatten_row(row: &Value) -> Map<String, Value> {
+    fn flatten_into(prefix: &str, value: &Value, columns: &mut Map<String, Value>) {
+        match value {
+            Value::Object(fields) => {
+                for (name, value) in fields {
+                    let column = if prefix.is_empty() {
+                        name.clone()
+                    } else {
+                        format!("{}.{}", prefix, name)
+                    };
+                    flatten_into(&column, value, columns);
+                }
+            }
+            value => {
+                columns.insert(prefix.to_string(), value.clone());
+            }
+        }
+    }
+
+    let mut columns = Map::new();
+    flatten_into("", row, &mut columns);
+    columns
+}
+
+/// The union of the rows' column names, sorted.
+pub fn column_names(rows: &[Map<String, Value>]) -> Vec<String> {
+    rows.iter()
+        .flat_map(|row| row.keys().cloned())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// A cell as text: strings as they are, null as empty and anything else as JSON.
+pub fn cell_text(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(value) => value.to_string(),
+    }
+}
+
+/// The rows of a query response: `data` itself once a transform has turned it
+/// into a list, or else its only top-level list. Fails with the reason when there
+/// are several lists or the rows are not flat objects, which only JSON can
+/// represent.
+pub fn result_rows(response: &Value) -> Result<Vec<Map<String, Value>>, String> {
+    let rows = match &response["data"] {
+        Value::Array(rows) => rows,
+        Value::Object(fields) => {
+            let lists: Vec<(&String, &Vec<Value>)> = fields
+                .iter()
+                .filter_map(|(field, value)| Some((field, value.as_array()?)))
+                .collect();
+            match lists.as_slice() {
+                [] => return Err("the result holds no list".to_string()),
+                [(_, rows)] => *rows,
+                lists => {
+                    return Err(format!(
+                        "the result holds several lists ({}); pick one with a transform's `select`",
+                        lists
+                            .iter()
+                            .map(|(field, _)| format!("`{}`", field))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ))
+                }
+            }
+        }
+        _ => return Err("the result holds no data".to_string()),
+    };
+
+    rows.iter()
+        .map(|row| {
+            if !row.is_object() {
+                return Err("the rows are not objects".to_string());
+            }
+            let columns = flatten_row(row);
+            match columns.iter().find(|(_, value)| value.is_array()) {
+                Some((column, _)) => Err(format!("the `{}` column holds a list", column)),
+                None => Ok(columns),
+            }
+        })
+        .collect()
+}
+
+/// Renders a query response in `format`. Responses whose rows cannot be laid out
+/// as a table are returned as JSON, with the reason under `format_fallback`.
+pub fn format_result(response: &Value, format: ResultFormat) -> String {
+    render(response, format).unwrap_or_else(|json| json)
+}
+
+/// Renders the rows of a query response in `format`, or fails with the response
+/// as JSON when the format is JSON or the rows cannot be laid out as a table.
+fn render(response: &Value, format: ResultFormat) -> Result<String, String> {
+    if format == ResultFormat::Json {
+        return Err(format!("{:#}", response));
+    }
+    let rows = result_rows(response).map_err(|reason| {
+        let mut response = response.clone();
+        response["format_fallback"] = json!(format!(
+            "Returned as JSON instead of {}: {}",
+            format.name(),
+            reason
+        ));
+        format!("{:#}", response)
+    })?;
+
+    Ok(match format {
+        ResultFormat::Markdown => render_markdown(&rows),
+        ResultFormat::Csv => render_csv(&rows),
+        ResultFormat::Ndjson => rows
+            .into_iter()
+            .map(|row| Value::Object(row).to_string())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        ResultFormat::Json => unreachable!("handled above"),
+    })
+}
+
+/// The content of a query tool result: the response in `format`, followed by the
+/// freshness warnings in an item of their own when the format has no room for
+/// them.
+pub fn result_contents(response: &Value, format: ResultFormat) -> Vec<Content> {
+    let text = match render(response, format) {
+        Ok(text) => text,
+        // JSON keeps the warnings under `freshness`
+        Err(json) => return vec![Content::text(json)],
+    };
+    let warnings: Vec<String> = response["freshness"]["warnings"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|warning| Some(format!("warning: {}", warning.as_str()?)))
+        .collect();
+
+    let mut contents = vec![Content::text(text)];
+    if !warnings.is_empty() {
+        contents.push(Content::text(warnings.join("\n")));
+    }
+    contents
+}
+
+/// Renders flattened rows as a Markdown table, sorted by column name.
+pub fn render_markdown(rows: &[Map<String, Value>]) -> String {
+    let headers = column_names(rows);
+    if headers.is_empty() {
+        return "(no rows)".to_string();
+    }
+    let escape = |text: String| text.replace('|', "\\|").replace(['\r', '\n'], " ");
+    let line = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+
+    std::iter::once(line(headers.iter().cloned().map(escape).collect()))
+        .chain([line(vec!["---".to_string(); headers.len()])])
+        .chain(rows.iter().map(|row| {
+            line(
+                headers
+                    .iter()
+                    .map(|column| escape(cell_text(row.get(column))))
+                    .collect(),
+            )
+        }))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// One CSV line, quoting fields as RFC 4180 does.
+pub fn csv_record(cells: impl IntoIterator<Item = String>) -> String {
+    cells
+        .into_iter()
+        .map(|text| {
+            if text.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", text.replace('"', "\"\""))
+            } else {
+                text
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Renders flattened rows as CSV with a header line.
+pub fn render_csv(rows: &[Map<String, Value>]) -> String {
+    let headers = column_names(rows);
+    if headers.is_empty() {
+        return String::new();
+    }
+    std::iter::once(csv_record(headers.clone()))
+        .chain(
+            rows.iter()
+                .map(|row| csv_record(headers.iter().map(|column| cell_text(row.get(column))))),
+        )
+        .collect::<Vec<_>>()
+        .join("\n")
+}
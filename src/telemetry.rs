@@ -0,0 +1,136 @@
+// SPDX-License-Identifier: Apache-2.0
+use opentelemetry::{global, trace::TracerProvider as _};
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator,
+    trace::{Sampler, SdkTracerProvider},
+    Resource,
+};
+use std::{collections::HashMap, env, future::Future};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Flushes and shuts down the OTLP exporter when dropped.
+pub struct TelemetryGuard {
+    provider: Option<SdkTracerProvider>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        if let Some(provider) = self.provider.take() {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("Failed to shut down trace exporter: {}", e);
+            }
+        }
+    }
+}
+
+/// Whether an OTLP endpoint has been configured for trace export.
+pub fn otlp_export_enabled() -> bool {
+    [
+        "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+        "OTEL_EXPORTER_OTLP_ENDPOINT",
+    ]
+    .iter()
+    .any(|name| env::var(name).is_ok_and(|v| !v.is_empty()))
+}
+
+/// Fraction of new traces that are sampled, from `OTEL_TRACES_SAMPLER_ARG`.
+/// Traces started by an upstream caller follow the caller's sampling decision.
+pub fn trace_sample_ratio() -> f64 {
+    env::var("OTEL_TRACES_SAMPLER_ARG")
+        .ok()
+        .and_then(|s| s.parse::<f64>().ok())
+        .map(|ratio| ratio.clamp(0.0, 1.0))
+        .unwrap_or(1.0)
+}
+
+/// Whether logs should be written as JSON lines, set with `LOG_FORMAT=json`.
+pub fn json_logs_enabled() -> bool {
+    env::var("LOG_FORMAT").is_ok_and(|format| format.eq_ignore_ascii_case("json"))
+}
+
+/// Installs the global tracing subscriber. Logs are written to stderr, filtered
+/// by `RUST_LOG`, and spans are exported over OTLP/HTTP when an endpoint is set.
+pub fn init_tracing() -> TelemetryGuard {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    // stdout carries the MCP protocol in STDIO mode, so logs must go to stderr
+    let fmt_layer = if json_logs_enabled() {
+        // The span list carries the request ID of the enclosing MCP request
+        tracing_subscriber::fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .with_writer(std::io::stderr)
+            .boxed()
+    } else {
+        tracing_subscriber::fmt::layer()
+            .with_writer(std::io::stderr)
+            .boxed()
+    };
+
+    let provider = if otlp_export_enabled() {
+        match build_tracer_provider() {
+            Ok(provider) => Some(provider),
+            Err(e) => {
+                eprintln!("Failed to initialize OTLP trace exporter: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let otel_layer = provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer().with_tracer(provider.tracer("subgraph-mcp"))
+    });
+
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    tracing_subscriber::registry()
+        .with(fmt_layer)
+        .with(otel_layer)
+        .with(filter)
+        .try_init()
+        .unwrap_or_else(|e| eprintln!("tracing subscriber init failed: {}", e));
+
+    TelemetryGuard { provider }
+}
+
+fn build_tracer_provider() -> anyhow::Result<SdkTracerProvider> {
+    // The endpoint, headers and timeout are read from the standard OTEL_EXPORTER_OTLP_* variables
+    let exporter = SpanExporter::builder().with_http().build()?;
+    let service_name =
+        env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| env!("CARGO_PKG_NAME").to_string());
+
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            trace_sample_ratio(),
+        ))))
+        .with_resource(Resource::builder().with_service_name(service_name).build())
+        .build())
+}
+
+/// Adds the W3C `traceparent` header for the current span to an outgoing request.
+pub fn inject_trace_context(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    let context = tracing::Span::current().context();
+    let mut headers = HashMap::new();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&context, &mut headers));
+    headers.into_iter().fold(request, |request, (name, value)| {
+        request.header(name, value)
+    })
+}
+
+/// Runs `f` with `request_id` as the ID of the MCP request being handled.
+pub async fn with_request_id<F: Future>(request_id: String, f: F) -> F::Output {
+    REQUEST_ID.scope(request_id, f).await
+}
+
+/// The ID of the MCP request being handled by the current task, if any.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|request_id| request_id.clone()).ok()
+}
@@ -0,0 +1,435 @@
+// SPDX-License-Identifier: Apache-2.0
+use crate::cross_chain::OrderDirection;
+use crate::error::SubgraphError;
+use crate::tabular::flatten_row;
+use bigdecimal::{BigDecimal, ToPrimitive};
+use rmcp::schemars;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{cmp::Ordering, collections::HashMap, str::FromStr};
+
+/// Significant digits kept by averages, the precision of graph-node's `BigDecimal`.
+const AVERAGE_PRECISION: u64 = 34;
+
+/// A declarative reshaping of a query result, applied to `data` before the result
+/// is returned: select the rows, flatten them, project or aggregate them, then
+/// sort and truncate.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct TransformSpec {
+    #[schemars(
+        description = "Optional path to the rows within `data`, e.g. 'pools' or '$.pools[*].swaps[*]'. Defaults to the only top-level list"
+    )]
+    pub select: Option<String>,
+    #[schemars(
+        description = "Optional. Flatten nested objects into dotted columns, e.g. 'token0.symbol'"
+    )]
+    pub flatten: Option<bool>,
+    #[schemars(
+        description = "Optional columns to keep, as dotted paths. Cannot be combined with group_by or aggregate"
+    )]
+    pub fields: Option<Vec<String>>,
+    #[schemars(description = "Optional columns to group the rows by, as dotted paths")]
+    pub group_by: Option<Vec<String>>,
+    #[schemars(
+        description = "Optional aggregates computed over all rows, or per group with group_by (which defaults to a count)"
+    )]
+    pub aggregate: Option<Vec<Aggregate>>,
+    #[schemars(description = "Optional column to sort the output rows by")]
+    pub sort_by: Option<String>,
+    #[schemars(description = "Optional sort direction: 'desc' (default) or 'asc'")]
+    pub sort_direction: Option<OrderDirection>,
+    #[schemars(description = "Optional maximum number of output rows")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct Aggregate {
+    #[schemars(description = "One of 'sum', 'avg', 'min', 'max' or 'count'")]
+    pub op: AggregateOp,
+    #[schemars(
+        description = "Column to aggregate, as a dotted path. Optional for 'count', which then counts rows"
+    )]
+    pub field: Option<String>,
+    #[serde(rename = "as")]
+    #[schemars(description = "Optional output column name, e.g. 'totalVolume'")]
+    pub alias: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AggregateOp {
+    Sum,
+    Avg,
+    Min,
+    Max,
+    Count,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Step {
+    Key(String),
+    Index(usize),
+    All,
+}
+
+/// A validated `TransformSpec`, checked before the query is sent so a bad spec
+/// costs no query fees.
+#[derive(Clone, Debug)]
+pub struct Transform {
+    select: Option<Vec<Step>>,
+    spec: TransformSpec,
+}
+
+impl Transform {
+    pub fn new(spec: TransformSpec) -> Result<Self, SubgraphError> {
+        let aggregating = spec.group_by.is_some() || spec.aggregate.is_some();
+        if aggregating && spec.fields.is_some() {
+            return Err(SubgraphError::InvalidTransform(
+                "`fields` cannot be combined with `group_by` or `aggregate`".to_string(),
+            ));
+        }
+        for aggregate in spec.aggregate.iter().flatten() {
+            if aggregate.field.is_none() && aggregate.op != AggregateOp::Count {
+                return Err(SubgraphError::InvalidTransform(format!(
+                    "The `{}` aggregate needs a `field`",
+                    aggregate.op.name()
+                )));
+            }
+        }
+        let select = spec.select.as_deref().map(parse_path).transpose()?;
+        Ok(Self { select, spec })
+    }
+
+    /// Replaces `response["data"]` with the transformed rows.
+    pub fn apply(&self, mut response: Value) -> Result<Value, SubgraphError> {
+        let data = response.get("data").cloned().unwrap_or(Value::Null);
+        response["data"] = Value::Array(self.rows(&data)?);
+        Ok(response)
+    }
+
+    /// The rows `data` is transformed into.
+    pub fn rows(&self, data: &Value) -> Result<Vec<Value>, SubgraphError> {
+        let mut rows = match &self.select {
+            Some(steps) => select(data, steps),
+            None => default_rows(data)?,
+        };
+        if self.spec.flatten.unwrap_or(false) {
+            rows = rows.into_iter().map(flatten).collect();
+        }
+
+        if self.spec.group_by.is_some() || self.spec.aggregate.is_some() {
+            rows = self.aggregate(&rows)?;
+        } else if let Some(fields) = &self.spec.fields {
+            rows = rows
+                .iter()
+                .map(|row| {
+                    let columns: Map<String, Value> = fields
+                        .iter()
+                        .map(|field| {
+                            let value = lookup(row, field).cloned().unwrap_or(Value::Null);
+                            (field.clone(), value)
+                        })
+                        .collect();
+                    Value::Object(columns)
+                })
+                .collect();
+        }
+
+        if let Some(sort_by) = &self.spec.sort_by {
+            sort_rows(
+                &mut rows,
+                sort_by,
+                self.spec.sort_direction.unwrap_or_default(),
+            );
+        }
+        if let Some(limit) = self.spec.limit {
+            rows.truncate(limit);
+        }
+        Ok(rows)
+    }
+
+    fn aggregate(&self, rows: &[Value]) -> Result<Vec<Value>, SubgraphError> {
+        let group_by = self.spec.group_by.clone().unwrap_or_default();
+        let count = [Aggregate {
+            op: AggregateOp::Count,
+            field: None,
+            alias: None,
+        }];
+        let aggregates = self.spec.aggregate.as_deref().unwrap_or(&count);
+
+        // Groups keep the order their first row appeared in
+        let mut groups: Vec<(Vec<Value>, Vec<&Value>)> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        for row in rows {
+            let key: Vec<Value> = group_by
+                .iter()
+                .map(|field| lookup(row, field).cloned().unwrap_or(Value::Null))
+                .collect();
+            let position = *index
+                .entry(Value::Array(key.clone()).to_string())
+                .or_insert_with(|| {
+                    groups.push((key, Vec::new()));
+                    groups.len() - 1
+                });
+            groups[position].1.push(row);
+        }
+
+        groups
+            .into_iter()
+            .map(|(key, members)| {
+                let mut columns: Map<String, Value> = group_by.iter().cloned().zip(key).collect();
+                for aggregate in aggregates {
+                    columns.insert(aggregate.column(), aggregate.compute(&members)?);
+                }
+                Ok(Value::Object(columns))
+            })
+            .collect()
+    }
+}
+
+impl AggregateOp {
+    fn name(&self) -> &'static str {
+        match self {
+            AggregateOp::Sum => "sum",
+            AggregateOp::Avg => "avg",
+            AggregateOp::Min => "min",
+            AggregateOp::Max => "max",
+            AggregateOp::Count => "count",
+        }
+    }
+}
+
+impl Aggregate {
+    fn column(&self) -> String {
+        match (&self.alias, &self.field) {
+            (Some(alias), _) => alias.clone(),
+            (None, Some(field)) => format!("{}_{}", self.op.name(), field.replace('.', "_")),
+            (None, None) => self.op.name().to_string(),
+        }
+    }
+
+    fn compute(&self, rows: &[&Value]) -> Result<Value, SubgraphError> {
+        let Some(field) = &self.field else {
+            return Ok(Value::from(rows.len()));
+        };
+        let values: Vec<&Value> = rows
+            .iter()
+            .filter_map(|row| lookup(row, field))
+            .filter(|value| !value.is_null())
+            .collect();
+
+        match self.op {
+            AggregateOp::Count => Ok(Value::from(values.len())),
+            AggregateOp::Min => Ok(values
+                .into_iter()
+                .min_by(|a, b| compare_values(a, b))
+                .cloned()
+                .unwrap_or(Value::Null)),
+            AggregateOp::Max => Ok(values
+                .into_iter()
+                .max_by(|a, b| compare_values(a, b))
+                .cloned()
+                .unwrap_or(Value::Null)),
+            AggregateOp::Sum | AggregateOp::Avg => {
+                let mut sum = BigDecimal::from(0);
+                for value in &values {
+                    sum += decimal(value).ok_or_else(|| {
+                        SubgraphError::InvalidTransform(format!(
+                            "Cannot {} `{}`: {} is not a number",
+                            self.op.name(),
+                            field,
+                            value
+                        ))
+                    })?;
+                }
+                let strings = values.iter().any(|value| value.is_string());
+                match self.op {
+                    AggregateOp::Avg if values.is_empty() => Ok(Value::Null),
+                    AggregateOp::Avg => {
+                        let average = (sum / BigDecimal::from(values.len() as u64))
+                            .with_prec(AVERAGE_PRECISION);
+                        Ok(decimal_value(&average, strings))
+                    }
+                    _ => Ok(decimal_value(&sum, strings)),
+                }
+            }
+        }
+    }
+}
+
+/// Parses a JSONPath-like path: dotted keys, `[n]` indexes, `[*]` wildcards and
+/// `['key']` for keys with dots, with an optional leading `$`.
+fn parse_path(path: &str) -> Result<Vec<Step>, SubgraphError> {
+    let invalid = || SubgraphError::InvalidTransform(format!("`{}` is not a valid path", path));
+    let mut rest = path.trim();
+    rest = rest.strip_prefix('$').unwrap_or(rest);
+
+    let mut steps = Vec::new();
+    while !rest.is_empty() {
+        if let Some(bracket) = rest.strip_prefix('[') {
+            let end = bracket.find(']').ok_or_else(invalid)?;
+            let inner = bracket[..end].trim();
+            steps.push(match inner {
+                "*" => Step::All,
+                quoted
+                    if quoted.len() >= 2
+                        && (quoted.starts_with('\'') && quoted.ends_with('\'')
+                            || quoted.starts_with('"') && quoted.ends_with('"')) =>
+                {
+                    Step::Key(quoted[1..quoted.len() - 1].to_string())
+                }
+                index => Step::Index(index.parse().map_err(|_| invalid())?),
+            });
+            rest = &bracket[end + 1..];
+        } else {
+            rest = rest.strip_prefix('.').unwrap_or(rest);
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            if end == 0 {
+                return Err(invalid());
+            }
+            steps.push(Step::Key(rest[..end].to_string()));
+            rest = &rest[end..];
+        }
+    }
+    Ok(steps)
+}
+
+/// Follows `steps` from `data`. A key applied to a list is applied to each of its
+/// items, and a path ending on lists yields their items as rows.
+fn select(data: &Value, steps: &[Step]) -> Vec<Value> {
+    let mut current = vec![data];
+    for step in steps {
+        let mut next = Vec::new();
+        for value in current {
+            match (step, value) {
+                (Step::Key(key), Value::Array(items)) => {
+                    next.extend(items.iter().filter_map(|item| item.get(key)))
+                }
+                (Step::Key(key), value) => next.extend(value.get(key)),
+                (Step::Index(index), value) => next.extend(value.get(*index)),
+                (Step::All, Value::Array(items)) => next.extend(items),
+                (Step::All, Value::Object(fields)) => next.extend(fields.values()),
+                (Step::All, _) => {}
+            }
+        }
+        current = next;
+    }
+
+    current
+        .into_iter()
+        .flat_map(|value| match value {
+            Value::Array(items) => items.clone(),
+            value => vec![value.clone()],
+        })
+        .collect()
+}
+
+fn default_rows(data: &Value) -> Result<Vec<Value>, SubgraphError> {
+    let lists: Vec<(&String, &Vec<Value>)> = data
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(field, value)| value.as_array().map(|items| (field, items)))
+        .collect();
+    match lists.as_slice() {
+        [(_, items)] => Ok(items.to_vec()),
+        [] => Err(SubgraphError::InvalidTransform(
+            "The result holds no list; set `select` to the rows to transform".to_string(),
+        )),
+        _ => Err(SubgraphError::InvalidTransform(format!(
+            "The result holds several lists; set `select` to one of {}",
+            lists
+                .iter()
+                .map(|(field, _)| format!("`{}`", field))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+    }
+}
+
+/// Turns nested objects into dotted columns. Lists are kept as they are.
+fn flatten(row: Value) -> Value {
+    match row {
+        Value::Object(_) => Value::Object(flatten_row(&row)),
+        row => row,
+    }
+}
+
+/// The value of a column, either a flattened `a.b` column or the nested path.
+pub(crate) fn lookup<'a>(row: &'a Value, path: &str) -> Option<&'a Value> {
+    row.get(path)
+        .or_else(|| path.split('.').try_fold(row, |value, key| value.get(key)))
+}
+
+/// The exact value of a JSON number or of a numeric string, as graph-node returns
+/// `BigInt` and `BigDecimal` values. Strings must be plain decimals; exponent
+/// notation such as `1e-999999999` would make every sum or comparison with it
+/// align billions of digits.
+fn decimal(value: &Value) -> Option<BigDecimal> {
+    match value {
+        Value::Number(n) => BigDecimal::from_str(&n.to_string()).ok(),
+        Value::String(s) if is_plain_decimal(s.trim()) => BigDecimal::from_str(s.trim()).ok(),
+        _ => None,
+    }
+}
+
+/// Whether `text` is an optionally negative decimal without an exponent.
+fn is_plain_decimal(text: &str) -> bool {
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    let (integer, fraction) = digits.split_once('.').unwrap_or((digits, "0"));
+    !integer.is_empty()
+        && !fraction.is_empty()
+        && integer.bytes().all(|b| b.is_ascii_digit())
+        && fraction.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// A computed number as a JSON value: a string when the inputs were strings, so no
+/// precision is lost, and a JSON number otherwise.
+fn decimal_value(value: &BigDecimal, strings: bool) -> Value {
+    let normalized = value.normalized();
+    if !strings {
+        if normalized.is_integer() {
+            if let Some(n) = normalized.to_i64() {
+                return Value::from(n);
+            }
+        }
+        if let Some(n) = normalized.to_f64().and_then(serde_json::Number::from_f64) {
+            return Value::Number(n);
+        }
+    }
+    Value::String(normalized.to_plain_string())
+}
+
+/// Compares JSON values exactly when both are numbers or numeric strings, as
+/// `BigInt` and `BigDecimal` values are. Numbers come before other strings, which
+/// come before any other values, so that the order stays total when a column
+/// mixes them.
+pub(crate) fn compare_values(a: &Value, b: &Value) -> Ordering {
+    match (decimal(a), decimal(b)) {
+        (Some(x), Some(y)) => x.cmp(&y),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => match (a, b) {
+            (Value::String(x), Value::String(y)) => x.cmp(y),
+            (Value::String(_), _) => Ordering::Less,
+            (_, Value::String(_)) => Ordering::Greater,
+            _ => a.to_string().cmp(&b.to_string()),
+        },
+    }
+}
+
+/// Sorts rows by a column. Rows missing the column come last.
+pub(crate) fn sort_rows(rows: &mut [Value], field: &str, direction: OrderDirection) {
+    fn key<'a>(row: &'a Value, field: &str) -> Option<&'a Value> {
+        lookup(row, field).filter(|value| !value.is_null())
+    }
+    rows.sort_by(|a, b| match (key(a, field), key(b, field)) {
+        (Some(x), Some(y)) => match direction {
+            OrderDirection::Asc => compare_values(x, y),
+            OrderDirection::Desc => compare_values(y, x),
+        },
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    });
+}
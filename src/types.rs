@@ -1,4 +1,10 @@
 // SPDX-License-Identifier: Apache-2.0
+use crate::cross_chain::OrderDirection;
+use crate::decode::DecodeSpec;
+use crate::export::ExportFormat;
+use crate::introspection::IntrospectionFormat;
+use crate::tabular::ResultFormat;
+use crate::transform::TransformSpec;
 use rmcp::schemars;
 use serde::{Deserialize, Serialize};
 #[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
@@ -35,6 +41,22 @@ pub struct ExecuteQueryByDeploymentIdRequest {
     pub query: String,
     #[schemars(description = "Optional JSON value for GraphQL variables")]
     pub variables: Option<serde_json::Value>,
+    #[schemars(
+        description = "Optional. Whether to report the deployment's latest indexed block and how far it is behind (on by default)"
+    )]
+    pub freshness: Option<bool>,
+    #[schemars(
+        description = "Optional decoding of raw values, applied before `transform`: BigInt amounts scaled by their token's decimals are added as '<field>_decoded' and timestamps as '<field>_iso', next to the original values"
+    )]
+    pub decode: Option<DecodeSpec>,
+    #[schemars(
+        description = "Optional transformation of `data` into a flat list of rows: select, flatten, project, group and aggregate (sum, avg, min, max, count), sort and limit"
+    )]
+    pub transform: Option<TransformSpec>,
+    #[schemars(
+        description = "Optional output format: 'json' (default), or 'markdown', 'csv' or 'ndjson' for the rows of the first top-level list, with nested fields as dotted columns"
+    )]
+    pub output_format: Option<ResultFormat>,
 }
 #[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
 pub struct ExecuteQueryByIpfsHashRequest {
@@ -44,6 +66,22 @@ pub struct ExecuteQueryByIpfsHashRequest {
     pub query: String,
     #[schemars(description = "Optional JSON value for GraphQL variables")]
     pub variables: Option<serde_json::Value>,
+    #[schemars(
+        description = "Optional. Whether to report the deployment's latest indexed block and how far it is behind (on by default)"
+    )]
+    pub freshness: Option<bool>,
+    #[schemars(
+        description = "Optional decoding of raw values, applied before `transform`: BigInt amounts scaled by their token's decimals are added as '<field>_decoded' and timestamps as '<field>_iso', next to the original values"
+    )]
+    pub decode: Option<DecodeSpec>,
+    #[schemars(
+        description = "Optional transformation of `data` into a flat list of rows: select, flatten, project, group and aggregate (sum, avg, min, max, count), sort and limit"
+    )]
+    pub transform: Option<TransformSpec>,
+    #[schemars(
+        description = "Optional output format: 'json' (default), or 'markdown', 'csv' or 'ndjson' for the rows of the first top-level list, with nested fields as dotted columns"
+    )]
+    pub output_format: Option<ResultFormat>,
 }
 
 #[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
@@ -54,6 +92,104 @@ pub struct ExecuteQueryBySubgraphIdRequest {
     pub query: String,
     #[schemars(description = "Optional JSON value for GraphQL variables")]
     pub variables: Option<serde_json::Value>,
+    #[schemars(
+        description = "Optional. Whether to report the deployment's latest indexed block and how far it is behind (on by default)"
+    )]
+    pub freshness: Option<bool>,
+    #[schemars(
+        description = "Optional decoding of raw values, applied before `transform`: BigInt amounts scaled by their token's decimals are added as '<field>_decoded' and timestamps as '<field>_iso', next to the original values"
+    )]
+    pub decode: Option<DecodeSpec>,
+    #[schemars(
+        description = "Optional transformation of `data` into a flat list of rows: select, flatten, project, group and aggregate (sum, avg, min, max, count), sort and limit"
+    )]
+    pub transform: Option<TransformSpec>,
+    #[schemars(
+        description = "Optional output format: 'json' (default), or 'markdown', 'csv' or 'ndjson' for the rows of the first top-level list, with nested fields as dotted columns"
+    )]
+    pub output_format: Option<ResultFormat>,
+}
+
+#[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct BatchQuery {
+    #[schemars(
+        description = "The deployment ID (0x...), IPFS hash (Qm...) or subgraph ID (e.g., 5zvR82...) to query"
+    )]
+    pub deployment: String,
+    #[schemars(description = "The GraphQL query string")]
+    pub query: String,
+    #[schemars(description = "Optional JSON value for GraphQL variables")]
+    pub variables: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct ExecuteQueriesBatchRequest {
+    #[schemars(description = "The queries to run, each against its own deployment")]
+    pub queries: Vec<BatchQuery>,
+    #[schemars(
+        description = "Optional maximum number of queries sent at the same time, capped by the server limit"
+    )]
+    pub max_concurrency: Option<usize>,
+    #[schemars(
+        description = "Optional. Whether each result reports its deployment's latest indexed block and how far it is behind (on by default)"
+    )]
+    pub freshness: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct QueryAcrossChainsRequest {
+    #[schemars(
+        description = "Keyword matched against subgraph display names (e.g., 'Uniswap V3'). Either this or subgraph_ids is required"
+    )]
+    pub keyword: Option<String>,
+    #[schemars(description = "Explicit list of subgraph IDs to query, instead of a keyword")]
+    pub subgraph_ids: Option<Vec<String>>,
+    #[schemars(description = "The GraphQL query string, run unchanged on every deployment")]
+    pub query: String,
+    #[schemars(description = "Optional JSON value for GraphQL variables")]
+    pub variables: Option<serde_json::Value>,
+    #[schemars(
+        description = "Optional. Also return the top-level collections of all chains merged into one list per field, each row tagged with `_network`"
+    )]
+    pub merge: Option<bool>,
+    #[schemars(
+        description = "Optional field to sort the merged rows by (e.g., totalValueLockedUSD)"
+    )]
+    pub order_by: Option<String>,
+    #[schemars(
+        description = "Optional sort direction of the merged rows: 'desc' (default) or 'asc'"
+    )]
+    pub order_direction: Option<OrderDirection>,
+    #[schemars(
+        description = "Optional. Whether each result reports its deployment's latest indexed block and how far it is behind (on by default)"
+    )]
+    pub freshness: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct ExportQueryRequest {
+    #[schemars(
+        description = "The deployment ID (0x...), IPFS hash (Qm...) or subgraph ID to export from"
+    )]
+    pub deployment: String,
+    #[schemars(
+        description = "A query selecting one entity collection, e.g. '{ swaps(where: {amountUSD_gt: \"1000\"}) { id amountUSD pool { id } } }'. It is paged through by id, so it must not set first, skip, orderBy or orderDirection"
+    )]
+    pub query: String,
+    #[schemars(description = "Optional JSON value for GraphQL variables")]
+    pub variables: Option<serde_json::Value>,
+    #[schemars(
+        description = "'csv' or 'parquet' for flattened rows with dotted column names (Parquet columns are typed from the schema), or 'ndjson' for the rows as returned"
+    )]
+    pub format: ExportFormat,
+    #[schemars(
+        description = "Optional file name, relative to the export directory. Defaults to '<collection>-<timestamp>.<format>'. Existing files are never overwritten"
+    )]
+    pub file_name: Option<String>,
+    #[schemars(description = "Optional maximum number of rows, capped by the server limit")]
+    pub max_rows: Option<usize>,
+    #[schemars(description = "Optional number of rows fetched per query, at most 1000 (default)")]
+    pub page_size: Option<usize>,
 }
 
 #[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
@@ -72,9 +208,27 @@ pub struct GetDeployment30DayQueryCountsRequest {
     pub ipfs_hashes: Vec<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct GetSessionUsageRequest {}
+
+#[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct SummarizeSchemaRequest {
+    #[schemars(
+        description = "The deployment ID (0x...), IPFS hash (Qm...) or subgraph ID (e.g., 5zvR82...) whose schema to summarize"
+    )]
+    pub deployment: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GraphQLRequest {
+    pub query: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variables: Option<serde_json::Value>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
-pub struct GraphQLResponse {
-    pub data: Option<serde_json::Value>,
+pub struct GraphQLResponse<T = serde_json::Value> {
+    pub data: Option<T>,
     pub errors: Option<Vec<GraphQLError>>,
 }
 
@@ -82,3 +236,31 @@ pub struct GraphQLResponse {
 pub struct GraphQLError {
     pub message: String,
 }
+
+#[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct GenerateExampleQueriesRequest {
+    #[schemars(
+        description = "The deployment ID (0x...), IPFS hash (Qm...) or subgraph ID (e.g., 5zvR82...) to generate queries for"
+    )]
+    pub deployment: String,
+    #[schemars(
+        description = "Optional entity type name (e.g., Pool). Defaults to the schema's most connected entity"
+    )]
+    pub entity: Option<String>,
+    #[schemars(
+        description = "Optional number of entities the list queries return (default 10, at most 1000)"
+    )]
+    pub first: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct GetSchemaByIntrospectionRequest {
+    #[schemars(
+        description = "The deployment ID (0x...), IPFS hash (Qm...) or subgraph ID (e.g., 5zvR82...) to introspect"
+    )]
+    pub deployment: String,
+    #[schemars(
+        description = "Optional output format: 'sdl' (default) for the query API as GraphQL SDL, or 'json' for the full introspection result"
+    )]
+    pub format: Option<IntrospectionFormat>,
+}
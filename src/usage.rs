@@ -0,0 +1,227 @@
+// SPDX-License-Identifier: Apache-2.0
+use crate::error::SubgraphError;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    env,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Billable query consumption. Fees are only known when the gateway reports
+/// them, so `fee_reports` tells how many of the counted queries they cover.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct UsageSnapshot {
+    pub query_count: u64,
+    pub estimated_fees_grt: f64,
+    pub fee_reports: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct BudgetLimits {
+    pub max_queries: Option<u64>,
+    pub max_fees_grt: Option<f64>,
+}
+
+impl BudgetLimits {
+    pub fn from_env() -> Self {
+        Self {
+            max_queries: env::var("SESSION_MAX_QUERIES")
+                .ok()
+                .and_then(|s| s.parse::<u64>().ok()),
+            max_fees_grt: env::var("SESSION_MAX_QUERY_FEES_GRT")
+                .ok()
+                .and_then(|s| s.parse::<f64>().ok()),
+        }
+    }
+}
+
+/// Usage of a single MCP session, shared by every clone of its `SubgraphServer`.
+#[derive(Debug)]
+pub struct SessionUsage {
+    session_id: String,
+    limits: BudgetLimits,
+    usage: Mutex<UsageSnapshot>,
+}
+
+impl SessionUsage {
+    pub fn new(limits: BudgetLimits) -> Self {
+        Self {
+            session_id: uuid::Uuid::new_v4().to_string(),
+            limits,
+            usage: Mutex::new(UsageSnapshot::default()),
+        }
+    }
+
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    pub fn limits(&self) -> BudgetLimits {
+        self.limits
+    }
+
+    pub fn snapshot(&self) -> UsageSnapshot {
+        *self.usage.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Counts one billable query against the session, refusing it when the
+    /// session has already reached its query count or fee limit. The query stays
+    /// counted only if the reservation is confirmed once the gateway has answered;
+    /// dropping it, as a failed or cancelled request does, gives it back.
+    pub fn reserve_query(&self) -> Result<QueryReservation<'_>, SubgraphError> {
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(max_queries) = self.limits.max_queries {
+            if usage.query_count >= max_queries {
+                return Err(SubgraphError::BudgetExceeded(format!(
+                    "Session query limit reached: {} of {} billable queries already executed in this session. Start a new session or raise SESSION_MAX_QUERIES to continue.",
+                    usage.query_count, max_queries
+                )));
+            }
+        }
+
+        if let Some(max_fees_grt) = self.limits.max_fees_grt {
+            if usage.estimated_fees_grt >= max_fees_grt {
+                return Err(SubgraphError::BudgetExceeded(format!(
+                    "Session fee limit reached: an estimated {:.6} GRT of {} GRT already spent in this session. Start a new session or raise SESSION_MAX_QUERY_FEES_GRT to continue.",
+                    usage.estimated_fees_grt, max_fees_grt
+                )));
+            }
+        }
+
+        usage.query_count += 1;
+        Ok(QueryReservation {
+            session: self,
+            confirmed: false,
+        })
+    }
+
+    pub fn record_fee(&self, fee_grt: f64) {
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        usage.estimated_fees_grt += fee_grt;
+        usage.fee_reports += 1;
+    }
+}
+
+/// A billable query counted against a session while it is in flight.
+#[derive(Debug)]
+#[must_use = "a reservation is given back when dropped unconfirmed"]
+pub struct QueryReservation<'a> {
+    session: &'a SessionUsage,
+    confirmed: bool,
+}
+
+impl QueryReservation<'_> {
+    /// Keeps the query counted, once the gateway has answered it.
+    pub fn confirm(mut self) {
+        self.confirmed = true;
+    }
+}
+
+impl Drop for QueryReservation<'_> {
+    fn drop(&mut self) {
+        if !self.confirmed {
+            let mut usage = self.session.usage.lock().unwrap_or_else(|e| e.into_inner());
+            usage.query_count = usage.query_count.saturating_sub(1);
+        }
+    }
+}
+
+/// Number of API keys whose usage is kept by default.
+const MAX_TRACKED_KEYS: usize = 10_000;
+/// How long the usage of a key that is no longer used is kept by default.
+const KEY_IDLE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+struct KeyEntry {
+    usage: UsageSnapshot,
+    last_seen: Instant,
+}
+
+/// Process-wide usage per API key, across all sessions. Keys idle for longer
+/// than the TTL are forgotten, and so is the least recently used key once the
+/// map is full, so the map stays bounded however many keys call the server.
+pub struct KeyUsage {
+    usage: Mutex<HashMap<String, KeyEntry>>,
+    max_keys: usize,
+    idle_ttl: Duration,
+}
+
+impl Default for KeyUsage {
+    fn default() -> Self {
+        Self::new(MAX_TRACKED_KEYS, KEY_IDLE_TTL)
+    }
+}
+
+impl KeyUsage {
+    pub fn new(max_keys: usize, idle_ttl: Duration) -> Self {
+        Self {
+            usage: Mutex::new(HashMap::new()),
+            max_keys: max_keys.max(1),
+            idle_ttl,
+        }
+    }
+
+    pub fn record_query(&self, api_key: &str) {
+        self.update(api_key, |usage| usage.query_count += 1);
+    }
+
+    pub fn record_fee(&self, api_key: &str, fee_grt: f64) {
+        self.update(api_key, |usage| {
+            usage.estimated_fees_grt += fee_grt;
+            usage.fee_reports += 1;
+        });
+    }
+
+    pub fn snapshot(&self, api_key: &str) -> UsageSnapshot {
+        let usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        usage
+            .get(api_key)
+            .filter(|entry| entry.last_seen.elapsed() < self.idle_ttl)
+            .map(|entry| entry.usage)
+            .unwrap_or_default()
+    }
+
+    fn update(&self, api_key: &str, f: impl FnOnce(&mut UsageSnapshot)) {
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        if let Some(entry) = usage.get_mut(api_key) {
+            if now.duration_since(entry.last_seen) >= self.idle_ttl {
+                entry.usage = UsageSnapshot::default();
+            }
+            entry.last_seen = now;
+            f(&mut entry.usage);
+            return;
+        }
+
+        if usage.len() >= self.max_keys {
+            usage.retain(|_, entry| now.duration_since(entry.last_seen) < self.idle_ttl);
+        }
+        if usage.len() >= self.max_keys {
+            let oldest = usage
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_seen)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                usage.remove(&oldest);
+            }
+        }
+        let mut entry = KeyEntry {
+            usage: UsageSnapshot::default(),
+            last_seen: now,
+        };
+        f(&mut entry.usage);
+        usage.insert(api_key.to_string(), entry);
+    }
+}
+
+pub static KEY_USAGE: Lazy<KeyUsage> = Lazy::new(KeyUsage::default);
+
+/// Name of the gateway response header carrying the fee charged for a query,
+/// if the configured gateway reports one.
+pub fn fee_header_name() -> Option<String> {
+    env::var("GATEWAY_QUERY_FEE_HEADER")
+        .ok()
+        .filter(|s| !s.is_empty())
+}
@@ -0,0 +1,116 @@
+use serde_json::json;
+use std::{sync::Arc, time::Duration};
+use subgraph_mcp::audit::{
+    hash_api_key, AuditDetails, AuditLogger, AuditedCall, NdjsonFileSink, Redaction,
+};
+use subgraph_mcp::policy::DeploymentTarget;
+
+#[test]
+fn test_query_literal_redaction() {
+    let query =
+        r#"{ swaps(first: 10, where: { origin: "0xabc", amount_gt: -1.5e3 }) { id } } # by alice"#;
+
+    assert_eq!(Redaction::None.redact_query(query), query);
+    assert_eq!(
+        Redaction::Strings.redact_query(query),
+        r#"{ swaps(first: 10, where: { origin: "<redacted>", amount_gt: -1.5e3 }) { id } } "#
+    );
+    assert_eq!(
+        Redaction::All.redact_query(query),
+        r#"{ swaps(first: 0, where: { origin: "<redacted>", amount_gt: 0 }) { id } } "#
+    );
+
+    let variables = json!({ "owner": "0xabc", "first": 5, "ids": ["a", "b"] });
+    assert_eq!(
+        Redaction::Strings.redact_value(&variables),
+        json!({ "owner": "<redacted>", "first": 5, "ids": ["<redacted>", "<redacted>"] })
+    );
+}
+
+#[test]
+fn test_entry_hashes_api_key_and_records_deployment() {
+    let path = std::env::temp_dir().join("subgraph-mcp-audit-unused.ndjson");
+    let logger = AuditLogger::new(
+        Arc::new(NdjsonFileSink::new(path, 1024, 2)),
+        Redaction::Strings,
+    );
+
+    let arguments = json!({
+        "subgraph_id": "5zvR82QoaXYFyDEKLZ9t6v9adgnptxYpKpSbxtgVENFV",
+        "query": "{ tokens(where: { symbol: \"GRT\" }) { id } }",
+    });
+    let entry = logger.entry(AuditedCall {
+        request_id: "req-1",
+        session_id: "session-1",
+        api_key: Some("secret-key"),
+        tool: "execute_query_by_subgraph_id",
+        arguments: arguments.as_object(),
+        details: AuditDetails {
+            deployment: Some(DeploymentTarget {
+                ipfs_hash: Some("QmResolved".to_string()),
+                subgraph_id: Some("5zvR82QoaXYFyDEKLZ9t6v9adgnptxYpKpSbxtgVENFV".to_string()),
+                ..Default::default()
+            }),
+            fee_grt: Some(0.0001),
+        },
+        response_bytes: 42,
+        error: None,
+        latency: Duration::from_millis(15),
+    });
+
+    assert_eq!(
+        entry.api_key_hash.as_deref(),
+        Some(hash_api_key("secret-key").as_str())
+    );
+    assert!(!serde_json::to_string(&entry)
+        .unwrap()
+        .contains("secret-key"));
+    assert_eq!(entry.ipfs_hash.as_deref(), Some("QmResolved"));
+    assert_eq!(
+        entry.query.as_deref(),
+        Some("{ tokens(where: { symbol: \"<redacted>\" }) { id } }")
+    );
+    assert_eq!(entry.status, "success");
+    assert_eq!(entry.latency_ms, 15);
+}
+
+#[test]
+fn test_ndjson_sink_rotates_files() {
+    let dir = std::env::temp_dir().join(format!(
+        "subgraph-mcp-audit-rotation-{}",
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("audit.ndjson");
+    let logger = AuditLogger::new(
+        Arc::new(NdjsonFileSink::new(&path, 400, 2)),
+        Redaction::None,
+    );
+
+    for i in 0..10 {
+        let request_id = format!("req-{}", i);
+        logger.record(AuditedCall {
+            request_id: &request_id,
+            session_id: "session-1",
+            api_key: None,
+            tool: "get_schema_by_ipfs_hash",
+            arguments: None,
+            details: AuditDetails::default(),
+            response_bytes: 0,
+            error: None,
+            latency: Duration::ZERO,
+        });
+    }
+
+    let current = std::fs::read_to_string(&path).unwrap();
+    assert!(current.contains("req-9"));
+    for line in current.lines() {
+        let entry: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert_eq!(entry["tool"], "get_schema_by_ipfs_hash");
+    }
+    assert!(dir.join("audit.ndjson.1").exists());
+    assert!(dir.join("audit.ndjson.2").exists());
+    assert!(!dir.join("audit.ndjson.3").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
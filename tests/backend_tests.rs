@@ -0,0 +1,114 @@
+use rmcp::model::{Extensions, RawContent};
+use serde_json::json;
+use std::sync::Arc;
+use subgraph_mcp::backend::{FixtureBackend, GatewayTarget};
+use subgraph_mcp::server::SubgraphServer;
+use subgraph_mcp::types::{ExecuteQueryByDeploymentIdRequest, GetSchemaBySubgraphIdRequest};
+
+/// Extensions carrying the request parts of an SSE call authenticated with `api_key`.
+fn bearer(api_key: &str) -> Extensions {
+    let (parts, _) = http::Request::builder()
+        .header(http::header::AUTHORIZATION, format!("Bearer {}", api_key))
+        .body(())
+        .unwrap()
+        .into_parts();
+    let mut extensions = Extensions::new();
+    extensions.insert(parts);
+    extensions
+}
+
+fn text(content: &rmcp::model::Content) -> &str {
+    match &content.raw {
+        RawContent::Text(text) => &text.text,
+        _ => panic!("expected text content"),
+    }
+}
+
+#[tokio::test]
+async fn test_schema_tool_served_from_fixture() {
+    let backend = Arc::new(FixtureBackend::new().with_network_subgraph_data(
+        "SubgraphSchema",
+        json!({
+            "subgraph": {
+                "currentVersion": {
+                    "subgraphDeployment": {
+                        "manifest": { "schema": { "schema": "type Pool @entity { id: ID! }" } }
+                    }
+                }
+            }
+        }),
+    ));
+    let server = SubgraphServer::builder().backend(backend.clone()).build();
+
+    let result = server
+        .get_schema_by_subgraph_id(
+            bearer("test-key"),
+            GetSchemaBySubgraphIdRequest {
+                subgraph_id: "5zvR82".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(text(&result.content[0]), "type Pool @entity { id: ID! }");
+
+    let requests = backend.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].0, GatewayTarget::NetworkSubgraph);
+    assert_eq!(requests[0].1.variables, Some(json!({ "id": "5zvR82" })));
+}
+
+#[tokio::test]
+async fn test_query_tool_served_from_fixture() {
+    let backend = Arc::new(FixtureBackend::new().with_deployment_data(
+        "deployments/id",
+        "0xdeadbeef",
+        json!({ "pools": [{ "id": "0x1" }] }),
+    ));
+    let server = SubgraphServer::builder().backend(backend.clone()).build();
+
+    let result = server
+        .execute_query_by_deployment_id(
+            bearer("test-key"),
+            ExecuteQueryByDeploymentIdRequest {
+                deployment_id: "0xdeadbeef".to_string(),
+                query: "{ pools(first: 1) { id } }".to_string(),
+                variables: None,
+                freshness: Some(false),
+                decode: None,
+                transform: None,
+                output_format: None,
+            },
+        )
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_str(text(&result.content[0])).unwrap();
+    assert_eq!(body, json!({ "data": { "pools": [{ "id": "0x1" }] } }));
+
+    let requests = backend.requests();
+    assert_eq!(
+        requests[0].0,
+        GatewayTarget::Deployment {
+            endpoint_type: "deployments/id".to_string(),
+            id: "0xdeadbeef".to_string(),
+        }
+    );
+    assert_eq!(requests[0].1.query, "{ pools(first: 1) { id } }");
+}
+
+#[tokio::test]
+async fn test_unmatched_request_fails() {
+    let backend = Arc::new(FixtureBackend::new());
+    let server = SubgraphServer::builder().backend(backend.clone()).build();
+
+    let err = server
+        .get_schema_by_subgraph_id(
+            bearer("test-key"),
+            GetSchemaBySubgraphIdRequest {
+                subgraph_id: "5zvR82".to_string(),
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("No fixture for NetworkSubgraph"));
+    assert_eq!(backend.requests().len(), 1);
+}
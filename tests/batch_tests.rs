@@ -0,0 +1,178 @@
+use rmcp::model::{CallToolResult, Extensions, RawContent};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use subgraph_mcp::audit::{AuditEntry, AuditLogger, AuditSink, Redaction};
+use subgraph_mcp::backend::FixtureBackend;
+use subgraph_mcp::batch::BatchLimits;
+use subgraph_mcp::error::SubgraphError;
+use subgraph_mcp::metrics::{BatchItemLabels, METRICS};
+use subgraph_mcp::rate_limit::{BudgetConfig, RateLimitConfig, RateLimiter, ToolClass};
+use subgraph_mcp::server::SubgraphServer;
+use subgraph_mcp::types::{BatchQuery, ExecuteQueriesBatchRequest};
+
+const UNISWAP_SUBGRAPH: &str = "5zvR82QoaXYFyDEKLZ9t6v9adgnptxYpKpSbxtgVENFV";
+const UNISWAP_DEPLOYMENT: &str = "QmTZ8ejXJxRo7vDBS4uwqBeGoxLSWbhaA7oXa1RvxunLy7";
+
+#[derive(Default)]
+struct MemorySink(Mutex<Vec<AuditEntry>>);
+
+impl AuditSink for MemorySink {
+    fn record(&self, entry: &AuditEntry) -> Result<(), SubgraphError> {
+        self.0.lock().unwrap().push(entry.clone());
+        Ok(())
+    }
+}
+
+fn query(deployment: &str, query: &str) -> BatchQuery {
+    BatchQuery {
+        deployment: deployment.to_string(),
+        query: query.to_string(),
+        variables: None,
+    }
+}
+
+fn body(result: &CallToolResult) -> Value {
+    let RawContent::Text(text) = &result.content[0].raw else {
+        panic!("expected text content");
+    };
+    serde_json::from_str(&text.text).unwrap()
+}
+
+#[tokio::test]
+async fn test_batch_returns_per_item_results() {
+    let backend = Arc::new(
+        FixtureBackend::new()
+            .with_deployment_data(
+                "deployments/id",
+                UNISWAP_DEPLOYMENT,
+                json!({ "pools": [{ "id": "0x1" }] }),
+            )
+            .with_deployment_data(
+                "subgraphs/id",
+                UNISWAP_SUBGRAPH,
+                json!({ "tokens": [{ "id": "0x2" }] }),
+            ),
+    );
+    let sink = Arc::new(MemorySink::default());
+    let server = SubgraphServer::builder()
+        .backend(backend.clone())
+        .api_key("batch-test-key")
+        .audit_logger(Arc::new(AuditLogger::new(sink.clone(), Redaction::None)))
+        .build();
+
+    let result = server
+        .execute_queries_batch(
+            Extensions::new(),
+            ExecuteQueriesBatchRequest {
+                queries: vec![
+                    query(UNISWAP_DEPLOYMENT, "{ pools(first: 1) { id } }"),
+                    query("QmMissing", "{ pools(first: 1) { id } }"),
+                    query(UNISWAP_SUBGRAPH, "{ tokens(first: 1) { id } }"),
+                    query(UNISWAP_SUBGRAPH, "{ tokens {"),
+                ],
+                max_concurrency: Some(2),
+                freshness: Some(false),
+            },
+        )
+        .await
+        .unwrap();
+    let body = body(&result);
+    assert_eq!(body["succeeded"], 2);
+    assert_eq!(body["failed"], 2);
+
+    let results = body["results"].as_array().unwrap();
+    assert_eq!(
+        results[0],
+        json!({ "index": 0, "deployment": UNISWAP_DEPLOYMENT, "data": { "pools": [{ "id": "0x1" }] } })
+    );
+    assert_eq!(results[1]["deployment"], "QmMissing");
+    assert!(results[1]["error"]["message"].is_string());
+    assert_eq!(results[2]["data"], json!({ "tokens": [{ "id": "0x2" }] }));
+    assert_eq!(results[3]["error"]["kind"], "query_rejected");
+    // The rejected query never reached the gateway
+    assert_eq!(backend.requests().len(), 3);
+
+    let succeeded = METRICS
+        .batch_items_total
+        .get_or_create(&BatchItemLabels {
+            status: "success".to_string(),
+            error_kind: "none".to_string(),
+            api_key: "batch-test-key".to_string(),
+        })
+        .get();
+    assert_eq!(succeeded, 2);
+
+    let entries = sink.0.lock().unwrap();
+    assert_eq!(entries.len(), 4);
+    assert!(entries.iter().all(|e| e.tool == "execute_queries_batch"));
+    let rejected = entries
+        .iter()
+        .find(|e| e.query.as_deref() == Some("{ tokens {"))
+        .unwrap();
+    assert_eq!(rejected.deployment.as_deref(), Some(UNISWAP_SUBGRAPH));
+    assert_eq!(rejected.status, "error");
+}
+
+#[tokio::test]
+async fn test_batch_size_limits() {
+    let server = SubgraphServer::builder()
+        .backend(Arc::new(FixtureBackend::new()))
+        .api_key("test-key")
+        .batch_limits(BatchLimits {
+            max_queries: 2,
+            max_concurrency: 2,
+        })
+        .build();
+    let request = |count: usize| ExecuteQueriesBatchRequest {
+        queries: (0..count)
+            .map(|_| query(UNISWAP_DEPLOYMENT, "{ pools { id } }"))
+            .collect(),
+        max_concurrency: None,
+        freshness: None,
+    };
+
+    let err = server
+        .execute_queries_batch(Extensions::new(), request(0))
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("no queries"));
+
+    let err = server
+        .execute_queries_batch(Extensions::new(), request(3))
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("more than the limit of 2"));
+    assert_eq!(err.data, Some(json!({ "max_queries": 2 })));
+}
+
+#[test]
+fn test_batch_counts_each_query_against_rate_limit() {
+    assert_eq!(
+        ToolClass::for_tool("execute_queries_batch"),
+        ToolClass::Query
+    );
+
+    let budget = BudgetConfig {
+        per_minute: 60.0,
+        burst: 10.0,
+        max_in_flight: 4,
+    };
+    let limiter = RateLimiter::new(RateLimitConfig {
+        query: budget,
+        metadata: budget,
+    });
+    drop(
+        limiter
+            .acquire_weighted("key", ToolClass::Query, 8)
+            .unwrap(),
+    );
+    assert!(limiter
+        .acquire_weighted("key", ToolClass::Query, 4)
+        .is_err());
+    assert!(limiter.acquire("key", ToolClass::Query).is_ok());
+
+    // Batches larger than the burst can still pass once the bucket is full
+    assert!(limiter
+        .acquire_weighted("other-key", ToolClass::Query, 50)
+        .is_ok());
+}
@@ -0,0 +1,184 @@
+use serde_json::{json, Value};
+use std::sync::Arc;
+use subgraph_mcp::cli::{self, Command, OutputFormat};
+use subgraph_mcp::error::SubgraphError;
+use subgraph_mcp::policy::{DeploymentPolicy, DeploymentRef, PolicyRules, PolicyStore};
+use subgraph_mcp::recording::ReplayBackend;
+use subgraph_mcp::server::SubgraphServer;
+
+const UNISWAP_SUBGRAPH: &str = "5zvR82QoaXYFyDEKLZ9t6v9adgnptxYpKpSbxtgVENFV";
+const UNISWAP_DEPLOYMENT: &str = "QmTZ8ejXJxRo7vDBS4uwqBeGoxLSWbhaA7oXa1RvxunLy7";
+const UNISWAP_ARBITRUM_DEPLOYMENT: &str = "QmZ5uwhnwsJXAQGYEF8qKPQ85iVhYAcVZcZAPfrF7ZNb9z";
+
+fn replay_server() -> SubgraphServer {
+    let backend = ReplayBackend::from_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/gateway_replay.ndjson"
+    ))
+    .unwrap();
+    SubgraphServer::builder()
+        .backend(Arc::new(backend))
+        .api_key("replay-key")
+        .build()
+}
+
+fn write_query(name: &str, query: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("{}-{}.graphql", name, std::process::id()));
+    std::fs::write(&path, query).unwrap();
+    path
+}
+
+#[test]
+fn test_deployment_ref_and_exit_codes() {
+    assert!(matches!(
+        DeploymentRef::parse("0xabc"),
+        DeploymentRef::DeploymentId("0xabc")
+    ));
+    assert!(matches!(
+        DeploymentRef::parse(UNISWAP_DEPLOYMENT),
+        DeploymentRef::IpfsHash(UNISWAP_DEPLOYMENT)
+    ));
+    assert!(matches!(
+        DeploymentRef::parse(UNISWAP_SUBGRAPH),
+        DeploymentRef::SubgraphId(UNISWAP_SUBGRAPH)
+    ));
+
+    assert_eq!(cli::exit_code(&SubgraphError::ApiKeyNotSet), 3);
+    assert_eq!(cli::exit_code(&SubgraphError::HttpStatus(502)), 5);
+    assert_eq!(
+        cli::exit_code(&SubgraphError::GraphQlError("bad".to_string())),
+        6
+    );
+    assert_eq!(
+        cli::exit_code(&SubgraphError::PolicyViolation("denied".to_string())),
+        10
+    );
+}
+
+#[tokio::test]
+async fn test_commands_replay_in_both_formats() {
+    let server = replay_server();
+
+    let search = cli::run(
+        &server,
+        Command::Search {
+            keyword: "uniswap".to_string(),
+        },
+        None,
+        OutputFormat::Json,
+    )
+    .await
+    .unwrap();
+    let search: Value = serde_json::from_str(&search).unwrap();
+    assert_eq!(search["total"], 4);
+
+    let file = write_query(
+        "cli-pools",
+        "{ pools(first: 2, orderBy: totalValueLockedUSD, orderDirection: desc) { id feeTier totalValueLockedUSD } }",
+    );
+    let table = cli::run(
+        &server,
+        Command::Query {
+            id: UNISWAP_SUBGRAPH.to_string(),
+            file: file.clone(),
+            vars: None,
+        },
+        None,
+        OutputFormat::Table,
+    )
+    .await
+    .unwrap();
+    std::fs::remove_file(file).unwrap();
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(lines[0], "pools");
+    assert!(lines[1].starts_with("feeTier"));
+    assert!(lines[1].contains("totalValueLockedUSD"));
+    // The recorded block is long past, so a staleness warning follows the rows
+    assert_eq!(lines.len(), 6);
+    assert!(lines[5].starts_with("warning: The deployment's latest indexed block is"));
+
+    let counts = cli::run(
+        &server,
+        Command::Counts {
+            ipfs_hashes: vec![
+                UNISWAP_ARBITRUM_DEPLOYMENT.to_string(),
+                UNISWAP_DEPLOYMENT.to_string(),
+            ],
+        },
+        None,
+        OutputFormat::Table,
+    )
+    .await
+    .unwrap();
+    assert!(counts
+        .lines()
+        .next()
+        .unwrap()
+        .starts_with("data_points_count"));
+}
+
+#[tokio::test]
+async fn test_command_errors_keep_their_variant() {
+    let server = replay_server();
+
+    let file = write_query("cli-bad", "{ pool(id: $id) { id } }");
+    let err = cli::run(
+        &server,
+        Command::Query {
+            id: UNISWAP_SUBGRAPH.to_string(),
+            file: file.clone(),
+            vars: None,
+        },
+        None,
+        OutputFormat::Json,
+    )
+    .await
+    .unwrap_err();
+    std::fs::remove_file(file).unwrap();
+    assert!(matches!(err, SubgraphError::GraphQlError(_)));
+    assert_eq!(cli::exit_code(&err), 6);
+
+    let err = cli::run(
+        &server,
+        Command::Schema {
+            id: UNISWAP_DEPLOYMENT.to_string(),
+        },
+        Some("nope"),
+        OutputFormat::Json,
+    )
+    .await
+    .unwrap_err();
+    assert!(matches!(err, SubgraphError::InvalidGatewayId(_)));
+
+    // Schemas are subject to the deployment policy like the `get_schema_*` tools
+    let backend = ReplayBackend::from_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/gateway_replay.ndjson"
+    ))
+    .unwrap();
+    let denied = SubgraphServer::builder()
+        .backend(Arc::new(backend))
+        .api_key("replay-key")
+        .deployment_policy(Arc::new(PolicyStore::from_policy(DeploymentPolicy {
+            deny: PolicyRules {
+                ipfs_hashes: vec![UNISWAP_DEPLOYMENT.to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        })))
+        .build();
+    let err = cli::run(
+        &denied,
+        Command::Schema {
+            id: UNISWAP_DEPLOYMENT.to_string(),
+        },
+        None,
+        OutputFormat::Table,
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(cli::exit_code(&err), 10);
+
+    let table = cli::render_table(&[json!({"id": "a", "nested": {"n": 1}}), json!({"id": "bb"})]);
+    assert_eq!(table, "id  nested.n\na   1\nbb");
+}
@@ -0,0 +1,237 @@
+use rmcp::model::{Extensions, RawContent};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use subgraph_mcp::backend::FixtureBackend;
+use subgraph_mcp::cross_chain::{
+    check_compatibility, merge_collections, required_fields, schema_hash, OrderDirection,
+    RequiredField,
+};
+use subgraph_mcp::policy::{DeploymentPolicy, PolicyRules, PolicyStore};
+use subgraph_mcp::schema_summary::summarize_schema;
+use subgraph_mcp::server::SubgraphServer;
+use subgraph_mcp::types::QueryAcrossChainsRequest;
+
+const POOLS_SCHEMA: &str = r#"
+type Pool @entity {
+  id: ID!
+  totalValueLockedUSD: BigDecimal!
+  token0: Token!
+}
+
+type Token @entity {
+  id: ID!
+  symbol: String!
+}
+"#;
+
+const POOLS_SCHEMA_REFORMATTED: &str =
+    "type Pool @entity { id: ID! totalValueLockedUSD: BigDecimal! token0: Token! }\ntype Token @entity { id: ID! symbol: String! }";
+
+const PAIRS_SCHEMA: &str = r#"
+type Pair @entity {
+  id: ID!
+  reserveUSD: BigDecimal!
+}
+"#;
+
+const POOLS_QUERY: &str =
+    "{ pools(first: 2) { id totalValueLockedUSD } _meta { block { number } } }";
+
+fn subgraph(id: &str, network: &str, ipfs_hash: &str, schema: &str) -> Value {
+    json!({
+        "id": id,
+        "metadata": { "displayName": "Uniswap V3" },
+        "currentVersion": {
+            "subgraphDeployment": {
+                "ipfsHash": ipfs_hash,
+                "manifest": { "network": network, "schema": { "schema": schema } },
+                "versions": [{ "subgraph": { "id": id } }],
+            }
+        }
+    })
+}
+
+#[test]
+fn test_required_fields_and_compatibility() {
+    let required = required_fields(POOLS_QUERY).unwrap();
+    assert_eq!(
+        required,
+        vec![RequiredField {
+            name: "pools".to_string(),
+            selected: vec!["id".to_string(), "totalValueLockedUSD".to_string()],
+        }]
+    );
+    assert!(required_fields("{ pools {").is_err());
+
+    let pools = summarize_schema(POOLS_SCHEMA).unwrap();
+    assert!(check_compatibility(&pools, &required).is_ok());
+    let token = required_fields("{ token(id: \"0x1\") { symbol decimals } }").unwrap();
+    assert_eq!(
+        check_compatibility(&pools, &token).unwrap_err(),
+        "The `Token` entity has no `decimals` field"
+    );
+    let pairs = summarize_schema(PAIRS_SCHEMA).unwrap();
+    assert_eq!(
+        check_compatibility(&pairs, &required).unwrap_err(),
+        "The schema has no `pools` query field"
+    );
+
+    assert_eq!(
+        schema_hash(POOLS_SCHEMA),
+        schema_hash(POOLS_SCHEMA_REFORMATTED)
+    );
+    assert_ne!(schema_hash(POOLS_SCHEMA), schema_hash(PAIRS_SCHEMA));
+}
+
+#[test]
+fn test_merge_collections() {
+    let mainnet = json!({ "pools": [{ "id": "a", "tvl": "900.5" }, { "id": "b" }], "_meta": {} });
+    let arbitrum = json!({ "pools": [{ "id": "c", "tvl": "10000" }, { "id": "d", "tvl": 3 }] });
+    let chains = [
+        ("mainnet".to_string(), &mainnet),
+        ("arbitrum-one".to_string(), &arbitrum),
+    ];
+
+    let merged = merge_collections(&chains, Some("tvl"), OrderDirection::Desc);
+    assert_eq!(merged.keys().collect::<Vec<_>>(), vec!["pools"]);
+    let ids: Vec<&str> = merged["pools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|row| row["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(ids, vec!["c", "a", "d", "b"]);
+    assert_eq!(merged["pools"][0]["_network"], "arbitrum-one");
+
+    let merged = merge_collections(&chains, Some("tvl"), OrderDirection::Asc);
+    assert_eq!(merged["pools"][0]["id"], "d");
+    let merged = merge_collections(&chains, None, OrderDirection::Desc);
+    assert_eq!(merged["pools"][0]["id"], "a");
+}
+
+#[tokio::test]
+async fn test_query_across_chains_tool() {
+    let backend = Arc::new(
+        FixtureBackend::new()
+            .with_network_subgraph_data(
+                "SubgraphsWithSchemas",
+                json!({ "subgraphs": [
+                    subgraph("sg-arbitrum", "arbitrum-one", "QmArbitrum", POOLS_SCHEMA_REFORMATTED),
+                    subgraph("sg-mainnet", "mainnet", "QmMainnet", POOLS_SCHEMA),
+                    subgraph("sg-v2", "mainnet", "QmV2", PAIRS_SCHEMA),
+                ] }),
+            )
+            .with_deployment_data(
+                "deployments/id",
+                "QmMainnet",
+                json!({ "pools": [{ "id": "a", "totalValueLockedUSD": "500" }] }),
+            )
+            .with_deployment_data(
+                "deployments/id",
+                "QmArbitrum",
+                json!({ "pools": [{ "id": "b", "totalValueLockedUSD": "700" }] }),
+            ),
+    );
+    let server = SubgraphServer::builder()
+        .backend(backend.clone())
+        .api_key("test-key")
+        .build();
+    let request =
+        |subgraph_ids: Option<Vec<&str>>, keyword: Option<&str>| QueryAcrossChainsRequest {
+            keyword: keyword.map(str::to_string),
+            subgraph_ids: subgraph_ids.map(|ids| ids.into_iter().map(str::to_string).collect()),
+            query: "{ pools(first: 1) { id totalValueLockedUSD } }".to_string(),
+            variables: None,
+            merge: Some(true),
+            order_by: Some("totalValueLockedUSD".to_string()),
+            order_direction: None,
+            freshness: Some(false),
+        };
+
+    let result = server
+        .query_across_chains(
+            Extensions::new(),
+            request(
+                Some(vec!["sg-mainnet", "sg-arbitrum", "sg-v2", "sg-gone"]),
+                None,
+            ),
+        )
+        .await
+        .unwrap();
+    let RawContent::Text(text) = &result.content[0].raw else {
+        panic!("expected text content");
+    };
+    let body: Value = serde_json::from_str(&text.text).unwrap();
+
+    assert_eq!(body["succeeded"], 2);
+    let deployments = body["deployments"].as_array().unwrap();
+    assert_eq!(deployments[0]["subgraph_id"], "sg-mainnet");
+    assert_eq!(deployments[0]["network"], "mainnet");
+    assert_eq!(deployments[1]["network"], "arbitrum-one");
+    assert_eq!(deployments[0]["schema_hash"], deployments[1]["schema_hash"]);
+    assert_eq!(
+        deployments[1]["data"],
+        json!({ "pools": [{ "id": "b", "totalValueLockedUSD": "700" }] })
+    );
+
+    let skipped = body["skipped"].as_array().unwrap();
+    assert_eq!(skipped.len(), 2);
+    assert_eq!(skipped[0]["subgraph_id"], "sg-gone");
+    assert_eq!(skipped[1]["subgraph_id"], "sg-v2");
+    assert_eq!(
+        skipped[1]["reason"],
+        "The schema has no `pools` query field"
+    );
+
+    assert_eq!(
+        body["merged"]["pools"],
+        json!([
+            { "id": "b", "totalValueLockedUSD": "700", "_network": "arbitrum-one" },
+            { "id": "a", "totalValueLockedUSD": "500", "_network": "mainnet" },
+        ])
+    );
+    // The incompatible deployment was never queried
+    assert_eq!(backend.requests().len(), 3);
+
+    let err = server
+        .query_across_chains(
+            Extensions::new(),
+            request(Some(vec!["sg-mainnet"]), Some("Uniswap")),
+        )
+        .await
+        .unwrap_err();
+    assert!(err
+        .message
+        .contains("either a keyword or a list of subgraph_ids"));
+
+    // Subgraph rules apply to each chain's deployment without looking it up again
+    let denied = SubgraphServer::builder()
+        .backend(backend.clone())
+        .api_key("test-key")
+        .deployment_policy(Arc::new(PolicyStore::from_policy(DeploymentPolicy {
+            deny: PolicyRules {
+                subgraph_ids: vec!["sg-arbitrum".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        })))
+        .build();
+    let sent = backend.requests().len();
+    let result = denied
+        .query_across_chains(
+            Extensions::new(),
+            request(Some(vec!["sg-mainnet", "sg-arbitrum"]), None),
+        )
+        .await
+        .unwrap();
+    let RawContent::Text(text) = &result.content[0].raw else {
+        panic!("expected text content");
+    };
+    let body: Value = serde_json::from_str(&text.text).unwrap();
+    assert_eq!(body["succeeded"], 1);
+    assert_eq!(body["deployments"][1]["subgraph_id"], "sg-arbitrum");
+    assert!(body["deployments"][1]["error"]
+        .to_string()
+        .contains("denied"));
+    assert_eq!(backend.requests().len(), sent + 2);
+}
@@ -0,0 +1,211 @@
+use rmcp::model::{Extensions, RawContent};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use subgraph_mcp::backend::FixtureBackend;
+use subgraph_mcp::decode::{DecodeSpec, ResultTypes};
+use subgraph_mcp::schema_summary::summarize_schema;
+use subgraph_mcp::server::SubgraphServer;
+use subgraph_mcp::types::ExecuteQueryByDeploymentIdRequest;
+
+const DEPLOYMENT_ID: &str = "0xae9e4f9fb2d5b3ff4b9ab2ce87e0b4d5f1c9d2a3b4c5d6e7f8091a2b3c4d5e6f";
+
+const SCHEMA: &str = r#"
+type Transfer @entity {
+  id: ID!
+  value: BigInt!
+  blockNumber: BigInt!
+  token: Token!
+}
+
+type Token @entity {
+  id: ID!
+  symbol: String!
+  name: String!
+  decimals: Int!
+  totalSupply: BigInt!
+  volume: BigDecimal!
+}
+
+type Swap @entity {
+  id: ID!
+  amount0: BigInt!
+  amount1: BigInt!
+  sqrtPriceX96: BigDecimal!
+  token0: Token!
+  token1: Token!
+}
+"#;
+
+fn decode(spec: Value, query: &str, data: Value) -> Value {
+    let types = ResultTypes::new(query, &summarize_schema(SCHEMA).unwrap());
+    serde_json::from_value::<DecodeSpec>(spec)
+        .unwrap()
+        .apply(json!({ "data": data }), &types)["data"]
+        .clone()
+}
+
+#[test]
+fn test_amounts_scaled_by_decimals() {
+    let data = decode(
+        json!({}),
+        "{ transfers { id value blockNumber token { symbol decimals totalSupply } } \
+           swaps { amount0 amount1 sqrtPriceX96 token0 { decimals } token1 { decimals } } }",
+        json!({
+            "transfers": [{
+                "id": "0x1",
+                "value": "1500000",
+                "blockNumber": "19000000",
+                "token": { "symbol": "USDC", "decimals": 6, "totalSupply": "25000000000000000" },
+            }],
+            "swaps": [{
+                "amount0": "-340282366920938463463374607431768211455",
+                "amount1": "42",
+                "sqrtPriceX96": "1.5e21",
+                "token0": { "decimals": "18" },
+                "token1": { "decimals": "6" },
+            }],
+        }),
+    );
+    let transfer = &data["transfers"][0];
+    assert_eq!(transfer["value"], "1500000");
+    assert_eq!(transfer["value_decoded"], "1.5");
+    assert_eq!(transfer["token"]["totalSupply_decoded"], "25000000000");
+    assert!(transfer.get("blockNumber_decoded").is_none());
+    assert!(transfer.get("id_decoded").is_none());
+
+    // A swap relates two tokens, so its amounts are left alone without hints
+    let swap = &data["swaps"][0];
+    assert!(swap.get("amount0_decoded").is_none());
+    assert_eq!(swap["sqrtPriceX96_decoded"], "1500000000000000000000");
+
+    let data = decode(
+        json!({ "decimals": { "amount0": "token0.decimals", "amount1": 2 } }),
+        "{ swaps { amount0 amount1 token0 { decimals } token1 { decimals } } }",
+        json!({ "swaps": [{
+            "amount0": "-340282366920938463463374607431768211455",
+            "amount1": 42,
+            "token0": { "decimals": "18" },
+            "token1": { "decimals": "6" },
+        }] }),
+    );
+    assert_eq!(
+        data["swaps"][0]["amount0_decoded"],
+        "-340282366920938463463.374607431768211455"
+    );
+    assert_eq!(data["swaps"][0]["amount1_decoded"], "0.42");
+}
+
+#[test]
+fn test_timestamps_as_iso() {
+    let data = decode(
+        json!({}),
+        "{ events { timestamp createdAt blockTimestamp hourStartTimestamp updatedAt value } }",
+        json!({ "events": [{
+            "timestamp": "1700000000",
+            "createdAt": 1700000000123i64,
+            "blockTimestamp": "0x6553f100",
+            "hourStartTimestamp": "1700000000000000",
+            "updatedAt": "12",
+            "value": "100",
+        }] }),
+    );
+    let event = &data["events"][0];
+    assert_eq!(event["timestamp"], "1700000000");
+    assert_eq!(event["timestamp_iso"], "2023-11-14T22:13:20Z");
+    assert_eq!(event["createdAt_iso"], "2023-11-14T22:13:20.123Z");
+    assert_eq!(event["blockTimestamp_iso"], "2023-11-14T22:13:20Z");
+    assert_eq!(event["hourStartTimestamp_iso"], "2023-11-14T22:13:20Z");
+    assert!(event.get("updatedAt_iso").is_none());
+    assert!(event.get("value_decoded").is_none());
+
+    let data = decode(
+        json!({ "timestamps": false }),
+        "{ block { timestamp } }",
+        json!({ "block": { "timestamp": "1700000000" } }),
+    );
+    assert_eq!(data, json!({ "block": { "timestamp": "1700000000" } }));
+}
+
+#[test]
+fn test_decoding_follows_schema_types() {
+    let data = decode(
+        json!({}),
+        "{ tokens { symbol name decimals volume supply: totalSupply } }",
+        json!({ "tokens": [{
+            "symbol": "1e999999999",
+            "name": "2E5",
+            "decimals": 18,
+            "volume": "1500",
+            "supply": "3000000000000000000",
+        }] }),
+    );
+    let token = &data["tokens"][0];
+    assert_eq!(token["supply_decoded"], "3");
+    // Free text is never read as a number, and `BigDecimal` values are already scaled
+    assert!(token.get("symbol_decoded").is_none());
+    assert!(token.get("name_decoded").is_none());
+    assert!(token.get("volume_decoded").is_none());
+
+    let data = decode(
+        json!({}),
+        "{ swaps { ...prices } } fragment prices on Swap { price: sqrtPriceX96 huge: sqrtPriceX96 }",
+        json!({ "swaps": [{ "price": "2.5e3", "huge": "1e999999999" }] }),
+    );
+    assert_eq!(data["swaps"][0]["price_decoded"], "2500");
+    assert!(data["swaps"][0].get("huge_decoded").is_none());
+}
+
+#[tokio::test]
+async fn test_query_tool_decodes_before_transform() {
+    let backend = Arc::new(
+        FixtureBackend::new()
+            .with_network_subgraph_data(
+                "SubgraphDeploymentSchema",
+                json!({ "subgraphDeployment": { "manifest": { "schema": { "schema": SCHEMA } } } }),
+            )
+            .with_deployment_data(
+                "deployments/id",
+                DEPLOYMENT_ID,
+                json!({ "transfers": [
+                    { "value": "2000000000000000000", "token": { "decimals": 18 } },
+                    { "value": "500000000000000000", "token": { "decimals": 18 } },
+                ] }),
+            ),
+    );
+    let server = SubgraphServer::builder()
+        .backend(backend)
+        .api_key("test-key")
+        .build();
+
+    let result = server
+        .execute_query_by_deployment_id(
+            Extensions::new(),
+            ExecuteQueryByDeploymentIdRequest {
+                deployment_id: DEPLOYMENT_ID.to_string(),
+                query: "{ transfers { value token { decimals } } }".to_string(),
+                variables: None,
+                freshness: Some(false),
+                decode: Some(DecodeSpec::default()),
+                transform: Some(
+                    serde_json::from_value(json!({
+                        "aggregate": [
+                            { "op": "sum", "field": "value_decoded", "as": "total" },
+                            { "op": "sum", "field": "value" },
+                        ]
+                    }))
+                    .unwrap(),
+                ),
+                output_format: None,
+            },
+        )
+        .await
+        .unwrap();
+    let RawContent::Text(text) = &result.content[0].raw else {
+        panic!("expected text content");
+    };
+    let body: Value = serde_json::from_str(&text.text).unwrap();
+    assert_eq!(
+        body["data"],
+        json!([{ "total": "2.5", "sum_value": "2500000000000000000" }])
+    );
+}
@@ -0,0 +1,160 @@
+use rmcp::model::{Extensions, RawContent};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use subgraph_mcp::backend::FixtureBackend;
+use subgraph_mcp::example_queries::generate_example_queries;
+use subgraph_mcp::query_analysis::{analyze_query, QueryLimits};
+use subgraph_mcp::schema_summary::summarize_schema;
+use subgraph_mcp::server::SubgraphServer;
+use subgraph_mcp::types::GenerateExampleQueriesRequest;
+
+const SCHEMA: &str = r#"
+enum PoolType {
+  Stable
+  Weighted
+}
+
+type Token @entity {
+  id: ID!
+  symbol: String!
+  pools: [Pool!]! @derivedFrom(field: "token0")
+}
+
+type Pool @entity {
+  id: ID!
+  poolType: PoolType!
+  token0: Token!
+  swaps: [Swap!]! @derivedFrom(field: "pool")
+  createdAtBlockNumber: BigInt!
+}
+
+type Swap @entity(immutable: true) {
+  id: Bytes!
+  pool: Pool!
+  amountUSD: BigDecimal!
+  timestamp: BigInt!
+}
+
+type PriceStats @aggregation(intervals: ["hour", "day"], source: "Price") {
+  id: Int8!
+  timestamp: Timestamp!
+  token: Token!
+  avgPrice: BigDecimal!
+}
+"#;
+
+#[test]
+fn test_examples_for_entity() {
+    let summary = summarize_schema(SCHEMA).unwrap();
+    let examples = json!(generate_example_queries(&summary, Some("Pool"), 20).unwrap());
+    assert_eq!(examples["entity"], "Pool");
+
+    let queries = examples["queries"].as_array().unwrap();
+    let names: Vec<&str> = queries
+        .iter()
+        .map(|q| q["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        ["LatestPools", "PoolById", "FilteredPools", "PoolsWithSwaps"]
+    );
+
+    let latest = queries[0]["query"].as_str().unwrap();
+    assert!(latest
+        .contains("pools(first: $first, orderBy: createdAtBlockNumber, orderDirection: desc)"));
+    assert_eq!(queries[0]["variables"], json!({ "first": 20 }));
+    assert_eq!(queries[0]["variable_types"], json!({ "first": "Int!" }));
+
+    let filtered = &queries[2];
+    assert!(filtered["query"]
+        .as_str()
+        .unwrap()
+        .contains("where: { poolType: $poolType }"));
+    assert_eq!(filtered["variables"]["poolType"], "Stable");
+    assert_eq!(filtered["variable_types"]["poolType"], "PoolType!");
+
+    let traversal = queries[3]["query"].as_str().unwrap();
+    assert!(traversal.contains("swaps(first: 5) {"));
+    assert!(traversal.contains("amountUSD"));
+
+    // Every example must pass the server's own query checks
+    for query in queries {
+        let analysis =
+            analyze_query(query["query"].as_str().unwrap(), Some(&query["variables"])).unwrap();
+        QueryLimits::default().check(&analysis).unwrap();
+    }
+}
+
+#[test]
+fn test_examples_for_aggregation_and_defaults() {
+    let summary = summarize_schema(SCHEMA).unwrap();
+
+    let stats = json!(generate_example_queries(&summary, Some("PriceStats"), 10).unwrap());
+    let queries = stats["queries"].as_array().unwrap();
+    assert!(queries.iter().all(|q| q["name"] != "PriceStatsById"));
+    assert!(queries[0]["query"]
+        .as_str()
+        .unwrap()
+        .contains("priceStatses(interval: $interval, first: $first, orderBy: timestamp"));
+    assert_eq!(queries[0]["variables"]["interval"], "hour");
+
+    let swap = json!(generate_example_queries(&summary, Some("Swap"), 10).unwrap());
+    assert_eq!(swap["queries"][1]["variables"], json!({ "id": "0x" }));
+    assert!(swap["queries"][2]["query"]
+        .as_str()
+        .unwrap()
+        .contains("where: { amountUSD_gt: $amountUSDGt }"));
+
+    let default = generate_example_queries(&summary, None, 10).unwrap();
+    assert_eq!(default.entity, "Pool");
+
+    let err = generate_example_queries(&summary, Some("Missing"), 10).unwrap_err();
+    assert!(err.to_string().contains("Available entities: Token, Pool"));
+
+    // `first` stays within graph-node's page size
+    let large = json!(generate_example_queries(&summary, Some("Pool"), 5000).unwrap());
+    assert_eq!(large["queries"][0]["variables"]["first"], 1000);
+    let zero = json!(generate_example_queries(&summary, Some("Pool"), 0).unwrap());
+    assert_eq!(zero["queries"][0]["variables"]["first"], 1);
+
+    // A hash is no recency field, even with `block` in its name
+    let hashes = summarize_schema(
+        "type Transfer @entity { id: ID! blockHash: Bytes! transactionHash: Bytes! }",
+    )
+    .unwrap();
+    let transfers = json!(generate_example_queries(&hashes, None, 10).unwrap());
+    assert!(transfers["queries"][0]["query"]
+        .as_str()
+        .unwrap()
+        .contains("orderBy: id,"));
+}
+
+#[tokio::test]
+async fn test_generate_example_queries_tool() {
+    let backend = Arc::new(FixtureBackend::new().with_network_subgraph_data(
+        "SubgraphSchema",
+        json!({ "subgraph": { "currentVersion": { "subgraphDeployment": { "manifest": { "schema": { "schema": SCHEMA } } } } } }),
+    ));
+    let server = SubgraphServer::builder()
+        .backend(backend)
+        .api_key("test-key")
+        .build();
+
+    let result = server
+        .generate_example_queries(
+            Extensions::new(),
+            GenerateExampleQueriesRequest {
+                deployment: "5zvR82QoaXYFyDEKLZ9t6v9adgnptxYpKpSbxtgVENFV".to_string(),
+                entity: Some("Swap".to_string()),
+                first: None,
+            },
+        )
+        .await
+        .unwrap();
+    let RawContent::Text(text) = &result.content[0].raw else {
+        panic!("expected text content");
+    };
+    let examples: Value = serde_json::from_str(&text.text).unwrap();
+    assert_eq!(examples["entity"], "Swap");
+    assert_eq!(examples["queries"][0]["variables"]["first"], 10);
+}
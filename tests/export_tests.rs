@@ -0,0 +1,218 @@
+use rmcp::model::{Extensions, RawContent};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::Arc;
+use subgraph_mcp::backend::{FixtureBackend, GatewayResponse, GatewayTarget};
+use subgraph_mcp::export::{
+    resolve_export_path, Column, ColumnKind, ExportFormat, ExportSettings, PagedQuery,
+};
+use subgraph_mcp::schema_summary::summarize_schema;
+use subgraph_mcp::server::SubgraphServer;
+use subgraph_mcp::types::ExportQueryRequest;
+
+const UNISWAP_SUBGRAPH: &str = "5zvR82QoaXYFyDEKLZ9t6v9adgnptxYpKpSbxtgVENFV";
+
+const SCHEMA: &str = r#"
+type Swap @entity {
+  id: ID!
+  amountUSD: BigDecimal!
+  logIndex: Int
+  timestamp: Int8!
+  pool: Pool!
+}
+
+type Pool @entity {
+  id: ID!
+  feeTier: Int!
+  swaps: [Swap!]! @derivedFrom(field: "pool")
+}
+"#;
+
+fn export_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "subgraph-mcp-export-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_paged_query_and_columns() {
+    let paged = PagedQuery::parse(
+        "{ trades: swaps(where: { amountUSD_gt: \"10\" }) { amountUSD pool { feeTier } } }",
+    )
+    .unwrap();
+    assert_eq!(paged.response_key, "trades");
+    assert_eq!(paged.field_name, "swaps");
+
+    let first = paged.page(100, None);
+    assert!(first.contains("first: 100"));
+    assert!(first.contains("orderBy: id"));
+    assert!(!first.contains("id_gt"));
+    let next = paged.page(100, Some("0x9"));
+    assert!(next.contains("amountUSD_gt: \"10\""));
+    assert!(next.contains("id_gt: \"0x9\""));
+    assert!(!next.contains("block"));
+
+    // `or` can't sit next to the cursor, so both go under `and`
+    let mut filtered = PagedQuery::parse(
+        "{ swaps(where: { or: [{ amountUSD_gt: \"10\" }, { logIndex: 0 }] }) { id } }",
+    )
+    .unwrap();
+    filtered.pin_block(19_000_000);
+    let next = filtered.page(100, Some("0x9"));
+    assert!(next.contains(
+        "where: {and: [{or: [{amountUSD_gt: \"10\"}, {logIndex: 0}]}, {id_gt: \"0x9\"}]}"
+    ));
+    assert!(next.contains("block: {number: 19000000}"));
+
+    // A block given by the query is kept
+    let mut historical = PagedQuery::parse("{ swaps(block: { number: 5 }) { id } }").unwrap();
+    historical.pin_block(19_000_000);
+    assert!(!historical.needs_block());
+    assert!(!historical.page(100, None).contains("19000000"));
+
+    let columns = paged.columns(&summarize_schema(SCHEMA).unwrap()).unwrap();
+    let column = |name: &str, kind| Column {
+        name: name.to_string(),
+        kind,
+    };
+    assert_eq!(
+        columns,
+        vec![
+            column("id", ColumnKind::Text),
+            column("amountUSD", ColumnKind::Text),
+            column("pool.feeTier", ColumnKind::Int),
+        ]
+    );
+
+    for query in [
+        "{ swaps(first: 10) { id } }",
+        "{ swaps(orderBy: timestamp) { id } }",
+        "{ swaps { id } pools { id } }",
+        "query A { swaps { id } } query B { pools { id } }",
+    ] {
+        assert!(PagedQuery::parse(query).is_err(), "{}", query);
+    }
+}
+
+#[test]
+fn test_export_path_stays_inside_root() {
+    let root = export_dir("paths");
+    let path = resolve_export_path(&root, "daily/swaps.csv").unwrap();
+    assert!(path.starts_with(root.canonicalize().unwrap()));
+    assert!(root.join("daily").is_dir());
+
+    for name in [
+        "",
+        "../swaps.csv",
+        "/tmp/swaps.csv",
+        "daily/../../swaps.csv",
+    ] {
+        let err = resolve_export_path(&root, name).unwrap_err();
+        assert!(
+            err.to_string().contains("outside the export directory"),
+            "{}",
+            name
+        );
+    }
+
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(std::env::temp_dir(), root.join("escape")).unwrap();
+        assert!(resolve_export_path(&root, "escape/nested/swaps.csv").is_err());
+        assert!(!std::env::temp_dir().join("nested").exists());
+    }
+}
+
+#[tokio::test]
+async fn test_export_query_tool() {
+    let target = GatewayTarget::Deployment {
+        endpoint_type: "subgraphs/id".to_string(),
+        id: UNISWAP_SUBGRAPH.to_string(),
+    };
+    let page =
+        |data: Value| GatewayResponse::json(reqwest::StatusCode::OK, &json!({ "data": data }));
+    let backend = Arc::new(
+        FixtureBackend::new()
+            .with_network_subgraph_data(
+                "SubgraphSchema",
+                json!({ "subgraph": { "currentVersion": { "subgraphDeployment": { "manifest": { "schema": { "schema": SCHEMA } } } } } }),
+            )
+            .with_response(
+                target.clone(),
+                Some("block: {number: 19000000}"),
+                page(json!({ "swaps": [{ "id": "0x3", "amountUSD": "3.5", "logIndex": null, "pool": { "feeTier": 500 } }] })),
+            )
+            .with_response(
+                target,
+                None,
+                page(json!({
+                    "swaps": [
+                        { "id": "0x1", "amountUSD": "1", "logIndex": 7, "pool": { "feeTier": 500 } },
+                        { "id": "0x2", "amountUSD": "2, or so", "logIndex": 8, "pool": { "feeTier": 3000 } },
+                    ],
+                    "_freshness": { "block": { "number": 19000000 } },
+                })),
+            ),
+    );
+    let root = export_dir("tool");
+    let server = SubgraphServer::builder()
+        .backend(backend.clone())
+        .api_key("test-key")
+        .export(ExportSettings {
+            dir: Some(root.clone()),
+            max_rows: 1000,
+        })
+        .build();
+    let request = |format| ExportQueryRequest {
+        deployment: UNISWAP_SUBGRAPH.to_string(),
+        query: "{ swaps { id amountUSD logIndex pool { feeTier } } }".to_string(),
+        variables: None,
+        format,
+        file_name: Some("swaps.csv".to_string()),
+        max_rows: None,
+        page_size: Some(2),
+    };
+
+    let result = server
+        .export_query(Extensions::new(), request(ExportFormat::Csv))
+        .await
+        .unwrap();
+    let RawContent::Text(text) = &result.content[0].raw else {
+        panic!("expected text content");
+    };
+    let body: Value = serde_json::from_str(&text.text).unwrap();
+    assert_eq!(body["rows"], 3);
+    assert_eq!(body["pages"], 2);
+    assert_eq!(body["complete"], true);
+    assert_eq!(body["preview"][2]["id"], "0x3");
+    assert_eq!(
+        std::fs::read_to_string(root.join("swaps.csv")).unwrap(),
+        "id,amountUSD,logIndex,pool.feeTier\n0x1,1,7,500\n0x2,\"2, or so\",8,3000\n0x3,3.5,,500\n"
+    );
+    assert_eq!(
+        body["bytes"],
+        std::fs::metadata(root.join("swaps.csv")).unwrap().len()
+    );
+
+    // The file exists now, and is never overwritten
+    let err = server
+        .export_query(Extensions::new(), request(ExportFormat::Ndjson))
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("already exists"));
+
+    let disabled = SubgraphServer::builder()
+        .backend(backend)
+        .api_key("test-key")
+        .build();
+    let err = disabled
+        .export_query(Extensions::new(), request(ExportFormat::Csv))
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("EXPORT_DIR"));
+}
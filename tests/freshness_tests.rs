@@ -0,0 +1,175 @@
+use rmcp::model::{Extensions, RawContent};
+use serde_json::{json, Value};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use subgraph_mcp::backend::FixtureBackend;
+use subgraph_mcp::freshness::{add_meta_selection, take_freshness, FreshnessSettings};
+use subgraph_mcp::query_analysis::{analyze_query, QueryLimits};
+use subgraph_mcp::server::SubgraphServer;
+use subgraph_mcp::types::ExecuteQueryByIpfsHashRequest;
+
+const UNISWAP_DEPLOYMENT: &str = "QmTZ8ejXJxRo7vDBS4uwqBeGoxLSWbhaA7oXa1RvxunLy7";
+
+fn meta(timestamp: u64, has_indexing_errors: bool) -> Value {
+    json!({
+        "block": { "number": 21481602, "hash": "0x3f0d", "timestamp": timestamp },
+        "hasIndexingErrors": has_indexing_errors,
+        "deployment": UNISWAP_DEPLOYMENT,
+    })
+}
+
+#[test]
+fn test_add_meta_selection() {
+    let query = add_meta_selection(
+        "query Pools($first: Int!) { _meta { block { number } } pools(first: $first) { id } }",
+    )
+    .unwrap();
+    let normalized = query.split_whitespace().collect::<Vec<_>>().join(" ");
+    assert_eq!(
+        normalized,
+        "query Pools($first: Int!) { _meta { block { number } } pools(first: $first) { id } _freshness: _meta { block { number hash timestamp } hasIndexingErrors deployment } }"
+    );
+    let analysis = analyze_query(&query, Some(&json!({ "first": 10 }))).unwrap();
+    QueryLimits::default().check(&analysis).unwrap();
+
+    assert!(add_meta_selection("{ __schema { types { name } } }").is_none());
+    assert!(add_meta_selection("mutation { reset }").is_none());
+    assert!(add_meta_selection("{ pools {").is_none());
+}
+
+#[test]
+fn test_take_freshness() {
+    let now = UNIX_EPOCH + Duration::from_secs(1_735_020_000);
+    let mut response = json!({
+        "data": { "pools": [], "_freshness": meta(1_735_012_211, true) }
+    });
+    let freshness = take_freshness(&mut response, Some(Duration::from_secs(900)), now).unwrap();
+    assert_eq!(response, json!({ "data": { "pools": [] } }));
+    assert_eq!(freshness.block_number, Some(21481602));
+    assert_eq!(freshness.seconds_behind, Some(7789));
+    assert_eq!(freshness.deployment.as_deref(), Some(UNISWAP_DEPLOYMENT));
+    assert_eq!(freshness.warnings.len(), 2);
+    assert!(freshness.warnings[0].contains("2h 9m behind, more than the 15m 0s threshold"));
+    assert!(freshness.warnings[1].contains("indexing errors"));
+
+    let mut response = json!({ "data": { "_freshness": meta(1_735_019_990, false) } });
+    let freshness = take_freshness(&mut response, Some(Duration::from_secs(900)), now).unwrap();
+    assert!(freshness.warnings.is_empty());
+    assert!(json!(freshness).get("warnings").is_none());
+
+    assert!(take_freshness(&mut json!({ "data": { "pools": [] } }), None, now).is_none());
+}
+
+#[tokio::test]
+async fn test_query_tool_reports_freshness() {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let backend = Arc::new(FixtureBackend::new().with_deployment_data(
+        "deployments/id",
+        UNISWAP_DEPLOYMENT,
+        json!({ "pools": [{ "id": "0x1" }], "_freshness": meta(now - 30, false) }),
+    ));
+    let request = |freshness| ExecuteQueryByIpfsHashRequest {
+        ipfs_hash: UNISWAP_DEPLOYMENT.to_string(),
+        query: "{ pools(first: 1) { id } }".to_string(),
+        variables: None,
+        freshness,
+        decode: None,
+        transform: None,
+        output_format: None,
+    };
+    let body = |result: rmcp::model::CallToolResult| -> Value {
+        let RawContent::Text(text) = &result.content[0].raw else {
+            panic!("expected text content");
+        };
+        serde_json::from_str(&text.text).unwrap()
+    };
+
+    let server = SubgraphServer::builder()
+        .backend(backend.clone())
+        .api_key("test-key")
+        .build();
+    let result = body(
+        server
+            .execute_query_by_ipfs_hash(Extensions::new(), request(None))
+            .await
+            .unwrap(),
+    );
+    assert_eq!(result["data"], json!({ "pools": [{ "id": "0x1" }] }));
+    assert_eq!(result["freshness"]["block_number"], 21481602);
+    assert!(result["freshness"]["seconds_behind"].as_u64().unwrap() < 900);
+    assert!(result["freshness"].get("warnings").is_none());
+    assert!(backend.requests()[0].1.query.contains("_freshness: _meta"));
+
+    // Off by server default, unless the request asks for it
+    let server = SubgraphServer::builder()
+        .backend(backend.clone())
+        .api_key("test-key")
+        .freshness(FreshnessSettings {
+            enabled: false,
+            max_staleness: None,
+        })
+        .build();
+    let result = body(
+        server
+            .execute_query_by_ipfs_hash(Extensions::new(), request(None))
+            .await
+            .unwrap(),
+    );
+    assert!(result.get("freshness").is_none());
+    assert_eq!(backend.requests()[1].1.query, "{ pools(first: 1) { id } }");
+    server
+        .execute_query_by_ipfs_hash(Extensions::new(), request(Some(true)))
+        .await
+        .unwrap();
+    assert!(backend.requests()[2].1.query.contains("_freshness"));
+}
+
+#[tokio::test]
+async fn test_query_at_limits_passes_with_freshness() {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let backend = Arc::new(FixtureBackend::new().with_deployment_data(
+        "deployments/id",
+        UNISWAP_DEPLOYMENT,
+        json!({ "pools": [{ "id": "0x1" }], "_freshness": meta(now - 30, false) }),
+    ));
+    // The query has exactly the depth and field count allowed; the `_meta`
+    // selection added for freshness does not count against them
+    let server = SubgraphServer::builder()
+        .backend(backend.clone())
+        .api_key("test-key")
+        .query_limits(QueryLimits {
+            max_depth: Some(2),
+            max_fields: Some(2),
+            max_estimated_rows: None,
+        })
+        .build();
+    let result = server
+        .execute_query_by_ipfs_hash(
+            Extensions::new(),
+            ExecuteQueryByIpfsHashRequest {
+                ipfs_hash: UNISWAP_DEPLOYMENT.to_string(),
+                query: "{ pools(first: 1) { id } }".to_string(),
+                variables: None,
+                freshness: Some(true),
+                decode: None,
+                transform: None,
+                output_format: None,
+            },
+        )
+        .await
+        .unwrap();
+    let RawContent::Text(text) = &result.content[0].raw else {
+        panic!("expected text content");
+    };
+    let body: Value = serde_json::from_str(&text.text).unwrap();
+    assert_eq!(body["freshness"]["block_number"], 21481602);
+    assert!(backend.requests()[0].1.query.contains("_freshness: _meta"));
+}
@@ -0,0 +1,42 @@
+use std::time::Duration;
+use subgraph_mcp::health::{version_info, HealthState};
+
+#[test]
+fn test_readiness_requires_bound_listener() {
+    let health = HealthState::new(None);
+    assert!(!health.readiness().ready);
+
+    health.set_sse_listener_bound(true);
+    let report = health.readiness();
+    assert!(report.ready);
+    assert!(report.gateway.is_none());
+
+    health.set_sse_listener_bound(false);
+    assert!(!health.readiness().ready);
+}
+
+#[test]
+fn test_readiness_requires_recent_gateway_answer_when_enabled() {
+    let health = HealthState::new(Some(Duration::from_secs(60)));
+    health.set_sse_listener_bound(true);
+
+    let report = health.readiness();
+    assert!(!report.ready);
+    let gateway = report.gateway.unwrap();
+    assert!(!gateway.ok);
+    assert_eq!(gateway.window_seconds, 60);
+    assert!(gateway.last_success_seconds_ago.is_none());
+
+    health.record_gateway_success();
+    let report = health.readiness();
+    assert!(report.ready);
+    assert_eq!(report.gateway.unwrap().last_success_seconds_ago, Some(0));
+}
+
+#[test]
+fn test_version_info_lists_gateways() {
+    let info = version_info();
+    assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+    assert!(!info.git_commit.is_empty());
+    assert!(info.gateways.contains_key(info.default_gateway));
+}
@@ -0,0 +1,191 @@
+use rmcp::model::{Extensions, RawContent};
+use serde_json::{json, Value};
+use std::{sync::Arc, time::Duration};
+use subgraph_mcp::backend::FixtureBackend;
+use subgraph_mcp::introspection::{
+    print_introspection_sdl, IntrospectionFormat, INTROSPECTION_QUERY,
+};
+use subgraph_mcp::query_analysis::{analyze_query, QueryLimits};
+use subgraph_mcp::schema_cache::SchemaCache;
+use subgraph_mcp::server::SubgraphServer;
+use subgraph_mcp::types::GetSchemaByIntrospectionRequest;
+
+const UNISWAP_DEPLOYMENT: &str = "QmTZ8ejXJxRo7vDBS4uwqBeGoxLSWbhaA7oXa1RvxunLy7";
+
+fn named(name: &str) -> Value {
+    json!({ "kind": "OBJECT", "name": name, "ofType": null })
+}
+
+fn non_null(inner: Value) -> Value {
+    json!({ "kind": "NON_NULL", "name": null, "ofType": inner })
+}
+
+fn introspection_data() -> Value {
+    let scalar = |name: &str| json!({ "kind": "SCALAR", "name": name, "ofType": null });
+    json!({
+        "__schema": {
+            "queryType": { "name": "Query" },
+            "mutationType": null,
+            "subscriptionType": { "name": "Subscription" },
+            "types": [
+                {
+                    "kind": "OBJECT",
+                    "name": "Query",
+                    "description": null,
+                    "fields": [
+                        {
+                            "name": "pools",
+                            "description": null,
+                            "args": [
+                                { "name": "first", "description": null, "type": scalar("Int"), "defaultValue": "100" },
+                                { "name": "orderBy", "description": null, "type": { "kind": "ENUM", "name": "Pool_orderBy", "ofType": null }, "defaultValue": null },
+                            ],
+                            "type": non_null(json!({ "kind": "LIST", "name": null, "ofType": non_null(named("Pool")) })),
+                            "isDeprecated": false,
+                            "deprecationReason": null,
+                        },
+                        {
+                            "name": "_meta",
+                            "description": "Access to subgraph metadata",
+                            "args": [],
+                            "type": named("_Meta_"),
+                            "isDeprecated": false,
+                            "deprecationReason": null,
+                        },
+                    ],
+                    "inputFields": null,
+                    "interfaces": [],
+                    "enumValues": null,
+                    "possibleTypes": null,
+                },
+                {
+                    "kind": "OBJECT",
+                    "name": "Pool",
+                    "description": null,
+                    "fields": [
+                        { "name": "id", "description": null, "args": [], "type": non_null(scalar("ID")), "isDeprecated": false, "deprecationReason": null },
+                        { "name": "liquidity", "description": null, "args": [], "type": non_null(scalar("BigInt")), "isDeprecated": true, "deprecationReason": "Use totalLiquidity" },
+                    ],
+                    "inputFields": null,
+                    "interfaces": [],
+                    "enumValues": null,
+                    "possibleTypes": null,
+                },
+                {
+                    "kind": "ENUM",
+                    "name": "Pool_orderBy",
+                    "description": null,
+                    "fields": null,
+                    "inputFields": null,
+                    "interfaces": null,
+                    "enumValues": [
+                        { "name": "id", "description": null, "isDeprecated": false, "deprecationReason": null },
+                        { "name": "liquidity", "description": null, "isDeprecated": false, "deprecationReason": null },
+                    ],
+                    "possibleTypes": null,
+                },
+                {
+                    "kind": "INPUT_OBJECT",
+                    "name": "Pool_filter",
+                    "description": null,
+                    "fields": null,
+                    "inputFields": [
+                        { "name": "id_gt", "description": null, "type": scalar("ID"), "defaultValue": null },
+                    ],
+                    "interfaces": null,
+                    "enumValues": null,
+                    "possibleTypes": null,
+                },
+                { "kind": "SCALAR", "name": "BigInt", "description": null, "fields": null, "inputFields": null, "interfaces": null, "enumValues": null, "possibleTypes": null },
+                { "kind": "SCALAR", "name": "ID", "description": null, "fields": null, "inputFields": null, "interfaces": null, "enumValues": null, "possibleTypes": null },
+                { "kind": "OBJECT", "name": "__Type", "description": null, "fields": [], "inputFields": null, "interfaces": [], "enumValues": null, "possibleTypes": null },
+            ],
+            "directives": [],
+        }
+    })
+}
+
+#[test]
+fn test_introspection_queries_are_subject_to_query_limits() {
+    // Only the server's own introspection query skips the limits; a user's deep
+    // `__schema` selection is rejected like any other query
+    let analysis = analyze_query(INTROSPECTION_QUERY, None).unwrap();
+    assert!(analysis.depth > QueryLimits::default().max_depth.unwrap());
+    let err = QueryLimits::default().check(&analysis).unwrap_err();
+    assert!(err.to_string().contains("Query depth"));
+
+    let shallow = analyze_query("{ __schema { queryType { name } } }", None).unwrap();
+    QueryLimits::default().check(&shallow).unwrap();
+}
+
+#[test]
+fn test_print_introspection_sdl() {
+    let sdl = print_introspection_sdl(&introspection_data()).unwrap();
+    assert_eq!(
+        sdl,
+        r#"type Query {
+  pools(first: Int = 100, orderBy: Pool_orderBy): [Pool!]!
+  "Access to subgraph metadata"
+  _meta: _Meta_
+}
+
+type Pool {
+  id: ID!
+  liquidity: BigInt! @deprecated(reason: "Use totalLiquidity")
+}
+
+enum Pool_orderBy {
+  id
+  liquidity
+}
+
+input Pool_filter {
+  id_gt: ID
+}
+
+scalar BigInt
+"#
+    );
+    // The printed SDL is valid GraphQL
+    graphql_parser::parse_schema::<String>(&sdl).unwrap();
+
+    assert!(print_introspection_sdl(&json!({})).is_err());
+}
+
+#[tokio::test]
+async fn test_introspection_tool_is_cached() {
+    let backend = Arc::new(FixtureBackend::new().with_deployment_data(
+        "deployments/id",
+        UNISWAP_DEPLOYMENT,
+        introspection_data(),
+    ));
+    let server = SubgraphServer::builder()
+        .backend(backend.clone())
+        .api_key("test-key")
+        .schema_cache(Arc::new(SchemaCache::new(Duration::from_secs(60), 16)))
+        .build();
+
+    let call = |format| {
+        server.get_schema_by_introspection(
+            Extensions::new(),
+            GetSchemaByIntrospectionRequest {
+                deployment: UNISWAP_DEPLOYMENT.to_string(),
+                format,
+            },
+        )
+    };
+    let text = |result: rmcp::model::CallToolResult| match &result.content[0].raw {
+        RawContent::Text(text) => text.text.clone(),
+        _ => panic!("expected text content"),
+    };
+
+    let sdl = text(call(None).await.unwrap());
+    assert!(sdl.starts_with("type Query {"));
+    let data: Value =
+        serde_json::from_str(&text(call(Some(IntrospectionFormat::Json)).await.unwrap())).unwrap();
+    assert_eq!(data, introspection_data());
+
+    let requests = backend.requests();
+    assert_eq!(requests.len(), 1);
+    assert!(requests[0].1.query.contains("__schema"));
+}
@@ -0,0 +1,133 @@
+use rmcp::model::{ErrorCode, Extensions};
+use serde_json::json;
+use std::sync::Arc;
+use subgraph_mcp::backend::FixtureBackend;
+use subgraph_mcp::constants::RATE_LIMITED_ERROR_CODE;
+use subgraph_mcp::error::SubgraphError;
+use subgraph_mcp::metrics::{buckets_from_env, note_tool_error, ErrorKind, IsSuccess, METRICS};
+use subgraph_mcp::server::SubgraphServer;
+use subgraph_mcp::types::ExecuteQueryByDeploymentIdRequest;
+
+#[test]
+fn test_subgraph_error_kinds() {
+    assert_eq!(SubgraphError::ApiKeyNotSet.error_kind(), "api_key_not_set");
+    assert_eq!(
+        SubgraphError::GraphQlError("bad".to_string()).error_kind(),
+        "graphql"
+    );
+    assert_eq!(
+        SubgraphError::QueryRejected("too deep".to_string()).error_kind(),
+        "query_rejected"
+    );
+
+    let ok: Result<(), SubgraphError> = Ok(());
+    assert_eq!(ok.error_kind(), "none");
+    let err: Result<(), SubgraphError> = Err(SubgraphError::PolicyViolation("x".to_string()));
+    assert_eq!(err.error_kind(), "policy_violation");
+}
+
+#[test]
+fn test_mcp_error_kinds() {
+    assert_eq!(
+        rmcp::Error::invalid_params("bad", None).error_kind(),
+        "invalid_params"
+    );
+    assert_eq!(
+        rmcp::Error::internal_error("boom", None).error_kind(),
+        "internal_error"
+    );
+    assert_eq!(
+        rmcp::Error::new(ErrorCode(RATE_LIMITED_ERROR_CODE), "slow down", None).error_kind(),
+        "rate_limited"
+    );
+}
+
+#[tokio::test]
+async fn test_tool_calls_labelled_with_noted_error_kind() {
+    let mut registry = prometheus_client::registry::Registry::default();
+    METRICS.register(&mut registry);
+
+    let result: Result<(), rmcp::Error> = METRICS
+        .observe_tool_call("test_noted_error_tool", "key", || async {
+            let error = SubgraphError::GraphQlError("bad".to_string());
+            note_tool_error(&error);
+            Err(rmcp::Error::internal_error(error.to_string(), None))
+        })
+        .await;
+    assert!(result.is_err());
+    let result: Result<(), rmcp::Error> = METRICS
+        .observe_tool_call("test_unnoted_error_tool", "key", || async {
+            Err(rmcp::Error::invalid_params("bad", None))
+        })
+        .await;
+    assert!(result.is_err());
+
+    let mut text = String::new();
+    prometheus_client::encoding::text::encode(&mut text, &registry).unwrap();
+    let calls = |tool: &str| {
+        text.lines()
+            .find(|line| line.starts_with("subgraph_mcp_tool_calls_total") && line.contains(tool))
+            .unwrap()
+            .to_string()
+    };
+    assert!(calls("test_noted_error_tool").contains("error_kind=\"graphql\""));
+    assert!(calls("test_unnoted_error_tool").contains("error_kind=\"invalid_params\""));
+}
+
+#[tokio::test]
+async fn test_gateway_requests_labelled_with_builder_gateway() {
+    let mut registry = prometheus_client::registry::Registry::default();
+    METRICS.register(&mut registry);
+
+    let deployment_id = "QmMetricsGatewayLabel";
+    let backend = Arc::new(FixtureBackend::new().with_deployment_data(
+        "deployments/id",
+        deployment_id,
+        json!({ "tokens": [] }),
+    ));
+    let server = SubgraphServer::builder()
+        .backend(backend)
+        .api_key("test-key")
+        .gateway("staging", "https://staging.gateway.example/api")
+        .default_gateway("staging")
+        .build();
+    server
+        .execute_query_by_deployment_id(
+            Extensions::new(),
+            ExecuteQueryByDeploymentIdRequest {
+                deployment_id: deployment_id.to_string(),
+                query: "{ tokens { id } }".to_string(),
+                variables: None,
+                freshness: Some(false),
+                decode: None,
+                transform: None,
+                output_format: None,
+            },
+        )
+        .await
+        .unwrap();
+
+    let mut text = String::new();
+    prometheus_client::encoding::text::encode(&mut text, &registry).unwrap();
+    assert!(text.lines().any(
+        |line| line.starts_with("subgraph_mcp_gateway_requests_total")
+            && line.contains("gateway=\"staging\"")
+    ));
+}
+
+#[test]
+fn test_buckets_from_env() {
+    let default = [1.0, 2.0];
+
+    std::env::set_var("TEST_METRICS_BUCKETS", "120, 0.5,30,0.5");
+    assert_eq!(
+        buckets_from_env("TEST_METRICS_BUCKETS", &default),
+        vec![0.5, 30.0, 120.0]
+    );
+
+    std::env::set_var("TEST_METRICS_BUCKETS", "1,fast");
+    assert_eq!(buckets_from_env("TEST_METRICS_BUCKETS", &default), default);
+
+    std::env::remove_var("TEST_METRICS_BUCKETS");
+    assert_eq!(buckets_from_env("TEST_METRICS_BUCKETS", &default), default);
+}
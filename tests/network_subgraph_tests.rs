@@ -0,0 +1,86 @@
+use serde_json::{json, Value};
+use subgraph_mcp::error::SubgraphError;
+use subgraph_mcp::network_subgraph::NetworkSubgraphClient;
+use subgraph_mcp::server::SubgraphServer;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+/// Answers every POST with `body` and `status`.
+async fn mock_gateway(status: u16, body: Value) -> MockServer {
+    let gateway = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(status).set_body_json(body))
+        .mount(&gateway)
+        .await;
+    gateway
+}
+
+#[tokio::test]
+async fn test_typed_deployment_lookup() {
+    let gateway = mock_gateway(
+        200,
+        json!({
+            "data": {
+                "subgraph": {
+                    "currentVersion": {
+                        "subgraphDeployment": {
+                            "ipfsHash": "QmCurrent",
+                            "manifest": { "network": "arbitrum-one" }
+                        }
+                    }
+                }
+            }
+        }),
+    )
+    .await;
+    let gateway_url = gateway.uri();
+    let server = SubgraphServer::new();
+    let client = NetworkSubgraphClient::new(&server, "key", &gateway_url);
+
+    let deployment = client.current_deployment("5zvR82").await.unwrap().unwrap();
+    assert_eq!(deployment.ipfs_hash.as_deref(), Some("QmCurrent"));
+    assert_eq!(
+        deployment.manifest.and_then(|m| m.network).as_deref(),
+        Some("arbitrum-one")
+    );
+
+    let err = client.subgraph_schema("5zvR82").await.unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "GraphQL error: Schema not found for current version in the response"
+    );
+}
+
+#[tokio::test]
+async fn test_graphql_error_wins_over_http_status() {
+    let gateway = mock_gateway(
+        400,
+        json!({ "errors": [{ "message": "auth error: invalid API key" }] }),
+    )
+    .await;
+    let gateway_url = gateway.uri();
+    let server = SubgraphServer::new();
+    let client = NetworkSubgraphClient::new(&server, "key", &gateway_url);
+
+    match client
+        .query::<Value>("{ _meta { block { number } } }", json!({}))
+        .await
+    {
+        Err(SubgraphError::GraphQlError(message)) => {
+            assert_eq!(message, "auth error: invalid API key")
+        }
+        other => panic!("expected a GraphQL error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_http_status_without_graphql_body() {
+    let gateway = mock_gateway(502, json!("upstream unavailable")).await;
+    let gateway_url = gateway.uri();
+    let server = SubgraphServer::new();
+    let client = NetworkSubgraphClient::new(&server, "key", &gateway_url);
+
+    match client.probe().await {
+        Err(SubgraphError::HttpStatus(status)) => assert_eq!(status, 502),
+        other => panic!("expected an HTTP status error, got {:?}", other),
+    }
+}
@@ -0,0 +1,151 @@
+use rmcp::model::Extensions;
+use serde_json::json;
+use std::sync::Arc;
+use subgraph_mcp::backend::FixtureBackend;
+use subgraph_mcp::policy::{
+    deployment_id_to_ipfs_hash, ipfs_hash_to_deployment_id, DeploymentPolicy, DeploymentTarget,
+    PolicyRules, PolicyStore,
+};
+use subgraph_mcp::server::SubgraphServer;
+use subgraph_mcp::types::GetSchemaByIpfsHashRequest;
+use subgraph_mcp::SubgraphError;
+
+const DEPLOYMENT_ID: &str = "0xc5b4d246cf890b0b468e005224622d4c85a8b723cc0b8fa7db6d1a93ddd2e5de";
+
+fn target(ipfs_hash: &str, network: Option<&str>) -> DeploymentTarget {
+    DeploymentTarget {
+        ipfs_hash: Some(ipfs_hash.to_string()),
+        network: network.map(|n| n.to_string()),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_deployment_id_ipfs_hash_round_trip() {
+    let ipfs_hash = deployment_id_to_ipfs_hash(DEPLOYMENT_ID).unwrap();
+    assert!(ipfs_hash.starts_with("Qm"));
+    assert_eq!(ipfs_hash.len(), 46);
+    assert_eq!(
+        ipfs_hash_to_deployment_id(&ipfs_hash).as_deref(),
+        Some(DEPLOYMENT_ID)
+    );
+    assert!(deployment_id_to_ipfs_hash("0x1234").is_none());
+}
+
+#[test]
+fn test_deny_rules_win_over_allow_rules() {
+    let ipfs_hash = deployment_id_to_ipfs_hash(DEPLOYMENT_ID).unwrap();
+    let policy = DeploymentPolicy {
+        allow: PolicyRules {
+            networks: vec!["mainnet".to_string()],
+            ..Default::default()
+        },
+        deny: PolicyRules {
+            deployment_ids: vec![DEPLOYMENT_ID.to_string()],
+            ..Default::default()
+        },
+        load_error: None,
+    };
+
+    assert!(policy.needs_network());
+    assert!(matches!(
+        policy.check(&target(&ipfs_hash, Some("mainnet"))),
+        Err(SubgraphError::PolicyViolation(_))
+    ));
+    assert!(policy.check(&target("QmOther", Some("mainnet"))).is_ok());
+    assert!(policy
+        .check(&target("QmOther", Some("arbitrum-one")))
+        .is_err());
+}
+
+#[test]
+fn test_policy_loaded_from_file() {
+    let path =
+        std::env::temp_dir().join(format!("subgraph-mcp-policy-{}.json", std::process::id()));
+    std::fs::write(&path, r#"{ "allow": { "ipfs_hashes": ["QmAllowed"] } }"#).unwrap();
+
+    let store = PolicyStore::from_file(&path).unwrap();
+    let policy = store.current();
+    assert!(!policy.needs_network());
+    assert!(policy.check(&target("QmAllowed", None)).is_ok());
+    assert!(policy.check(&target("QmOther", None)).is_err());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_unresolved_targets_fail_closed() {
+    let policy = DeploymentPolicy {
+        deny: PolicyRules {
+            subgraph_ids: vec!["Denied".to_string()],
+            networks: vec!["sepolia".to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert!(policy.needs_subgraphs());
+
+    let resolved = DeploymentTarget {
+        subgraph_ids: Some(vec!["Other".to_string(), "Denied".to_string()]),
+        ..target("QmShared", Some("mainnet"))
+    };
+    let err = policy.check(&resolved).unwrap_err();
+    assert!(err.to_string().contains("subgraph_id Denied"));
+
+    let clean = DeploymentTarget {
+        subgraph_ids: Some(vec!["Other".to_string()]),
+        ..target("QmShared", Some("mainnet"))
+    };
+    assert!(policy.check(&clean).is_ok());
+    // Missing from the network subgraph: neither owners nor network are known
+    let err = policy.check(&target("QmUnknown", None)).unwrap_err();
+    assert!(err.to_string().contains("could not be resolved"));
+
+    let allow = DeploymentPolicy {
+        allow: PolicyRules {
+            subgraph_ids: vec!["Vetted".to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let owned = DeploymentTarget {
+        subgraph_ids: Some(vec!["Vetted".to_string()]),
+        ..target("QmVetted", None)
+    };
+    assert!(allow.check(&owned).is_ok());
+    assert!(allow.check(&target("QmVetted", None)).is_err());
+}
+
+#[tokio::test]
+async fn test_subgraph_rules_apply_to_deployments_by_hash() {
+    let backend = Arc::new(FixtureBackend::new().with_network_subgraph_data(
+        "ResolveDeployment",
+        json!({ "subgraphDeployments": [{
+            "ipfsHash": "QmDenied",
+            "manifest": { "network": "mainnet" },
+            "versions": [{ "subgraph": { "id": "DeniedSubgraph" } }],
+        }] }),
+    ));
+    let server = SubgraphServer::builder()
+        .backend(backend)
+        .api_key("test-key")
+        .deployment_policy(Arc::new(PolicyStore::from_policy(DeploymentPolicy {
+            deny: PolicyRules {
+                subgraph_ids: vec!["DeniedSubgraph".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        })))
+        .build();
+
+    let err = server
+        .get_schema_by_ipfs_hash(
+            Extensions::new(),
+            GetSchemaByIpfsHashRequest {
+                ipfs_hash: "QmDenied".to_string(),
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("subgraph_id DeniedSubgraph"));
+}
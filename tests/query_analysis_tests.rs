@@ -0,0 +1,111 @@
+use serde_json::json;
+use subgraph_mcp::query_analysis::{analyze_query, analyze_query_with_schema, QueryLimits};
+use subgraph_mcp::schema_summary::summarize_schema;
+use subgraph_mcp::SubgraphError;
+
+#[test]
+fn test_nested_first_arguments_multiply() {
+    let query = r#"
+        query Pools($swaps: Int) {
+            pools(first: 1000) {
+                id
+                swaps(first: $swaps, orderBy: timestamp) {
+                    id
+                    amountUSD
+                }
+            }
+            _meta { block { number } }
+        }
+    "#;
+
+    let analysis = analyze_query(query, Some(&json!({ "swaps": 500 }))).unwrap();
+    assert_eq!(analysis.depth, 3);
+    assert_eq!(analysis.field_count, 8);
+    assert_eq!(analysis.estimated_rows, 1000 + 1000 * 500);
+
+    let heaviest = analysis.heaviest_selection.unwrap();
+    assert_eq!(heaviest.path, "pools(first: 1000).swaps(first: 500)");
+    assert_eq!(heaviest.rows, 500_000);
+}
+
+#[test]
+fn test_fragments_and_default_page_size() {
+    let query = r#"
+        query {
+            tokens(where: { symbol: "GRT" }) { ...TokenFields }
+        }
+        fragment TokenFields on Token {
+            id
+            ... on Token { pools(first: 10) { id } }
+        }
+    "#;
+
+    let analysis = analyze_query(query, None).unwrap();
+    assert_eq!(analysis.depth, 3);
+    assert_eq!(analysis.estimated_rows, 100 + 100 * 10);
+    assert_eq!(
+        analysis.deepest_path,
+        "tokens(first: 100).pools(first: 10).id"
+    );
+}
+
+#[test]
+fn test_list_fields_without_arguments() {
+    let schema = summarize_schema(
+        r#"
+        type Pool @entity {
+          id: ID!
+          token0: Token!
+          swaps: [Swap!]! @derivedFrom(field: "pool")
+        }
+        type Swap @entity { id: ID! pool: Pool! }
+        type Token @entity { id: ID! symbol: String! }
+        "#,
+    )
+    .unwrap();
+
+    // graph-node returns 100 entities from list fields without `first`
+    let query = "{ pools(first: 1000) { swaps { id } } }";
+    let analysis = analyze_query(query, None).unwrap();
+    assert_eq!(analysis.estimated_rows, 1000 + 1000 * 100);
+    let analysis = analyze_query_with_schema(query, None, Some(&schema)).unwrap();
+    assert_eq!(analysis.estimated_rows, 1000 + 1000 * 100);
+
+    // Without a schema every nested object might be a list; the schema tells
+    let query = "{ pools(first: 1000) { token0 { symbol } } pool(id: \"0x1\") { id } }";
+    let analysis = analyze_query(query, None).unwrap();
+    assert_eq!(analysis.estimated_rows, 1000 + 1000 * 100);
+    let analysis = analyze_query_with_schema(query, None, Some(&schema)).unwrap();
+    assert_eq!(analysis.estimated_rows, 1000);
+}
+
+#[test]
+fn test_limits_reject_with_selection_to_narrow() {
+    let limits = QueryLimits {
+        max_depth: Some(8),
+        max_fields: None,
+        max_estimated_rows: Some(100_000),
+    };
+
+    let small = analyze_query(
+        "{ pools(first: 100) { id swaps(first: 100) { id } } }",
+        None,
+    );
+    assert!(limits.check(&small.unwrap()).is_ok());
+
+    let large = analyze_query(
+        "{ pools(first: 1000) { id swaps(first: 1000) { id } } }",
+        None,
+    );
+    match limits.check(&large.unwrap()) {
+        Err(SubgraphError::QueryRejected(msg)) => {
+            assert!(msg.contains("pools(first: 1000).swaps(first: 1000)"));
+        }
+        other => panic!("expected a rejected query, got {:?}", other),
+    }
+
+    assert!(matches!(
+        analyze_query("{ pools(first: 10) { id }", None),
+        Err(SubgraphError::QueryRejected(_))
+    ));
+}
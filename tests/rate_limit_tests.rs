@@ -0,0 +1,95 @@
+use subgraph_mcp::rate_limit::{
+    BudgetConfig, RateLimitConfig, RateLimiter, ThrottleReason, ToolClass,
+};
+
+fn test_config() -> RateLimitConfig {
+    RateLimitConfig {
+        query: BudgetConfig {
+            per_minute: 60.0,
+            burst: 2.0,
+            max_in_flight: 1,
+        },
+        metadata: BudgetConfig {
+            per_minute: 60.0,
+            burst: 5.0,
+            max_in_flight: 5,
+        },
+    }
+}
+
+#[test]
+fn test_tool_class_classification() {
+    assert_eq!(
+        ToolClass::for_tool("execute_query_by_ipfs_hash"),
+        ToolClass::Query
+    );
+    assert_eq!(
+        ToolClass::for_tool("search_subgraphs_by_keyword"),
+        ToolClass::Metadata
+    );
+}
+
+#[test]
+fn test_burst_exhaustion_returns_retry_after() {
+    let limiter = RateLimiter::new(test_config());
+
+    drop(limiter.acquire("key", ToolClass::Query).unwrap());
+    drop(limiter.acquire("key", ToolClass::Query).unwrap());
+
+    let throttled = limiter
+        .acquire("key", ToolClass::Query)
+        .err()
+        .expect("third call should be throttled");
+    assert_eq!(throttled.reason, ThrottleReason::RateLimit);
+    assert!(throttled.retry_after.as_secs_f64() > 0.0);
+
+    // Metadata tools and other keys have their own budgets
+    assert!(limiter.acquire("key", ToolClass::Metadata).is_ok());
+    assert!(limiter.acquire("other-key", ToolClass::Query).is_ok());
+}
+
+#[test]
+fn test_concurrency_cap_released_on_drop() {
+    let limiter = RateLimiter::new(test_config());
+
+    let permit = limiter.acquire("key", ToolClass::Query).unwrap();
+    let throttled = limiter
+        .acquire("key", ToolClass::Query)
+        .err()
+        .expect("second concurrent call should be throttled");
+    assert_eq!(throttled.reason, ThrottleReason::Concurrency);
+
+    drop(permit);
+    assert!(limiter.acquire("key", ToolClass::Query).is_ok());
+}
+
+#[test]
+fn test_unusable_limits_fall_back_to_defaults() {
+    std::env::set_var("RATE_LIMIT_QUERY_PER_MINUTE", "nan");
+    std::env::set_var("RATE_LIMIT_QUERY_BURST", "0");
+    std::env::set_var("RATE_LIMIT_QUERY_MAX_IN_FLIGHT", "0");
+    std::env::set_var("RATE_LIMIT_METADATA_PER_MINUTE", "inf");
+    std::env::set_var("RATE_LIMIT_METADATA_BURST", "3");
+    let config = RateLimitConfig::from_env().unwrap();
+    let defaults = RateLimitConfig::default();
+    assert_eq!(config.query.per_minute, defaults.query.per_minute);
+    assert_eq!(config.query.burst, defaults.query.burst);
+    assert_eq!(config.query.max_in_flight, defaults.query.max_in_flight);
+    assert_eq!(config.metadata.per_minute, defaults.metadata.per_minute);
+    assert_eq!(config.metadata.burst, 3.0);
+
+    // A refill too slow to express as a delay waits as long as possible
+    let mut config = test_config();
+    config.query.per_minute = 1e-30;
+    let limiter = RateLimiter::new(config);
+    drop(
+        limiter
+            .acquire_weighted("key", ToolClass::Query, 2)
+            .unwrap(),
+    );
+    let throttled = limiter
+        .acquire("key", ToolClass::Query)
+        .err()
+        .expect("an empty bucket should throttle");
+    assert_eq!(throttled.retry_after, std::time::Duration::MAX);
+}
@@ -0,0 +1,326 @@
+use rmcp::model::{CallToolResult, Extensions, RawContent};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use subgraph_mcp::backend::{FixtureBackend, GatewayBackend, GatewayResponse, GatewayTarget};
+use subgraph_mcp::recording::{
+    RecordedExchange, RecordedResponse, RecordingBackend, ReplayBackend, SCRUBBED_API_KEY,
+};
+use subgraph_mcp::server::SubgraphServer;
+use subgraph_mcp::types::*;
+
+const UNISWAP_SUBGRAPH: &str = "5zvR82QoaXYFyDEKLZ9t6v9adgnptxYpKpSbxtgVENFV";
+const UNISWAP_DEPLOYMENT: &str = "QmTZ8ejXJxRo7vDBS4uwqBeGoxLSWbhaA7oXa1RvxunLy7";
+const UNISWAP_ARBITRUM_DEPLOYMENT: &str = "QmZ5uwhnwsJXAQGYEF8qKPQ85iVhYAcVZcZAPfrF7ZNb9z";
+const POOLS_QUERY: &str =
+    "{ pools(first: 2, orderBy: totalValueLockedUSD, orderDirection: desc) { id feeTier totalValueLockedUSD } }";
+
+fn replay_server() -> SubgraphServer {
+    let backend = ReplayBackend::from_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/gateway_replay.ndjson"
+    ))
+    .unwrap();
+    SubgraphServer::builder()
+        .backend(Arc::new(backend))
+        .api_key("replay-key")
+        .build()
+}
+
+fn text(result: &CallToolResult) -> &str {
+    match &result.content[0].raw {
+        RawContent::Text(text) => &text.text,
+        _ => panic!("expected text content"),
+    }
+}
+
+fn json_text(result: &CallToolResult) -> Value {
+    serde_json::from_str(text(result)).unwrap()
+}
+
+#[tokio::test]
+async fn test_schema_tools_replay() {
+    let server = replay_server();
+
+    let by_deployment = server
+        .get_schema_by_deployment_id(
+            Extensions::new(),
+            GetSchemaByDeploymentIdRequest {
+                deployment_id: "0x4d2b8f1a0f3c9e7b6a5d4c3b2a1908f7e6d5c4b3a29180f7e6d5c4b3a2918070"
+                    .to_string(),
+            },
+        )
+        .await
+        .unwrap();
+    let by_subgraph = server
+        .get_schema_by_subgraph_id(
+            Extensions::new(),
+            GetSchemaBySubgraphIdRequest {
+                subgraph_id: UNISWAP_SUBGRAPH.to_string(),
+            },
+        )
+        .await
+        .unwrap();
+    let by_hash = server
+        .get_schema_by_ipfs_hash(
+            Extensions::new(),
+            GetSchemaByIpfsHashRequest {
+                ipfs_hash: UNISWAP_DEPLOYMENT.to_string(),
+            },
+        )
+        .await
+        .unwrap();
+
+    assert!(text(&by_deployment).contains("type Pool @entity"));
+    assert_eq!(text(&by_subgraph), text(&by_deployment));
+    assert_eq!(text(&by_hash), text(&by_deployment));
+}
+
+#[tokio::test]
+async fn test_discovery_tools_replay() {
+    let server = replay_server();
+
+    let search = server
+        .search_subgraphs_by_keyword(
+            Extensions::new(),
+            SearchSubgraphsByKeywordRequest {
+                keyword: "uniswap".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+    let search = json_text(&search);
+    assert_eq!(search["total"], 4);
+    assert_eq!(
+        search["subgraphs"][0]["metadata"]["displayName"],
+        "Uniswap V3"
+    );
+    assert_eq!(
+        search["subgraphs"][0]["currentVersion"]["subgraphDeployment"]["ipfsHash"],
+        UNISWAP_DEPLOYMENT
+    );
+
+    let top = server
+        .get_top_subgraph_deployments(
+            Extensions::new(),
+            GetTopSubgraphDeploymentsRequest {
+                contract_address: "0x1f98431c8ad98523631ae4a59f267346ea31f984".to_string(),
+                chain: "mainnet".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+    let top = json_text(&top);
+    assert_eq!(
+        top["subgraphDeployments"][0]["ipfsHash"],
+        UNISWAP_DEPLOYMENT
+    );
+    assert_eq!(
+        top["subgraphDeployments"][0]["queryFeesAmount"],
+        "1933467582710452861245"
+    );
+
+    // The timestamp variable differs from the recording, so the query alone is matched
+    let counts = server
+        .get_deployment_30day_query_counts(
+            Extensions::new(),
+            GetDeployment30DayQueryCountsRequest {
+                ipfs_hashes: vec![
+                    UNISWAP_ARBITRUM_DEPLOYMENT.to_string(),
+                    UNISWAP_DEPLOYMENT.to_string(),
+                ],
+            },
+        )
+        .await
+        .unwrap();
+    let counts = json_text(&counts);
+    assert_eq!(counts["deployments"][0]["ipfs_hash"], UNISWAP_DEPLOYMENT);
+    assert_eq!(counts["deployments"][0]["total_query_count"], 4640139);
+    assert_eq!(counts["deployments"][1]["data_points_count"], 2);
+}
+
+#[tokio::test]
+async fn test_query_tools_replay() {
+    let server = replay_server();
+    let expected = json!({
+        "data": {
+            "pools": [
+                {
+                    "id": "0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640",
+                    "feeTier": "500",
+                    "totalValueLockedUSD": "207432981.4471029370132465738926751"
+                },
+                {
+                    "id": "0x11b815efb8f581194ae79006d24e0d814b7697f6",
+                    "feeTier": "500",
+                    "totalValueLockedUSD": "61872954.22760354897611210837540392"
+                }
+            ]
+        }
+    });
+
+    let by_deployment = server
+        .execute_query_by_deployment_id(
+            Extensions::new(),
+            ExecuteQueryByDeploymentIdRequest {
+                deployment_id: "0x4d2b8f1a0f3c9e7b6a5d4c3b2a1908f7e6d5c4b3a29180f7e6d5c4b3a2918070"
+                    .to_string(),
+                query: POOLS_QUERY.to_string(),
+                variables: None,
+                freshness: None,
+                decode: None,
+                transform: None,
+                output_format: None,
+            },
+        )
+        .await
+        .unwrap();
+    let by_hash = server
+        .execute_query_by_ipfs_hash(
+            Extensions::new(),
+            ExecuteQueryByIpfsHashRequest {
+                ipfs_hash: UNISWAP_DEPLOYMENT.to_string(),
+                query: POOLS_QUERY.to_string(),
+                variables: None,
+                freshness: None,
+                decode: None,
+                transform: None,
+                output_format: None,
+            },
+        )
+        .await
+        .unwrap();
+    let by_subgraph = server
+        .execute_query_by_subgraph_id(
+            Extensions::new(),
+            ExecuteQueryBySubgraphIdRequest {
+                subgraph_id: UNISWAP_SUBGRAPH.to_string(),
+                query: POOLS_QUERY.to_string(),
+                variables: None,
+                freshness: None,
+                decode: None,
+                transform: None,
+                output_format: None,
+            },
+        )
+        .await
+        .unwrap();
+    for result in [&by_deployment, &by_hash, &by_subgraph] {
+        let body = json_text(result);
+        assert_eq!(body["data"], expected["data"]);
+        assert_eq!(body["freshness"]["block_number"], 21481602);
+        assert_eq!(body["freshness"]["deployment"], UNISWAP_DEPLOYMENT);
+    }
+
+    let err = server
+        .execute_query_by_subgraph_id(
+            Extensions::new(),
+            ExecuteQueryBySubgraphIdRequest {
+                subgraph_id: UNISWAP_SUBGRAPH.to_string(),
+                query: "{ pool(id: $id) { id } }".to_string(),
+                variables: None,
+                freshness: None,
+                decode: None,
+                transform: None,
+                output_format: None,
+            },
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.message,
+        "GraphQL error: Variable \"$id\" is not defined"
+    );
+}
+
+#[tokio::test]
+async fn test_recording_scrubs_api_key_and_replays() {
+    let path = std::env::temp_dir().join(format!(
+        "subgraph-mcp-recording-{}.ndjson",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+
+    let live = FixtureBackend::new().with_response(
+        GatewayTarget::QosOracle,
+        None,
+        GatewayResponse::json(
+            http::StatusCode::UNAUTHORIZED,
+            &json!({ "errors": [{ "message": "auth error: API key secret-key-123 not found" }] }),
+        ),
+    );
+    let recorder = RecordingBackend::new(Arc::new(live), &path);
+    let request = GraphQLRequest {
+        query: "{ subgraphDeployments { id } }".to_string(),
+        variables: None,
+    };
+    recorder
+        .query_qos_oracle("secret-key-123", "https://gateway.example/api", &request)
+        .await
+        .unwrap();
+
+    let recorded = std::fs::read_to_string(&path).unwrap();
+    assert!(!recorded.contains("secret-key-123"));
+    assert!(recorded.contains(SCRUBBED_API_KEY));
+
+    let replayed = ReplayBackend::from_file(&path)
+        .unwrap()
+        .query_qos_oracle("other-key", "https://gateway.example/api", &request)
+        .await
+        .unwrap();
+    assert_eq!(replayed.status, http::StatusCode::UNAUTHORIZED);
+    let body: Value = serde_json::from_slice(&replayed.body).unwrap();
+    assert_eq!(
+        body["errors"][0]["message"],
+        "auth error: API key <api-key> not found"
+    );
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_replay_matches_variables() {
+    let exchange = |variables: Value, symbol: &str| RecordedExchange {
+        target: GatewayTarget::NetworkSubgraph,
+        request: GraphQLRequest {
+            query: "query Token($id: ID!, $since: BigInt!) { token(id: $id) { symbol } }"
+                .to_string(),
+            variables: Some(variables),
+        },
+        response: RecordedResponse {
+            status: 200,
+            headers: Default::default(),
+            body: json!({ "data": { "token": { "symbol": symbol } } }),
+            text: false,
+        },
+    };
+    let backend = ReplayBackend::new(vec![
+        exchange(json!({ "id": "0x1", "since": "100" }), "GRT"),
+        exchange(json!({ "id": "0x2", "since": "100" }), "USDC"),
+    ])
+    .ignoring_variables(["since"]);
+    let replay = |variables: Value| {
+        let request = GraphQLRequest {
+            query: "query Token($id: ID!, $since: BigInt!) { token(id: $id) { symbol } }"
+                .to_string(),
+            variables: Some(variables),
+        };
+        let backend = &backend;
+        async move {
+            backend
+                .query_network_subgraph("key", "https://gateway.example/api", &request)
+                .await
+                .map(|response| serde_json::from_slice::<Value>(&response.body).unwrap())
+        }
+    };
+
+    let body = replay(json!({ "id": "0x2", "since": "200" }))
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["token"]["symbol"], "USDC");
+
+    // Another ID is never answered with a recording made for a different one
+    let err = replay(json!({ "id": "0x3", "since": "100" }))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("No recorded exchange"));
+}
@@ -0,0 +1,156 @@
+use rmcp::model::{Extensions, RawContent};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use subgraph_mcp::backend::FixtureBackend;
+use subgraph_mcp::schema_summary::{collection_field_name, single_field_name, summarize_schema};
+use subgraph_mcp::server::SubgraphServer;
+use subgraph_mcp::types::SummarizeSchemaRequest;
+
+const SCHEMA: &str = r#"
+enum PoolType {
+  Stable
+  Weighted
+}
+
+"""
+A token traded in pools
+"""
+type Token @entity {
+  id: ID!
+  symbol: String!
+  decimals: BigInt!
+  pools: [Pool!]! @derivedFrom(field: "token0")
+}
+
+type Pool @entity {
+  id: ID!
+  poolType: PoolType!
+  token0: Token!
+  token1: Token!
+  swaps: [Swap!]! @derivedFrom(field: "pool")
+  totalValueLockedUSD: BigDecimal!
+}
+
+type Swap @entity(immutable: true) {
+  id: ID!
+  pool: Pool!
+  amountUSD: BigDecimal!
+  timestamp: BigInt!
+}
+
+type Price @entity(timeseries: true) {
+  id: Int8!
+  timestamp: Timestamp!
+  token: Token!
+  priceUSD: BigDecimal!
+}
+
+type PriceStats @aggregation(intervals: ["hour", "day"], source: "Price") {
+  id: Int8!
+  timestamp: Timestamp!
+  token: Token!
+  avgPrice: BigDecimal! @aggregate(fn: "avg", arg: "priceUSD")
+}
+"#;
+
+#[test]
+fn test_query_field_names() {
+    assert_eq!(single_field_name("Pool"), "pool");
+    assert_eq!(single_field_name("TokenDayData"), "tokenDayData");
+    assert_eq!(single_field_name("NFTSale"), "nftSale");
+    assert_eq!(single_field_name("ERC20Token"), "erc20Token");
+    assert_eq!(collection_field_name("Pool"), "pools");
+    assert_eq!(collection_field_name("TokenDayData"), "tokenDayDatas");
+    assert_eq!(collection_field_name("Registry"), "registries");
+    assert_eq!(collection_field_name("Day"), "days");
+    assert_eq!(collection_field_name("Match"), "matches");
+}
+
+#[test]
+fn test_summarize_schema() {
+    let summary = json!(summarize_schema(SCHEMA).unwrap());
+    let entity = |name: &str| -> Value {
+        summary["entities"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|e| e["name"] == name)
+            .unwrap()
+            .clone()
+    };
+
+    let token = entity("Token");
+    assert_eq!(token["kind"], "entity");
+    assert!(token.get("immutable").is_none());
+    assert_eq!(
+        token["fields"],
+        json!([
+            { "name": "id", "type": "ID!" },
+            { "name": "symbol", "type": "String!" },
+            { "name": "decimals", "type": "BigInt!" },
+        ])
+    );
+    assert_eq!(
+        token["relationships"],
+        json!([{ "field": "pools", "target": "Pool", "list": true, "derived_from": "token0" }])
+    );
+    assert_eq!(
+        token["query_fields"],
+        json!({ "single": "token", "collection": "tokens", "where": "Token_filter", "order_by": "Token_orderBy" })
+    );
+
+    let pool = entity("Pool");
+    assert!(pool["fields"]
+        .as_array()
+        .unwrap()
+        .contains(&json!({ "name": "poolType", "type": "PoolType!" })));
+    assert_eq!(entity("Swap")["immutable"], true);
+    assert_eq!(entity("Price")["kind"], "timeseries");
+    assert_eq!(entity("Price")["immutable"], true);
+
+    let stats = entity("PriceStats");
+    assert_eq!(stats["kind"], "aggregation");
+    assert_eq!(stats["aggregation_intervals"], json!(["hour", "day"]));
+    assert!(stats["query_fields"].get("single").is_none());
+    assert_eq!(
+        stats["query_fields"]["required_arguments"],
+        json!(["interval: Aggregation_interval!"])
+    );
+
+    assert_eq!(
+        summary["enums"],
+        json!([{ "name": "PoolType", "values": ["Stable", "Weighted"] }])
+    );
+    assert_eq!(summary["entry_points"][0], "tokens");
+    assert_eq!(summary["entry_points"][1], "pools");
+}
+
+#[tokio::test]
+async fn test_summarize_schema_tool() {
+    let backend = Arc::new(FixtureBackend::new().with_network_subgraph_data(
+        "DeploymentSchemaByIpfsHash",
+        json!({ "subgraphDeployments": [{ "manifest": { "schema": { "schema": SCHEMA } } }] }),
+    ));
+    let server = SubgraphServer::builder()
+        .backend(backend)
+        .api_key("test-key")
+        .build();
+
+    let result = server
+        .summarize_schema(
+            Extensions::new(),
+            SummarizeSchemaRequest {
+                deployment: "QmTZ8ejXJxRo7vDBS4uwqBeGoxLSWbhaA7oXa1RvxunLy7".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+    let RawContent::Text(text) = &result.content[0].raw else {
+        panic!("expected text content");
+    };
+    let summary: Value = serde_json::from_str(&text.text).unwrap();
+    assert_eq!(summary["entities"].as_array().unwrap().len(), 5);
+
+    let err = summarize_schema("type {").unwrap_err();
+    assert!(err.to_string().contains("Invalid schema"));
+}
@@ -0,0 +1,113 @@
+use rmcp::model::{Extensions, RawContent};
+use serde_json::json;
+use std::{sync::Arc, time::Duration};
+use subgraph_mcp::backend::FixtureBackend;
+use subgraph_mcp::schema_cache::SchemaCache;
+use subgraph_mcp::types::GetSchemaByIpfsHashRequest;
+use subgraph_mcp::SubgraphServerBuilder;
+
+fn schema_fixture() -> Arc<FixtureBackend> {
+    Arc::new(FixtureBackend::new().with_network_subgraph_data(
+        "DeploymentSchemaByIpfsHash",
+        json!({
+            "subgraphDeployments": [
+                { "manifest": { "schema": { "schema": "type Token @entity { id: ID! }" } } }
+            ]
+        }),
+    ))
+}
+
+fn schema_text(result: &rmcp::model::CallToolResult) -> &str {
+    match &result.content[0].raw {
+        RawContent::Text(text) => &text.text,
+        _ => panic!("expected text content"),
+    }
+}
+
+#[tokio::test]
+async fn test_builder_uses_explicit_api_key_and_gateways() {
+    let server = SubgraphServerBuilder::new()
+        .backend(schema_fixture())
+        .gateways(Default::default())
+        .gateway("local", "http://localhost:7700/api")
+        .default_gateway("local")
+        .api_key("embedded-key")
+        .build();
+
+    // No Bearer token in the request, so the configured key is used
+    let result = server
+        .get_schema_by_ipfs_hash(
+            Extensions::new(),
+            GetSchemaByIpfsHashRequest {
+                ipfs_hash: "QmToken".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(schema_text(&result), "type Token @entity { id: ID! }");
+
+    let (parts, _) = http::Request::builder()
+        .header("x-gateway-id", "edgeandnode")
+        .body(())
+        .unwrap()
+        .into_parts();
+    let mut extensions = Extensions::new();
+    extensions.insert(parts);
+    let err = server
+        .get_schema_by_ipfs_hash(
+            extensions,
+            GetSchemaByIpfsHashRequest {
+                ipfs_hash: "QmToken".to_string(),
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("Valid gateway IDs are: local"));
+}
+
+#[tokio::test]
+async fn test_builder_without_api_key_rejects_calls() {
+    let server = SubgraphServerBuilder::new()
+        .backend(schema_fixture())
+        .build();
+
+    let err = server
+        .get_schema_by_ipfs_hash(
+            Extensions::new(),
+            GetSchemaByIpfsHashRequest {
+                ipfs_hash: "QmToken".to_string(),
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("API key not found"));
+}
+
+#[tokio::test]
+async fn test_schema_cache_shared_between_servers() {
+    let backend = schema_fixture();
+    let cache = Arc::new(SchemaCache::new(Duration::from_secs(60), 16));
+    let build = || {
+        SubgraphServerBuilder::new()
+            .backend(backend.clone())
+            .api_key("embedded-key")
+            .schema_cache(cache.clone())
+            .build()
+    };
+
+    for server in [build(), build()] {
+        let result = server
+            .get_schema_by_ipfs_hash(
+                Extensions::new(),
+                GetSchemaByIpfsHashRequest {
+                    ipfs_hash: "QmToken".to_string(),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(schema_text(&result), "type Token @entity { id: ID! }");
+    }
+
+    assert_eq!(backend.requests().len(), 1);
+    assert_eq!(cache.len(), 1);
+}
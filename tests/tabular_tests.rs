@@ -0,0 +1,152 @@
+use rmcp::model::{Extensions, RawContent};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use subgraph_mcp::backend::FixtureBackend;
+use subgraph_mcp::server::SubgraphServer;
+use subgraph_mcp::tabular::{format_result, result_contents, ResultFormat};
+use subgraph_mcp::types::ExecuteQueryBySubgraphIdRequest;
+
+const UNISWAP_SUBGRAPH: &str = "5zvR82QoaXYFyDEKLZ9t6v9adgnptxYpKpSbxtgVENFV";
+
+fn pools() -> Value {
+    json!({
+        "data": {
+            "pools": [
+                { "id": "0x1", "token0": { "symbol": "WETH" }, "feeTier": 500 },
+                { "id": "0x2", "token0": { "symbol": "A|B, \"C\"" }, "feeTier": null },
+            ]
+        },
+        "freshness": { "block_number": 1, "warnings": ["The deployment has indexing errors."] }
+    })
+}
+
+#[test]
+fn test_markdown_and_csv() {
+    assert_eq!(
+        format_result(&pools(), ResultFormat::Markdown),
+        "| feeTier | id | token0.symbol |\n\
+         | --- | --- | --- |\n\
+         | 500 | 0x1 | WETH |\n\
+         |  | 0x2 | A\\|B, \"C\" |"
+    );
+    assert_eq!(
+        format_result(&pools(), ResultFormat::Csv),
+        "feeTier,id,token0.symbol\n500,0x1,WETH\n,0x2,\"A|B, \"\"C\"\"\""
+    );
+    assert_eq!(
+        format_result(&json!({ "data": { "pools": [] } }), ResultFormat::Markdown),
+        "(no rows)"
+    );
+
+    // Freshness warnings follow in an item of their own, except in JSON
+    for format in [
+        ResultFormat::Markdown,
+        ResultFormat::Csv,
+        ResultFormat::Ndjson,
+    ] {
+        let contents = result_contents(&pools(), format);
+        assert_eq!(contents.len(), 2);
+        let RawContent::Text(warnings) = &contents[1].raw else {
+            panic!("expected text content");
+        };
+        assert_eq!(
+            warnings.text,
+            "warning: The deployment has indexing errors."
+        );
+    }
+    assert_eq!(result_contents(&pools(), ResultFormat::Json).len(), 1);
+}
+
+#[test]
+fn test_ndjson_and_json_fallback() {
+    let ndjson = format_result(&pools(), ResultFormat::Ndjson);
+    let lines: Vec<Value> = ndjson
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(
+        lines[0],
+        json!({ "id": "0x1", "token0.symbol": "WETH", "feeTier": 500 })
+    );
+    assert_eq!(lines.len(), 2);
+
+    let nested = json!({ "data": { "pools": [{ "id": "0x1", "swaps": [{ "id": "s" }] }] } });
+    let fallback: Value = serde_json::from_str(&format_result(&nested, ResultFormat::Csv)).unwrap();
+    assert_eq!(fallback["data"], nested["data"]);
+    assert_eq!(
+        fallback["format_fallback"],
+        "Returned as JSON instead of csv: the `swaps` column holds a list"
+    );
+
+    let single: Value = serde_json::from_str(&format_result(
+        &json!({ "data": { "pool": { "id": "0x1" } } }),
+        ResultFormat::Markdown,
+    ))
+    .unwrap();
+    assert!(single["format_fallback"]
+        .as_str()
+        .unwrap()
+        .ends_with("the result holds no list"));
+
+    let several: Value = serde_json::from_str(&format_result(
+        &json!({ "data": { "tokens": [{ "id": "0x2" }], "pools": [{ "id": "0x1" }] } }),
+        ResultFormat::Ndjson,
+    ))
+    .unwrap();
+    assert!(several["format_fallback"]
+        .as_str()
+        .unwrap()
+        .contains("several lists (`pools`, `tokens`)"));
+}
+
+#[tokio::test]
+async fn test_query_tool_output_format() {
+    let backend = Arc::new(FixtureBackend::new().with_deployment_data(
+        "subgraphs/id",
+        UNISWAP_SUBGRAPH,
+        pools()["data"].clone(),
+    ));
+    let server = SubgraphServer::builder()
+        .backend(backend)
+        .api_key("test-key")
+        .build();
+    let request = |output_format, transform: Option<Value>| ExecuteQueryBySubgraphIdRequest {
+        subgraph_id: UNISWAP_SUBGRAPH.to_string(),
+        query: "{ pools { id token0 { symbol } feeTier } }".to_string(),
+        variables: None,
+        freshness: Some(false),
+        decode: None,
+        transform: transform.map(|spec| serde_json::from_value(spec).unwrap()),
+        output_format: Some(output_format),
+    };
+    let text = |result: rmcp::model::CallToolResult| -> String {
+        let RawContent::Text(text) = &result.content[0].raw else {
+            panic!("expected text content");
+        };
+        text.text.clone()
+    };
+
+    let csv = text(
+        server
+            .execute_query_by_subgraph_id(Extensions::new(), request(ResultFormat::Csv, None))
+            .await
+            .unwrap(),
+    );
+    assert_eq!(csv.lines().next(), Some("feeTier,id,token0.symbol"));
+    assert_eq!(csv.lines().count(), 3);
+
+    // Transformed rows are rendered as they are
+    let markdown = text(
+        server
+            .execute_query_by_subgraph_id(
+                Extensions::new(),
+                request(
+                    ResultFormat::Markdown,
+                    Some(json!({ "fields": ["id"], "limit": 1 })),
+                ),
+            )
+            .await
+            .unwrap(),
+    );
+    assert_eq!(markdown, "| id |\n| --- |\n| 0x1 |");
+}
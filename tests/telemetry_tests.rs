@@ -0,0 +1,49 @@
+use opentelemetry::{global, trace::TracerProvider as _};
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider};
+use subgraph_mcp::telemetry::{current_request_id, inject_trace_context, with_request_id};
+use tracing_subscriber::layer::SubscriberExt;
+
+#[test]
+fn test_traceparent_injected_for_current_span() {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    let provider = SdkTracerProvider::builder().build();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+
+    let client = reqwest::Client::new();
+    let request = tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("gateway.request");
+        let _entered = span.enter();
+        inject_trace_context(client.post("http://localhost/graphql"))
+            .build()
+            .unwrap()
+    });
+
+    let traceparent = request
+        .headers()
+        .get("traceparent")
+        .expect("traceparent header")
+        .to_str()
+        .unwrap();
+    assert!(traceparent.starts_with("00-"));
+    assert_eq!(traceparent.split('-').count(), 4);
+}
+
+#[test]
+fn test_no_traceparent_without_active_trace() {
+    let client = reqwest::Client::new();
+    let request = inject_trace_context(client.post("http://localhost/graphql"))
+        .build()
+        .unwrap();
+    assert!(request.headers().get("traceparent").is_none());
+}
+
+#[tokio::test]
+async fn test_request_id_scoped_to_task() {
+    assert!(current_request_id().is_none());
+
+    let seen = with_request_id("req-1".to_string(), async { current_request_id() }).await;
+    assert_eq!(seen.as_deref(), Some("req-1"));
+
+    assert!(current_request_id().is_none());
+}
@@ -0,0 +1,190 @@
+use rmcp::model::{Extensions, RawContent};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use subgraph_mcp::backend::FixtureBackend;
+use subgraph_mcp::server::SubgraphServer;
+use subgraph_mcp::transform::{Transform, TransformSpec};
+use subgraph_mcp::types::ExecuteQueryByIpfsHashRequest;
+
+const UNISWAP_DEPLOYMENT: &str = "QmTZ8ejXJxRo7vDBS4uwqBeGoxLSWbhaA7oXa1RvxunLy7";
+
+fn transform(spec: Value) -> Transform {
+    Transform::new(serde_json::from_value::<TransformSpec>(spec).unwrap()).unwrap()
+}
+
+fn swaps() -> Value {
+    json!({
+        "pools": [
+            { "id": "0x1", "token0": { "symbol": "WETH" }, "swaps": [
+                { "amountUSD": "0.1", "amount0": "340282366920938463463374607431768211455" },
+                { "amountUSD": "0.2", "amount0": "1" },
+            ] },
+            { "id": "0x2", "token0": { "symbol": "USDC" }, "swaps": [
+                { "amountUSD": "10", "amount0": "9" },
+            ] },
+            { "id": "0x3", "token0": { "symbol": "WETH" }, "swaps": [] },
+        ],
+        "_meta": { "block": { "number": 1 } }
+    })
+}
+
+#[test]
+fn test_select_flatten_and_project() {
+    let rows = transform(json!({
+        "flatten": true,
+        "fields": ["id", "token0.symbol"],
+        "sort_by": "id",
+        "limit": 2,
+    }))
+    .rows(&swaps())
+    .unwrap();
+    assert_eq!(
+        rows,
+        vec![
+            json!({ "id": "0x3", "token0.symbol": "WETH" }),
+            json!({ "id": "0x2", "token0.symbol": "USDC" }),
+        ]
+    );
+
+    let rows = transform(json!({ "select": "$.pools[*].swaps[*].amountUSD" }))
+        .rows(&swaps())
+        .unwrap();
+    assert_eq!(rows, vec![json!("0.1"), json!("0.2"), json!("10")]);
+    let rows = transform(json!({ "select": "pools[1].token0" }))
+        .rows(&swaps())
+        .unwrap();
+    assert_eq!(rows, vec![json!({ "symbol": "USDC" })]);
+
+    let err = transform(json!({}))
+        .rows(&json!({ "a": [], "b": [] }))
+        .unwrap_err();
+    assert!(err.to_string().contains("set `select` to one of `a`, `b`"));
+    assert!(Transform::new(TransformSpec {
+        select: Some("pools[".to_string()),
+        ..Default::default()
+    })
+    .is_err());
+}
+
+#[test]
+fn test_group_and_aggregate_exactly() {
+    let rows = transform(json!({
+        "select": "pools",
+        "group_by": ["token0.symbol"],
+        "sort_by": "count",
+    }))
+    .rows(&swaps())
+    .unwrap();
+    assert_eq!(rows[0], json!({ "token0.symbol": "WETH", "count": 2 }));
+
+    let rows = transform(json!({
+        "select": "pools[*].swaps",
+        "aggregate": [
+            { "op": "sum", "field": "amountUSD", "as": "volume" },
+            { "op": "avg", "field": "amountUSD" },
+            { "op": "sum", "field": "amount0" },
+            { "op": "max", "field": "amount0" },
+            { "op": "min", "field": "amount0" },
+            { "op": "count" },
+        ],
+    }))
+    .rows(&swaps())
+    .unwrap();
+    assert_eq!(
+        rows,
+        vec![json!({
+            "volume": "10.3",
+            "avg_amountUSD": "3.433333333333333333333333333333333",
+            "sum_amount0": "340282366920938463463374607431768211465",
+            "max_amount0": "340282366920938463463374607431768211455",
+            "min_amount0": "1",
+            "count": 3,
+        })]
+    );
+
+    let err = transform(json!({ "aggregate": [{ "op": "sum", "field": "id" }] }))
+        .rows(&swaps())
+        .unwrap_err();
+    assert!(err.to_string().contains("\"0x1\" is not a number"));
+
+    // Numbers sort before text and text before other values, and exponent
+    // notation is text rather than a number to expand
+    let rows = transform(json!({ "sort_by": "v", "sort_direction": "asc" }))
+        .rows(&json!({ "rows": [
+            { "v": "1a" }, { "v": true }, { "v": "10" }, { "v": "1e-999999999" }, { "v": 9 },
+        ] }))
+        .unwrap();
+    let sorted: Vec<&Value> = rows.iter().map(|row| &row["v"]).collect();
+    assert_eq!(
+        sorted,
+        vec![
+            &json!(9),
+            &json!("10"),
+            &json!("1a"),
+            &json!("1e-999999999"),
+            &json!(true)
+        ]
+    );
+    let err = transform(json!({ "aggregate": [{ "op": "sum", "field": "v" }] }))
+        .rows(&json!({ "rows": [{ "v": "1" }, { "v": "1e-999999999" }] }))
+        .unwrap_err();
+    assert!(err.to_string().contains("is not a number"));
+}
+
+#[tokio::test]
+async fn test_query_tool_applies_transform() {
+    let backend = Arc::new(FixtureBackend::new().with_deployment_data(
+        "deployments/id",
+        UNISWAP_DEPLOYMENT,
+        swaps(),
+    ));
+    let server = SubgraphServer::builder()
+        .backend(backend.clone())
+        .api_key("test-key")
+        .build();
+    let request = |transform: Value| ExecuteQueryByIpfsHashRequest {
+        ipfs_hash: UNISWAP_DEPLOYMENT.to_string(),
+        query: "{ pools { id token0 { symbol } swaps { amountUSD amount0 } } }".to_string(),
+        variables: None,
+        freshness: Some(false),
+        decode: None,
+        transform: Some(serde_json::from_value(transform).unwrap()),
+        output_format: None,
+    };
+
+    let result = server
+        .execute_query_by_ipfs_hash(
+            Extensions::new(),
+            request(json!({
+                "select": "pools",
+                "group_by": ["token0.symbol"],
+                "aggregate": [{ "op": "count", "field": "swaps" }],
+                "sort_by": "token0.symbol",
+                "sort_direction": "asc",
+            })),
+        )
+        .await
+        .unwrap();
+    let RawContent::Text(text) = &result.content[0].raw else {
+        panic!("expected text content");
+    };
+    let body: Value = serde_json::from_str(&text.text).unwrap();
+    assert_eq!(
+        body["data"],
+        json!([
+            { "token0.symbol": "USDC", "count_swaps": 1 },
+            { "token0.symbol": "WETH", "count_swaps": 2 },
+        ])
+    );
+
+    // An invalid spec is rejected before the query is sent
+    let err = server
+        .execute_query_by_ipfs_hash(
+            Extensions::new(),
+            request(json!({ "fields": ["id"], "group_by": ["id"] })),
+        )
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("cannot be combined"));
+    assert_eq!(backend.requests().len(), 1);
+}
@@ -0,0 +1,66 @@
+use std::time::Duration;
+use subgraph_mcp::usage::{BudgetLimits, KeyUsage, SessionUsage};
+use subgraph_mcp::SubgraphError;
+
+#[test]
+fn test_session_query_limit() {
+    let usage = SessionUsage::new(BudgetLimits {
+        max_queries: Some(2),
+        max_fees_grt: None,
+    });
+
+    // A query the gateway never answered is given back
+    drop(usage.reserve_query().unwrap());
+    assert_eq!(usage.snapshot().query_count, 0);
+
+    usage.reserve_query().unwrap().confirm();
+    usage.reserve_query().unwrap().confirm();
+    assert!(matches!(
+        usage.reserve_query(),
+        Err(SubgraphError::BudgetExceeded(_))
+    ));
+    assert_eq!(usage.snapshot().query_count, 2);
+}
+
+#[test]
+fn test_session_fee_limit() {
+    let usage = SessionUsage::new(BudgetLimits {
+        max_queries: None,
+        max_fees_grt: Some(0.01),
+    });
+
+    usage.reserve_query().unwrap().confirm();
+    usage.record_fee(0.006);
+    usage.reserve_query().unwrap().confirm();
+    usage.record_fee(0.006);
+
+    let err = usage.reserve_query().unwrap_err();
+    assert!(err.to_string().contains("Session fee limit reached"));
+
+    let snapshot = usage.snapshot();
+    assert_eq!(snapshot.query_count, 2);
+    assert_eq!(snapshot.fee_reports, 2);
+}
+
+#[test]
+fn test_key_usage_is_bounded() {
+    let usage = KeyUsage::new(2, Duration::from_secs(3600));
+    usage.record_query("a");
+    usage.record_query("b");
+    usage.record_query("a");
+    usage.record_query("c");
+    assert_eq!(usage.snapshot("a").query_count, 2);
+    assert_eq!(usage.snapshot("b").query_count, 0);
+    assert_eq!(usage.snapshot("c").query_count, 1);
+
+    let expiring = KeyUsage::new(10, Duration::ZERO);
+    expiring.record_fee("a", 0.5);
+    assert_eq!(expiring.snapshot("a").fee_reports, 0);
+}
+
+#[test]
+fn test_sessions_have_distinct_ids() {
+    let a = SessionUsage::new(BudgetLimits::default());
+    let b = SessionUsage::new(BudgetLimits::default());
+    assert_ne!(a.session_id(), b.session_id());
+}